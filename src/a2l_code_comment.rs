@@ -1,42 +1,70 @@
-use regex::Regex;
 use a2lfile::*;
 
+use crate::a2l_comment_parser::*;
+use crate::diagnostics::Diagnostic;
+use crate::error::SourceLocation;
+
 #[derive(Debug, PartialEq)]
 pub enum A2lType {
     Measurement,
     Characteristic,
-    Unknown, // Fallback for unsupported or missing types
+    AxisPts,
+    // unsupported or missing type
+    Unknown,
 }
 
 impl A2lType {
-    // Function to parse a string into an A2lType
+    // the keyword of an a2l-type statement, compared as a whole
     pub fn from_str(type_str: &str) -> Self {
-        if type_str.to_lowercase().contains("measurement") {
-            A2lType::Measurement
-        } else if type_str.to_lowercase().contains("characteristic") {
-            A2lType::Characteristic
-        } else {
-            A2lType::Unknown
+        match type_str.to_lowercase().replace('-', "_").as_str() {
+            "measurement" => A2lType::Measurement,
+            "characteristic" => A2lType::Characteristic,
+            "axis_pts" | "axispts" => A2lType::AxisPts,
+            _ => A2lType::Unknown,
         }
     }
 }
 
+// axis description of a curve, map, cuboid or cube
+#[derive(Debug, Clone, PartialEq)]
+pub struct A2lAxis {
+    pub input_quantity: String,
+    pub conversion: String,
+    pub max_axis_points: u16,
+    pub lower_limit: f64,
+    pub upper_limit: f64,
+    // name of the AXIS_PTS of a COM_AXIS, the other values are taken from it
+    pub axis_pts_ref: Option<String>,
+}
 
 pub struct A2lCodeComment {
     pub a2l_on: bool,
     pub a2l_type: A2lType, // Use the enum here
     pub a2l_characteristic_type: CharacteristicType, // Fully qualified path
     pub a2l_description: String,
-    pub a2l_min: f64,
-    pub a2l_max: f64,
-    pub a2l_linear_coeffs: String,
-    pub a2l_rat_func_coeffs: String,
+    // physical limits, the limits of the data type are used for the ones not given
+    pub a2l_min: Option<f64>,
+    pub a2l_max: Option<f64>,
+    // a and b of phys = a * int + b, empty if not given
+    pub a2l_linear_coeffs: Vec<f64>,
+    // a to f of int = (a * phys^2 + b * phys + c) / (d * phys^2 + e * phys + f), empty if not given
+    pub a2l_rat_func_coeffs: Vec<f64>,
+    // value and text pairs of a verbal table, empty if not given
+    pub a2l_vtab: Vec<(f64, String)>,
     pub a2l_display_identifier: String,
     pub a2l_group: String,
     pub a2l_max_refresh: String,
     pub a2l_read_only: bool,
     pub a2l_read_write: bool,
     pub a2l_unit: String,
+    pub a2l_format: String,
+    pub a2l_axes: Vec<A2lAxis>,
+    pub a2l_matrix_dim: Vec<u16>,
+    pub a2l_input_quantity: String,
+    // byte order of the variable if it differs from the one of the target
+    pub a2l_byte_order: Option<ByteOrderEnum>,
+    // storage order of the values of blocks and maps
+    pub a2l_index_mode: IndexMode,
 }
 
 impl A2lCodeComment {
@@ -46,136 +74,425 @@ impl A2lCodeComment {
             a2l_type: A2lType::Unknown,
             a2l_characteristic_type: CharacteristicType::Value,
             a2l_description: String::new(),
-            a2l_min: 0.0,
-            a2l_max: 0.0,
-            a2l_linear_coeffs: String::new(),
-            a2l_rat_func_coeffs: String::new(),
+            a2l_min: None,
+            a2l_max: None,
+            a2l_linear_coeffs: Vec::new(),
+            a2l_rat_func_coeffs: Vec::new(),
+            a2l_vtab: Vec::new(),
             a2l_display_identifier: String::new(),
             a2l_group: String::new(),
             a2l_max_refresh: String::new(),
             a2l_read_only: false,
             a2l_read_write: false,
             a2l_unit: String::new(),
+            a2l_format: String::new(),
+            a2l_axes: Vec::new(),
+            a2l_matrix_dim: Vec::new(),
+            a2l_input_quantity: String::new(),
+            a2l_byte_order: None,
+            a2l_index_mode: IndexMode::RowDir,
         }
     }
 
+    // parse a comment without a source file, e.g. in tests
+    #[cfg(test)]
     pub fn from_comment(comment: &str) -> Self {
-        // a comment is multiple lines
+        A2lCodeComment::parse(comment, &SourceLocation::new("", 1, 1), &mut Vec::new())
+    }
+
+    // parse a comment which starts at the given location in the source file
+    // mistakes in the annotations are added to diagnostics and the default value is kept
+    pub fn parse(comment: &str, location: &SourceLocation, diagnostics: &mut Vec<Diagnostic>) -> Self {
         let mut a2l_code_comment = A2lCodeComment::new();
-        for line in comment.lines() {
-            // check for a2l on or off search witch regex
-            let re_on = Regex::new(r"a2l\s+on").unwrap();
-            if re_on.is_match(line) {
-                a2l_code_comment.a2l_on = true;
+        // needed to check the number of axes after all statements are known
+        let mut characteristic_type_token: Option<Token> = None;
+        let mut first_axis_token: Option<Token> = None;
+        // only one conversion can be used
+        let mut conversion_token: Option<Token> = None;
+        for statement in parse_statements(comment, location, diagnostics) {
+            let keyword = &statement.keyword;
+            // flags don't have arguments, all other keywords need at least one
+            let is_flag = matches!(keyword.text.as_str(), "a2l-read-only" | "a2l-read-write");
+            if !is_flag && statement.arguments.is_empty() {
+                diagnostics.push(keyword.diagnostic(location, format!("missing value for '{}'", keyword.text)));
+                continue;
             }
-            let re_off = Regex::new(r"a2l\s+off").unwrap();
-            if re_off.is_match(line) {
-                a2l_code_comment.a2l_on = false;
+            if is_flag && let Some(argument) = statement.arguments.first() {
+                diagnostics.push(argument.diagnostic(
+                    location,
+                    format!("'{}' doesn't take a value", keyword.text),
+                ));
             }
-            // check for a2l type measurement or characteristic
-            if line.contains("a2l-type") {
-                a2l_code_comment.a2l_type = A2lType::from_str(&line);
-            }
-            // check for a2l characteristic type
-            // Todo: add more types Maps, Curves,etc
-            if line.contains("a2l-characteristic-type") {
-                let re = Regex::new(r"a2l-characteristic-type\s+(\w+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_characteristic_type = match captures[1].to_lowercase().as_str() {
+            let value = statement.arguments.first();
+            match keyword.text.as_str() {
+                // check for a2l on or off
+                "a2l" => match statement.text().as_str() {
+                    "on" => a2l_code_comment.a2l_on = true,
+                    "off" => a2l_code_comment.a2l_on = false,
+                    text => diagnostics.push(keyword.diagnostic(
+                        location,
+                        format!("expected 'a2l on' or 'a2l off', found 'a2l {}'", text),
+                    )),
+                },
+                // check for a2l type measurement or characteristic
+                "a2l-type" => {
+                    let value = value.unwrap();
+                    if let Some(extra) = statement.arguments.get(1) {
+                        diagnostics.push(extra.diagnostic(
+                            location,
+                            format!("a2l-type takes one value, found '{}'", statement.text()),
+                        ));
+                        continue;
+                    }
+                    a2l_code_comment.a2l_type = A2lType::from_str(&value.text);
+                    if a2l_code_comment.a2l_type == A2lType::Unknown {
+                        diagnostics.push(value.diagnostic(
+                            location,
+                            format!(
+                                "unsupported a2l-type '{}', expected measurement, characteristic or axis_pts",
+                                value.text
+                            ),
+                        ));
+                    }
+                }
+                // check for a2l characteristic type
+                "a2l-characteristic-type" => {
+                    let value = value.unwrap();
+                    a2l_code_comment.a2l_characteristic_type = match value.text.to_lowercase().as_str() {
                         "ascii" => CharacteristicType::Ascii,
                         "value" => CharacteristicType::Value,
-                        "valblk" => CharacteristicType::ValBlk,
-                        _ => CharacteristicType::Value, // Default case
+                        "valblk" | "val_blk" => CharacteristicType::ValBlk,
+                        "curve" => CharacteristicType::Curve,
+                        "map" => CharacteristicType::Map,
+                        "cuboid" => CharacteristicType::Cuboid,
+                        "cube_4" => CharacteristicType::Cube4,
+                        "cube_5" => CharacteristicType::Cube5,
+                        _ => {
+                            diagnostics.push(value.diagnostic(
+                                location,
+                                format!(
+                                    "unsupported a2l-characteristic-type '{}', expected ascii, value, val_blk, curve, map, cuboid, cube_4 or cube_5",
+                                    value.text
+                                ),
+                            ));
+                            CharacteristicType::Value // Default case
+                        }
                     };
+                    characteristic_type_token = Some(value.clone());
                 }
-            }
-            // check for a2l description
-            if line.contains("a2l-description") {
-                let re = Regex::new(r"a2l-description\s+(.+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_description = captures[1].to_string();
-                }               
-            }
-            // check for a2l min (float or integer)
-            if line.contains("a2l-min") {
-                let re = Regex::new(r"a2l-min\s+([-+]?\d*\.?\d+([eE][-+]?\d+)?)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    if let Ok(value) = captures[1].parse::<f64>() {
-                        a2l_code_comment.a2l_min = value;
-                    } else {
-                        eprintln!("Failed to parse a2l-min value");
+                // check for an axis description: input quantity, conversion, max axis points, lower and upper limit
+                "a2l-axis" => {
+                    if first_axis_token.is_none() {
+                        first_axis_token = Some(keyword.clone());
+                    }
+                    if let Some(axis) = parse_axis(&statement, location, diagnostics) {
+                        a2l_code_comment.a2l_axes.push(axis);
                     }
                 }
-            }
-            // check for a2l max (float or integer)
-            if line.contains("a2l-max") {
-                let re = Regex::new(r"a2l-max\s+([-+]?\d*\.?\d+([eE][-+]?\d+)?)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    if let Ok(value) = captures[1].parse::<f64>() {
-                        a2l_code_comment.a2l_max = value;
-                    } else {
-                        eprintln!("Failed to parse a2l-max value");
+                // check for a reference to shared axis points
+                "a2l-axis-ref" => {
+                    if first_axis_token.is_none() {
+                        first_axis_token = Some(keyword.clone());
                     }
+                    a2l_code_comment.a2l_axes.push(A2lAxis {
+                        input_quantity: "NO_INPUT_QUANTITY".to_string(),
+                        conversion: "NO_COMPU_METHOD".to_string(),
+                        max_axis_points: 0,
+                        lower_limit: 0.0,
+                        upper_limit: 0.0,
+                        axis_pts_ref: Some(value.unwrap().text.clone()),
+                    });
                 }
-            }
-            // check for a2l linear coeffs
-            if line.contains("a2l-linear-coeffs") {
-                let re = Regex::new(r"a2l-linear-coeffs\s+(.+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_linear_coeffs = captures[1].to_string();
+                "a2l-input-quantity" => a2l_code_comment.a2l_input_quantity = statement.text(),
+                // check for the dimensions of a value block
+                "a2l-matrix-dim" => {
+                    let mut matrix_dim = Vec::new();
+                    for argument in &statement.arguments {
+                        match argument.text.parse::<u16>() {
+                            Ok(dim) if dim > 0 => matrix_dim.push(dim),
+                            _ => diagnostics.push(argument.diagnostic(
+                                location,
+                                format!("'{}' is not a valid dimension", argument.text),
+                            )),
+                        }
+                    }
+                    if matrix_dim.len() == statement.arguments.len() {
+                        a2l_code_comment.a2l_matrix_dim = matrix_dim;
+                    }
                 }
-            }
-            // check for a2l rat func coeffs
-            if line.contains("a2l-rat-func-coeffs") {
-                let re = Regex::new(r"a2l-rat-func-coeffs\s+(.+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_rat_func_coeffs = captures[1].to_string();
+                "a2l-description" => a2l_code_comment.a2l_description = statement.text(),
+                // check for a2l min and max (float or integer)
+                "a2l-min" => {
+                    if let Some(number) = parse_number(value.unwrap(), location, diagnostics) {
+                        a2l_code_comment.a2l_min = Some(number);
+                    }
                 }
-            }
-            // check for a2l display identifier
-            if line.contains("a2l-display-identifier") {
-                let re = Regex::new(r"a2l-display-identifier\s+(.+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_display_identifier = captures[1].to_string();
+                "a2l-max" => {
+                    if let Some(number) = parse_number(value.unwrap(), location, diagnostics) {
+                        a2l_code_comment.a2l_max = Some(number);
+                    }
                 }
-            }
-            // check for a2l group
-            if line.contains("a2l-group") {
-                let re = Regex::new(r"a2l-group\s+(.+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_group = captures[1].to_string();
+                "a2l-linear-coeffs" | "a2l-rat-func-coeffs" | "a2l-vtab" => {
+                    if let Some(token) = &conversion_token {
+                        diagnostics.push(keyword.diagnostic(
+                            location,
+                            format!("'{}' is ignored, the conversion is already given by '{}'", keyword.text, token.text),
+                        ));
+                        continue;
+                    }
+                    // a conversion with mistakes is dropped, a later one may still give the conversion
+                    let is_valid = match keyword.text.as_str() {
+                        "a2l-linear-coeffs" => {
+                            a2l_code_comment.a2l_linear_coeffs = parse_coeffs(&statement, 2, location, diagnostics);
+                            !a2l_code_comment.a2l_linear_coeffs.is_empty()
+                        }
+                        "a2l-rat-func-coeffs" => {
+                            a2l_code_comment.a2l_rat_func_coeffs = parse_coeffs(&statement, 6, location, diagnostics);
+                            !a2l_code_comment.a2l_rat_func_coeffs.is_empty()
+                        }
+                        _ => {
+                            a2l_code_comment.a2l_vtab = parse_vtab(&statement, location, diagnostics);
+                            !a2l_code_comment.a2l_vtab.is_empty()
+                        }
+                    };
+                    if is_valid {
+                        conversion_token = Some(keyword.clone());
+                    }
                 }
-            }
-            // check for a2l max refresh
-            if line.contains("a2l-max-refresh") {
-                let re = Regex::new(r"a2l-max-refresh\s+(.+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_max_refresh = captures[1].to_string();
+                "a2l-format" => {
+                    let format = value.unwrap();
+                    if is_valid_format(&format.text) {
+                        a2l_code_comment.a2l_format = format.text.clone();
+                    } else {
+                        diagnostics.push(format.diagnostic(
+                            location,
+                            format!("'{}' is not a valid format, expected e.g. %8.3", format.text),
+                        ));
+                    }
                 }
-            }
-            // check for a2l read only
-            if line.contains("a2l-read-only") {
-                a2l_code_comment.a2l_read_only = true;
-            }
-            // check for a2l read write
-            if line.contains("a2l-read-write") {
-                a2l_code_comment.a2l_read_write = true;
-            }
-            // check for a2l unit
-            if line.contains("a2l-unit") {
-                let re = Regex::new(r"a2l-unit\s+(.+)").unwrap();
-                if let Some(captures) = re.captures(line) {
-                    a2l_code_comment.a2l_unit = captures[1].to_string();
+                "a2l-byte-order" => {
+                    let value = value.unwrap();
+                    match parse_byte_order(&value.text) {
+                        Some(byte_order) => a2l_code_comment.a2l_byte_order = Some(byte_order),
+                        None => diagnostics.push(value.diagnostic(
+                            location,
+                            format!(
+                                "unsupported a2l-byte-order '{}', expected big_endian, little_endian, msb_first or msb_last",
+                                value.text
+                            ),
+                        )),
+                    }
                 }
+                "a2l-index-mode" => {
+                    let value = value.unwrap();
+                    match value.text.to_lowercase().as_str() {
+                        "row_dir" => a2l_code_comment.a2l_index_mode = IndexMode::RowDir,
+                        "column_dir" => a2l_code_comment.a2l_index_mode = IndexMode::ColumnDir,
+                        _ => diagnostics.push(value.diagnostic(
+                            location,
+                            format!("unsupported a2l-index-mode '{}', expected row_dir or column_dir", value.text),
+                        )),
+                    }
+                }
+                "a2l-display-identifier" => a2l_code_comment.a2l_display_identifier = statement.text(),
+                "a2l-group" => a2l_code_comment.a2l_group = statement.text(),
+                "a2l-max-refresh" => {
+                    let max_refresh = statement.text();
+                    if parse_max_refresh(&max_refresh).is_some() {
+                        a2l_code_comment.a2l_max_refresh = max_refresh;
+                    } else {
+                        diagnostics.push(value.unwrap().diagnostic(
+                            location,
+                            format!("'{}' is not a valid refresh rate, expected e.g. 10ms, 100us or 1s", max_refresh),
+                        ));
+                    }
+                }
+                "a2l-read-only" => a2l_code_comment.a2l_read_only = true,
+                "a2l-read-write" => a2l_code_comment.a2l_read_write = true,
+                "a2l-unit" => a2l_code_comment.a2l_unit = statement.text(),
+                _ => diagnostics.push(keyword.diagnostic(
+                    location,
+                    format!("unknown annotation '{}'", keyword.text),
+                )),
             }
-
-
+        }
+        // curves need one axis, maps two and so on
+        let expected_axes = axis_count(a2l_code_comment.a2l_characteristic_type);
+        if a2l_code_comment.a2l_axes.len() != expected_axes
+            && let Some(token) = characteristic_type_token.or(first_axis_token)
+        {
+            diagnostics.push(token.diagnostic(
+                location,
+                format!(
+                    "a2l-characteristic-type {} needs {} a2l-axis, found {}",
+                    characteristic_type_name(a2l_code_comment.a2l_characteristic_type),
+                    expected_axes,
+                    a2l_code_comment.a2l_axes.len()
+                ),
+            ));
         }
         // return the a2l code comment
         a2l_code_comment
     }
 }
 
+// number of axis descriptions a characteristic type needs
+pub fn axis_count(characteristic_type: CharacteristicType) -> usize {
+    match characteristic_type {
+        CharacteristicType::Curve => 1,
+        CharacteristicType::Map => 2,
+        CharacteristicType::Cuboid => 3,
+        CharacteristicType::Cube4 => 4,
+        CharacteristicType::Cube5 => 5,
+        CharacteristicType::Ascii | CharacteristicType::ValBlk | CharacteristicType::Value => 0,
+    }
+}
+
+fn characteristic_type_name(characteristic_type: CharacteristicType) -> &'static str {
+    match characteristic_type {
+        CharacteristicType::Ascii => "ascii",
+        CharacteristicType::Curve => "curve",
+        CharacteristicType::Map => "map",
+        CharacteristicType::Cuboid => "cuboid",
+        CharacteristicType::Cube4 => "cube_4",
+        CharacteristicType::Cube5 => "cube_5",
+        CharacteristicType::ValBlk => "val_blk",
+        CharacteristicType::Value => "value",
+    }
+}
+
+// parse "a2l-axis <input quantity> <conversion> <max axis points> <lower limit> <upper limit>"
+fn parse_axis(statement: &Statement, location: &SourceLocation, diagnostics: &mut Vec<Diagnostic>) -> Option<A2lAxis> {
+    let arguments = &statement.arguments;
+    if arguments.len() != 5 {
+        diagnostics.push(statement.keyword.diagnostic(
+            location,
+            format!(
+                "a2l-axis expects <input quantity> <conversion> <max axis points> <lower limit> <upper limit>, found {} values",
+                arguments.len()
+            ),
+        ));
+        return None;
+    }
+    let max_axis_points = match arguments[2].text.parse::<u16>() {
+        Ok(max_axis_points) => max_axis_points,
+        Err(_) => {
+            diagnostics.push(arguments[2].diagnostic(
+                location,
+                format!("'{}' is not a valid number of axis points", arguments[2].text),
+            ));
+            return None;
+        }
+    };
+    let lower_limit = parse_number(&arguments[3], location, diagnostics)?;
+    let upper_limit = parse_number(&arguments[4], location, diagnostics)?;
+    Some(A2lAxis {
+        input_quantity: arguments[0].text.clone(),
+        conversion: arguments[1].text.clone(),
+        max_axis_points,
+        lower_limit,
+        upper_limit,
+        axis_pts_ref: None,
+    })
+}
+
+// parse the coefficients of a conversion, an empty list is returned on mistakes
+fn parse_coeffs(
+    statement: &Statement,
+    count: usize,
+    location: &SourceLocation,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<f64> {
+    if statement.arguments.len() != count {
+        diagnostics.push(statement.keyword.diagnostic(
+            location,
+            format!(
+                "'{}' needs {} coefficients, found {}",
+                statement.keyword.text,
+                count,
+                statement.arguments.len()
+            ),
+        ));
+        return Vec::new();
+    }
+    let coeffs: Vec<f64> = statement
+        .arguments
+        .iter()
+        .filter_map(|argument| parse_number(argument, location, diagnostics))
+        .collect();
+    if coeffs.len() != count {
+        return Vec::new();
+    }
+    coeffs
+}
+
+// parse the value and text pairs of a verbal table like: 0 "Off" 1 "On"
+fn parse_vtab(statement: &Statement, location: &SourceLocation, diagnostics: &mut Vec<Diagnostic>) -> Vec<(f64, String)> {
+    if !statement.arguments.len().is_multiple_of(2) {
+        diagnostics.push(statement.keyword.diagnostic(
+            location,
+            format!("'{}' needs pairs of a value and a text", statement.keyword.text),
+        ));
+        return Vec::new();
+    }
+    let mut vtab = Vec::new();
+    for pair in statement.arguments.chunks(2) {
+        let value = parse_number(&pair[0], location, diagnostics);
+        if let Some(value) = value {
+            vtab.push((value, pair[1].text.clone()));
+        }
+    }
+    if vtab.len() != statement.arguments.len() / 2 {
+        return Vec::new();
+    }
+    vtab
+}
+
+// the most significant byte comes first in big endian data, MSB_FIRST in a2l
+fn parse_byte_order(text: &str) -> Option<ByteOrderEnum> {
+    match text.to_lowercase().as_str() {
+        "big_endian" | "msb_first" => Some(ByteOrderEnum::MsbFirst),
+        "little_endian" | "msb_last" => Some(ByteOrderEnum::MsbLast),
+        _ => None,
+    }
+}
+
+// a2l display format "%Length.Layout", e.g. %8.3
+fn is_valid_format(format: &str) -> bool {
+    let Some((length, layout)) = format.strip_prefix('%').and_then(|format| format.split_once('.')) else {
+        return false;
+    };
+    length.parse::<u8>().is_ok() && layout.parse::<u8>().is_ok()
+}
+
+// parse a refresh rate like "50ms", "100us" or "1s" into a MAX_REFRESH
+pub fn parse_max_refresh(max_refresh: &str) -> Option<MaxRefresh> {
+    let max_refresh = max_refresh.trim();
+    let digits_end = max_refresh
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(max_refresh.len());
+    let rate = max_refresh[..digits_end].parse::<u32>().ok()?;
+    // scaling units as defined in the ASAM MCD-2 MC specification
+    let scaling_unit = match max_refresh[digits_end..].trim() {
+        "us" => 0,
+        "ms" | "" => 3,
+        "s" => 6,
+        _ => return None,
+    };
+    Some(MaxRefresh::new(scaling_unit, rate))
+}
+
+// parse a number argument (float or integer), report a diagnostic if it isn't a number
+fn parse_number(token: &Token, location: &SourceLocation, diagnostics: &mut Vec<Diagnostic>) -> Option<f64> {
+    match token.text.parse::<f64>() {
+        Ok(number) => Some(number),
+        Err(_) => {
+            diagnostics.push(token.diagnostic(location, format!("'{}' is not a number", token.text)));
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -189,13 +506,13 @@ mod tests {
         a2l-description This is a test description
         a2l-min -10.5
         a2l-max 1000
-        a2l-linear-coeffs 1.23
-        a2l-rat-func-coeffs 4.56
+        a2l-linear-coeffs 1.23 -4
         a2l-display-identifier TestIdentifier
         a2l-group TestGroup
         a2l-max-refresh 50ms
         a2l-read-only
         a2l-unit m/s
+        a2l-format %6.2
         "#;
 
         let a2l_code_comment = A2lCodeComment::from_comment(comment);
@@ -205,16 +522,17 @@ mod tests {
         assert_eq!(a2l_code_comment.a2l_type, A2lType::Measurement);
         assert_eq!(a2l_code_comment.a2l_characteristic_type, CharacteristicType::Ascii);
         assert_eq!(a2l_code_comment.a2l_description, "This is a test description");
-        assert_eq!(a2l_code_comment.a2l_min, -10.5);
-        assert_eq!(a2l_code_comment.a2l_max, 1000.0);
-        assert_eq!(a2l_code_comment.a2l_linear_coeffs, "1.23");
-        assert_eq!(a2l_code_comment.a2l_rat_func_coeffs, "4.56");
+        assert_eq!(a2l_code_comment.a2l_min, Some(-10.5));
+        assert_eq!(a2l_code_comment.a2l_max, Some(1000.0));
+        assert_eq!(a2l_code_comment.a2l_linear_coeffs, vec![1.23, -4.0]);
+        assert!(a2l_code_comment.a2l_rat_func_coeffs.is_empty());
         assert_eq!(a2l_code_comment.a2l_display_identifier, "TestIdentifier");
         assert_eq!(a2l_code_comment.a2l_group, "TestGroup");
         assert_eq!(a2l_code_comment.a2l_max_refresh, "50ms");
         assert!(a2l_code_comment.a2l_read_only);
         assert!(!a2l_code_comment.a2l_read_write); // Not set in the comment
         assert_eq!(a2l_code_comment.a2l_unit, "m/s");
+        assert_eq!(a2l_code_comment.a2l_format, "%6.2");
     }
 
     #[test]
@@ -243,10 +561,10 @@ mod tests {
         // Check the vaild values
         assert!(a2l_code_comment.a2l_on);
         assert_eq!(a2l_code_comment.a2l_description, "This is a test description");
-        assert_eq!(a2l_code_comment.a2l_min, 0.0); // Default value
-        assert_eq!(a2l_code_comment.a2l_max, 0.0); // Default value
-        assert_eq!(a2l_code_comment.a2l_linear_coeffs, "invalid_value");
-        assert_eq!(a2l_code_comment.a2l_rat_func_coeffs, "invalid_value");
+        assert_eq!(a2l_code_comment.a2l_min, None);
+        assert_eq!(a2l_code_comment.a2l_max, None);
+        assert!(a2l_code_comment.a2l_linear_coeffs.is_empty());
+        assert!(a2l_code_comment.a2l_rat_func_coeffs.is_empty());
         assert_eq!(a2l_code_comment.a2l_display_identifier, "TestIdentifier");
         assert_eq!(a2l_code_comment.a2l_group, "TestGroup");
         assert_eq!(a2l_code_comment.a2l_max_refresh, "50ms");
@@ -256,6 +574,183 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_a2l_code_comment_diagnostics() {
+        let comment = "a2l on\n  a2l-min invalid_value\n  a2l-type InvalidType\n  a2l-characteristic-type matrix\n  a2l-offset 5\n";
+        let location = SourceLocation::new("test.c", 10, 4);
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment = A2lCodeComment::parse(comment, &location, &mut diagnostics);
+
+        // the defaults are kept for all invalid values
+        assert!(a2l_code_comment.a2l_on);
+        assert_eq!(a2l_code_comment.a2l_min, None);
+        assert_eq!(a2l_code_comment.a2l_type, A2lType::Unknown);
+        assert_eq!(a2l_code_comment.a2l_characteristic_type, CharacteristicType::Value);
+
+        assert_eq!(diagnostics.len(), 4);
+        assert_eq!(diagnostics[0].location, SourceLocation::new("test.c", 11, 11));
+        assert_eq!(diagnostics[0].length, 13);
+        assert_eq!(diagnostics[0].message, "'invalid_value' is not a number");
+        assert_eq!(diagnostics[1].location, SourceLocation::new("test.c", 12, 12));
+        assert_eq!(diagnostics[1].length, 11);
+        assert_eq!(diagnostics[2].location, SourceLocation::new("test.c", 13, 27));
+        assert_eq!(diagnostics[3].location, SourceLocation::new("test.c", 14, 3));
+        assert_eq!(diagnostics[3].message, "unknown annotation 'a2l-offset'");
+    }
+
+    #[test]
+    fn test_a2l_code_comment_keyword_prefixes() {
+        let comment = r#"
+        a2l on
+        a2l-max-refresh 10ms
+        a2l-description uses a2l-unit and a2l-max internally
+        a2l-unit "m / s"
+        "#;
+
+        let a2l_code_comment = A2lCodeComment::from_comment(comment);
+
+        assert_eq!(a2l_code_comment.a2l_max, None);
+        assert_eq!(a2l_code_comment.a2l_max_refresh, "10ms");
+        assert_eq!(a2l_code_comment.a2l_description, "uses a2l-unit and a2l-max internally");
+        assert_eq!(a2l_code_comment.a2l_unit, "m / s");
+    }
+
+    #[test]
+    fn test_a2l_type_tokens() {
+        assert_eq!(A2lType::from_str("AXIS-PTS"), A2lType::AxisPts);
+        assert_eq!(A2lType::from_str("characteristic_measurement"), A2lType::Unknown);
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment =
+            A2lCodeComment::parse("a2l-type characteristic measurement", &SourceLocation::new("", 1, 1), &mut diagnostics);
+        assert_eq!(a2l_code_comment.a2l_type, A2lType::Unknown);
+        assert_eq!(diagnostics[0].message, "a2l-type takes one value, found 'characteristic measurement'");
+        assert_eq!(diagnostics[0].location, SourceLocation::new("", 1, 25));
+    }
+
+    #[test]
+    fn test_a2l_code_comment_map() {
+        let comment = r#"
+        a2l on
+        a2l-type characteristic
+        a2l-characteristic-type map
+        a2l-axis engine_speed NO_COMPU_METHOD 8 0 8000
+        a2l-axis engine_load NO_COMPU_METHOD 4 0.0 100.0
+        "#;
+
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment = A2lCodeComment::parse(comment, &SourceLocation::new("", 1, 1), &mut diagnostics);
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(a2l_code_comment.a2l_characteristic_type, CharacteristicType::Map);
+        assert_eq!(a2l_code_comment.a2l_axes.len(), 2);
+        assert_eq!(
+            a2l_code_comment.a2l_axes[0],
+            A2lAxis {
+                input_quantity: "engine_speed".to_string(),
+                conversion: "NO_COMPU_METHOD".to_string(),
+                max_axis_points: 8,
+                lower_limit: 0.0,
+                upper_limit: 8000.0,
+                axis_pts_ref: None,
+            }
+        );
+        assert_eq!(a2l_code_comment.a2l_axes[1].max_axis_points, 4);
+    }
+
+    #[test]
+    fn test_a2l_code_comment_axis_mistakes() {
+        let comment = "a2l-characteristic-type cuboid\na2l-axis x NO_COMPU_METHOD many 0 1\na2l-matrix-dim 4 0\n";
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment = A2lCodeComment::parse(comment, &SourceLocation::new("", 1, 1), &mut diagnostics);
+
+        assert!(a2l_code_comment.a2l_axes.is_empty());
+        assert!(a2l_code_comment.a2l_matrix_dim.is_empty());
+        let messages: Vec<_> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "'many' is not a valid number of axis points",
+                "'0' is not a valid dimension",
+                "a2l-characteristic-type cuboid needs 3 a2l-axis, found 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_a2l_code_comment_axis_pts() {
+        let a2l_code_comment = A2lCodeComment::from_comment(
+            "a2l on\na2l-type axis_pts\na2l-input-quantity engine_speed\n",
+        );
+        assert_eq!(a2l_code_comment.a2l_type, A2lType::AxisPts);
+        assert_eq!(a2l_code_comment.a2l_input_quantity, "engine_speed");
+
+        let a2l_code_comment = A2lCodeComment::from_comment(
+            "a2l-characteristic-type map\na2l-axis-ref speed_axis\na2l-axis load NO_COMPU_METHOD 4 0 100\n",
+        );
+        assert_eq!(a2l_code_comment.a2l_axes.len(), 2);
+        assert_eq!(a2l_code_comment.a2l_axes[0].axis_pts_ref, Some("speed_axis".to_string()));
+        assert_eq!(a2l_code_comment.a2l_axes[1].axis_pts_ref, None);
+    }
+
+    #[test]
+    fn test_a2l_code_comment_val_blk() {
+        let a2l_code_comment =
+            A2lCodeComment::from_comment("a2l-characteristic-type val_blk\na2l-matrix-dim 4 8\n");
+        assert_eq!(a2l_code_comment.a2l_characteristic_type, CharacteristicType::ValBlk);
+        assert_eq!(a2l_code_comment.a2l_matrix_dim, vec![4, 8]);
+    }
+
+    #[test]
+    fn test_a2l_code_comment_conversions() {
+        let a2l_code_comment = A2lCodeComment::from_comment("a2l-rat-func-coeffs 0 1 0 0 0 0.5\n");
+        assert_eq!(a2l_code_comment.a2l_rat_func_coeffs, vec![0.0, 1.0, 0.0, 0.0, 0.0, 0.5]);
+
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment = A2lCodeComment::parse(
+            "a2l-linear-coeffs 2\na2l-rat-func-coeffs 0 1 0 0 0 x\na2l-linear-coeffs 1 0\na2l-vtab 0 off\n\
+             a2l-format 8.3\n",
+            &SourceLocation::new("", 1, 1),
+            &mut diagnostics,
+        );
+        // only the valid conversion counts
+        assert_eq!(a2l_code_comment.a2l_linear_coeffs, vec![1.0, 0.0]);
+        assert!(a2l_code_comment.a2l_rat_func_coeffs.is_empty());
+        assert!(a2l_code_comment.a2l_vtab.is_empty());
+        assert_eq!(a2l_code_comment.a2l_format, "");
+        let messages: Vec<_> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "'a2l-linear-coeffs' needs 2 coefficients, found 1",
+                "'x' is not a number",
+                "'a2l-vtab' is ignored, the conversion is already given by 'a2l-linear-coeffs'",
+                "'8.3' is not a valid format, expected e.g. %8.3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_a2l_code_comment_vtab() {
+        let a2l_code_comment = A2lCodeComment::from_comment(r#"a2l-vtab 0 "Off" 1 "On" 2 "Not available""#);
+        assert_eq!(
+            a2l_code_comment.a2l_vtab,
+            vec![
+                (0.0, "Off".to_string()),
+                (1.0, "On".to_string()),
+                (2.0, "Not available".to_string())
+            ]
+        );
+
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment = A2lCodeComment::parse(
+            "a2l-vtab 0 \"Off\" 1\n",
+            &SourceLocation::new("", 1, 1),
+            &mut diagnostics,
+        );
+        assert!(a2l_code_comment.a2l_vtab.is_empty());
+        assert_eq!(diagnostics[0].message, "'a2l-vtab' needs pairs of a value and a text");
+    }
+
     #[test]
     fn test_a2l_code_comment_defaults() {
         let a2l_code_comment = A2lCodeComment::new();
@@ -265,16 +760,55 @@ mod tests {
         assert_eq!(a2l_code_comment.a2l_type, A2lType::Unknown);
         assert_eq!(a2l_code_comment.a2l_characteristic_type, CharacteristicType::Value);
         assert_eq!(a2l_code_comment.a2l_description, "");
-        assert_eq!(a2l_code_comment.a2l_min, 0.0);
-        assert_eq!(a2l_code_comment.a2l_max, 0.0);
-        assert_eq!(a2l_code_comment.a2l_linear_coeffs, "");
-        assert_eq!(a2l_code_comment.a2l_rat_func_coeffs, "");
+        assert_eq!(a2l_code_comment.a2l_min, None);
+        assert_eq!(a2l_code_comment.a2l_max, None);
+        assert!(a2l_code_comment.a2l_linear_coeffs.is_empty());
+        assert!(a2l_code_comment.a2l_rat_func_coeffs.is_empty());
+        assert!(a2l_code_comment.a2l_vtab.is_empty());
         assert_eq!(a2l_code_comment.a2l_display_identifier, "");
         assert_eq!(a2l_code_comment.a2l_group, "");
         assert_eq!(a2l_code_comment.a2l_max_refresh, "");
         assert!(!a2l_code_comment.a2l_read_only);
         assert!(!a2l_code_comment.a2l_read_write);
         assert_eq!(a2l_code_comment.a2l_unit, "");
+        assert_eq!(a2l_code_comment.a2l_format, "");
+        assert!(a2l_code_comment.a2l_axes.is_empty());
+        assert!(a2l_code_comment.a2l_matrix_dim.is_empty());
+        assert_eq!(a2l_code_comment.a2l_byte_order, None);
+        assert_eq!(a2l_code_comment.a2l_index_mode, IndexMode::RowDir);
+    }
+
+    #[test]
+    fn test_a2l_code_comment_storage() {
+        let a2l_code_comment = A2lCodeComment::from_comment("a2l-byte-order big_endian");
+        assert_eq!(a2l_code_comment.a2l_byte_order, Some(ByteOrderEnum::MsbFirst));
+        let a2l_code_comment = A2lCodeComment::from_comment("a2l-byte-order MSB_LAST");
+        assert_eq!(a2l_code_comment.a2l_byte_order, Some(ByteOrderEnum::MsbLast));
+
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment = A2lCodeComment::parse(
+            "a2l-byte-order middle\n",
+            &SourceLocation::new("", 1, 1),
+            &mut diagnostics,
+        );
+        assert_eq!(a2l_code_comment.a2l_byte_order, None);
+        assert!(diagnostics[0].message.starts_with("unsupported a2l-byte-order 'middle'"));
+
+        let mut diagnostics = Vec::new();
+        let a2l_code_comment = A2lCodeComment::parse(
+            "a2l-max-refresh 50min\n",
+            &SourceLocation::new("", 1, 1),
+            &mut diagnostics,
+        );
+        assert_eq!(a2l_code_comment.a2l_max_refresh, "");
+        assert_eq!(
+            diagnostics[0].message,
+            "'50min' is not a valid refresh rate, expected e.g. 10ms, 100us or 1s"
+        );
+        assert_eq!(diagnostics[0].location, SourceLocation::new("", 1, 17));
+
+        let a2l_code_comment = A2lCodeComment::from_comment("a2l-index-mode COLUMN_DIR");
+        assert_eq!(a2l_code_comment.a2l_index_mode, IndexMode::ColumnDir);
     }
 
     #[test]
@@ -291,7 +825,7 @@ mod tests {
         // Assertions
         assert!(a2l_code_comment.a2l_on);
         assert_eq!(a2l_code_comment.a2l_type, A2lType::Measurement);
-        assert_eq!(a2l_code_comment.a2l_min, -12300.0); // Parsed scientific notation
-        assert_eq!(a2l_code_comment.a2l_max, 0.00567);  // Parsed scientific notation
+        assert_eq!(a2l_code_comment.a2l_min, Some(-12300.0)); // Parsed scientific notation
+        assert_eq!(a2l_code_comment.a2l_max, Some(0.00567));  // Parsed scientific notation
     }
-}
\ No newline at end of file
+}
\ No newline at end of file
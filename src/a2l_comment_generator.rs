@@ -1,28 +1,1159 @@
-use regex::Regex;
-use tree_sitter::{Node, Parser};
-use tree_sitter_c::LANGUAGE;
+use std::collections::{HashMap, HashSet};
+
 use a2lfile::*;
 
-pub struct A2lCommentGenerator{
+use crate::a2l_code_comment::*;
+use crate::c_types::{StructType, TargetProfile};
+use crate::code_parser::AnnotatedDeclaration;
+use crate::diagnostics::Diagnostic;
+use crate::dwarf::{DebugInfo, DwarfType, Encoding};
+use crate::elf::LinkerSymbol;
+use crate::error::*;
+
+// the ANNOTATION_LABEL of the generated objects, a merge only removes stale objects which carry it
+pub const OWNER_LABEL: &str = "generated by A2lCommentGenerator";
+
+pub struct A2lCommentGenerator {
+    a2l_file: A2lFile,
+    axis_references: Vec<AxisReference>,
+    typedef_structures: bool,
+    legacy_arrays: bool,
+    target: TargetProfile,
+    placements: Vec<Placement>,
+    variables: Vec<Variable>,
+    debug_info: Option<DebugInfo>,
+    // use the layout of the debug info instead of the C source if they differ
+    prefer_debug_info: bool,
+}
+
+// data type, conversion and limits of a declaration
+struct Scaling {
+    datatype: DataType,
+    conversion: String,
+    min: f64,
+    max: f64,
+    // dimensions of an array, empty for scalars
+    matrix_dim: Vec<u16>,
+}
+
+struct StructLayout {
+    offsets: Vec<usize>,
+    size: usize,
+    alignment: usize,
+}
+
+// the variable in which the data of an object is stored, its address is looked up in the firmware
+struct Placement {
+    name: String,
+    symbol: String,
+    offset: usize,
+    location: SourceLocation,
+}
 
+// a variable with the size of its C type, which is compared with the size in the firmware
+struct Variable {
+    name: String,
+    size: usize,
+    location: SourceLocation,
+    // number of characters of the declaration
+    length: usize,
 }
 
+// a COM_AXIS reference to an AXIS_PTS, checked once all declarations are known
+struct AxisReference {
+    characteristic: String,
+    axis_index: usize,
+    axis_pts: String,
+    location: SourceLocation,
+    // number of table elements in the direction of the axis, if the table is an array
+    table_dim: Option<usize>,
+    // the reference of a TYPEDEF_CHARACTERISTIC, it may also point to a TYPEDEF_AXIS
+    typedef: bool,
+}
 
 impl A2lCommentGenerator {
-    pub fn new() -> Self {
-        A2lCommentGenerator {}
+    pub fn new(project_name: &str, module_name: &str) -> Self {
+        let mut a2l_file = a2lfile::new();
+        a2l_file.project.name = project_name.to_string();
+        a2l_file.project.module[0].name = module_name.to_string();
+        let mut generator = A2lCommentGenerator {
+            a2l_file,
+            axis_references: Vec::new(),
+            typedef_structures: false,
+            legacy_arrays: false,
+            target: TargetProfile::default(),
+            placements: Vec::new(),
+            variables: Vec::new(),
+            debug_info: None,
+            prefer_debug_info: false,
+        };
+        generator.update_mod_common();
+        generator
+    }
+
+    pub fn a2l_file(&self) -> &A2lFile {
+        &self.a2l_file
+    }
+
+    // write the generated a2l file to disk
+    pub fn write(&self, path: &str) -> Result<(), GeneratorError> {
+        self.a2l_file
+            .write(path, Some(OWNER_LABEL))
+            .map_err(|error| GeneratorError::Write {
+                file: path.to_string(),
+                message: error.to_string(),
+            })
+    }
+
+    // create the a2l object described by the annotation comment of a declaration
+    pub fn add_declaration(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        let placements = self.placements.len();
+        self.add_object(declaration, &declaration.name, 0, diagnostics)?;
+        if self.placements.len() > placements {
+            if let Some(debug_info) = &self.debug_info
+                && debug_info.variable_type(&declaration.name).is_none()
+            {
+                diagnostics.push(Diagnostic::new(
+                    declaration.location.clone(),
+                    declaration.declaration.trim_end_matches(';').len(),
+                    format!(
+                        "'{}' is not in the debug info of the firmware, its layout isn't verified",
+                        declaration.name
+                    ),
+                ));
+            }
+            self.variables.push(Variable {
+                name: declaration.name.clone(),
+                size: self.variable_size(declaration)?,
+                location: declaration.location.clone(),
+                length: declaration.declaration.trim_end_matches(';').len(),
+            });
+        }
+        Ok(())
+    }
+
+    // size in bytes of a variable derived from its C type
+    fn variable_size(&self, declaration: &AnnotatedDeclaration) -> Result<usize, GeneratorError> {
+        let size = match &declaration.struct_type {
+            Some(struct_type) => self.struct_layout(struct_type)?.size,
+            None => datatype_size(self.declaration_datatype(declaration)?),
+        };
+        Ok(size * declaration.array_dims.iter().product::<usize>())
+    }
+
+    fn declaration_datatype(&self, declaration: &AnnotatedDeclaration) -> Result<DataType, GeneratorError> {
+        // enums have the size of an int
+        if declaration.enumerators.is_empty() {
+            self.match_c_type_to_a2l_type(&declaration.c_type, &declaration.base_type, &declaration.location)
+        } else {
+            Ok(integer_datatype(self.target.int_size, true))
+        }
+    }
+
+    // create the object of a declaration which is stored at the offset in the variable symbol
+    fn add_object(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        symbol: &str,
+        offset: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        let a2l_code_comment = A2lCodeComment::parse(
+            &declaration.comment,
+            &declaration.comment_location,
+            diagnostics,
+        );
+        if !a2l_code_comment.a2l_on {
+            return Ok(());
+        }
+        if let Some(struct_type) = &declaration.struct_type {
+            if self.typedef_structures {
+                return self.add_instance(declaration, &a2l_code_comment, struct_type, diagnostics);
+            }
+            let mut offsets = HashMap::new();
+            self.member_offsets(&declaration.name, struct_type, offset, &mut offsets)?;
+            for member in declaration.members() {
+                self.add_object(&member, symbol, offsets[&member.name], diagnostics)?;
+            }
+            return Ok(());
+        }
+
+        let datatype = self.declaration_datatype(declaration)?;
+        let (offset, array_dims, datatype) = self.check_debug_info(declaration, symbol, offset, datatype, diagnostics);
+        let corrected;
+        let declaration = if array_dims != declaration.array_dims {
+            corrected = AnnotatedDeclaration {
+                array_dims,
+                ..declaration.clone()
+            };
+            &corrected
+        } else {
+            declaration
+        };
+        let scaling = self.get_scaling(declaration, &a2l_code_comment, datatype, diagnostics)?;
+        match a2l_code_comment.a2l_type {
+            A2lType::Characteristic => self.add_characteristic(declaration, &a2l_code_comment, &scaling)?,
+            A2lType::AxisPts => self.add_axis_pts(declaration, &a2l_code_comment, &scaling)?,
+            // variables without a valid a2l-type are only observed
+            A2lType::Measurement | A2lType::Unknown => {
+                self.add_measurement(declaration, &a2l_code_comment, &scaling)
+            }
+        }
+        self.placements.push(Placement {
+            name: declaration.name.clone(),
+            symbol: symbol.to_string(),
+            offset,
+            location: declaration.location.clone(),
+        });
+        Ok(())
+    }
+
+    // compare an object with the debug info of the firmware, the compiler may lay out the data differently
+    // than the C source suggests, e.g. with packing pragmas
+    // returns the offset in its variable, the array dimensions and the data type, from the debug info if it wins
+    fn check_debug_info(
+        &self,
+        declaration: &AnnotatedDeclaration,
+        symbol: &str,
+        offset: usize,
+        datatype: DataType,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> (usize, Vec<usize>, DataType) {
+        let declared = (offset, declaration.array_dims.clone(), datatype);
+        // variables which aren't in the debug info are reported once by add_declaration
+        let Some(mut debug_type) = self.debug_info.as_ref().and_then(|debug_info| debug_info.variable_type(symbol))
+        else {
+            return declared;
+        };
+        let length = declaration.declaration.trim_end_matches(';').len();
+        let mut debug_offset = 0;
+        let path = declaration.name.strip_prefix(symbol).unwrap_or_default();
+        for member_name in path.split('.').filter(|name| !name.is_empty()) {
+            let Some(member) = debug_type.member(member_name) else {
+                diagnostics.push(Diagnostic::new(
+                    declaration.location.clone(),
+                    length,
+                    format!(
+                        "'{}' is not in the debug info of the firmware, its layout isn't verified",
+                        declaration.name
+                    ),
+                ));
+                return declared;
+            };
+            debug_offset += member.offset;
+            debug_type = &member.member_type;
+        }
+        let (debug_dims, element_type) = match debug_type {
+            DwarfType::Array { element, dims } => (dims.clone(), element.as_ref()),
+            _ => (Vec::new(), debug_type),
+        };
+        if *element_type == DwarfType::Unknown {
+            return declared;
+        }
+        let debug_datatype = debug_datatype(element_type).unwrap_or(datatype);
+
+        let mut differences = Vec::new();
+        if debug_offset != offset {
+            differences.push(format!("offset {} instead of {}", debug_offset, offset));
+        }
+        if debug_dims != declaration.array_dims {
+            differences.push(format!(
+                "dimensions {:?} instead of {:?}",
+                debug_dims, declaration.array_dims
+            ));
+        }
+        if debug_datatype != datatype {
+            differences.push(format!("data type {} instead of {}", debug_datatype, datatype));
+        }
+        if differences.is_empty() {
+            return declared;
+        }
+        diagnostics.push(self.debug_info_mismatch(&declaration.name, &differences, &declaration.location, length));
+        if self.prefer_debug_info {
+            (debug_offset, debug_dims, debug_datatype)
+        } else {
+            declared
+        }
+    }
+
+    // compare the size and the member offsets of a TYPEDEF_STRUCTURE with the debug info, the values of
+    // the debug info are written if it wins, location and length are those of the declaration using the type
+    fn check_typedef_structure(
+        &mut self,
+        type_name: &str,
+        struct_type: &StructType,
+        debug_type: &DwarfType,
+        location: &SourceLocation,
+        length: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        let layout = self.struct_layout(struct_type)?;
+        if let DwarfType::Struct { size, .. } = debug_type
+            && *size != layout.size
+        {
+            let difference = format!("size {} instead of {}", size, layout.size);
+            diagnostics.push(self.debug_info_mismatch(type_name, &[difference], location, length));
+            let module = &mut self.a2l_file.project.module[0];
+            if self.prefer_debug_info
+                && let Some(typedef) = module.typedef_structure.iter_mut().find(|typedef| typedef.name == type_name)
+            {
+                typedef.total_size = *size as u32;
+            }
+        }
+        for (member, offset) in struct_type.members.iter().zip(layout.offsets) {
+            let component_name = format!("{}.{}", type_name, member.name);
+            let length = member.declaration.trim_end_matches(';').len();
+            let Some(debug_member) = debug_type.member(&member.name) else {
+                diagnostics.push(Diagnostic::new(
+                    member.location.clone(),
+                    length,
+                    format!(
+                        "'{}' is not in the debug info of the firmware, its layout isn't verified",
+                        component_name
+                    ),
+                ));
+                continue;
+            };
+            if debug_member.offset != offset {
+                let difference = format!("offset {} instead of {}", debug_member.offset, offset);
+                diagnostics.push(self.debug_info_mismatch(&component_name, &[difference], &member.location, length));
+                let module = &mut self.a2l_file.project.module[0];
+                if self.prefer_debug_info
+                    && let Some(component) = module
+                        .typedef_structure
+                        .iter_mut()
+                        .find(|typedef| typedef.name == type_name)
+                        .and_then(|typedef| {
+                            typedef
+                                .structure_component
+                                .iter_mut()
+                                .find(|component| component.component_name == member.name)
+                        })
+                {
+                    component.address_offset = debug_member.offset as u32;
+                }
+            }
+            if let Some(nested) = &member.struct_type {
+                let nested_name = typedef_name(&member.c_type, &component_name);
+                self.check_typedef_structure(
+                    &nested_name,
+                    nested,
+                    &debug_member.member_type,
+                    &member.location,
+                    length,
+                    diagnostics,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn debug_info_mismatch(
+        &self,
+        name: &str,
+        differences: &[String],
+        location: &SourceLocation,
+        length: usize,
+    ) -> Diagnostic {
+        let used = if self.prefer_debug_info {
+            "the debug info is used"
+        } else {
+            "the C source is used"
+        };
+        Diagnostic::new(
+            location.clone(),
+            length,
+            format!(
+                "'{}' differs from the debug info of the firmware: {}, {}",
+                name,
+                differences.join(", "),
+                used
+            ),
+        )
+    }
+
+    // verify the layout of the objects with the debug info of the firmware, call this before add_declaration
+    pub fn set_debug_info(&mut self, debug_info: DebugInfo, prefer_debug_info: bool) {
+        self.debug_info = Some(debug_info);
+        self.prefer_debug_info = prefer_debug_info;
+    }
+
+    // also write the deprecated NUMBER and ARRAY_SIZE keywords for arrays
+    pub fn set_legacy_arrays(&mut self, legacy_arrays: bool) {
+        self.legacy_arrays = legacy_arrays;
+    }
+
+    // emit a TYPEDEF_STRUCTURE instead of one object per member of a struct instance
+    pub fn set_typedef_structures(&mut self, typedef_structures: bool) {
+        self.typedef_structures = typedef_structures;
+    }
+
+    // sizes of the C types which depend on the target
+    pub fn set_target(&mut self, target: TargetProfile) {
+        self.target = target;
+        self.update_mod_common();
+    }
+
+    // describe the byte order and the alignment of the target in MOD_COMMON
+    fn update_mod_common(&mut self) {
+        let target = &self.target;
+        let alignment = |size: usize| size.min(target.max_alignment) as u16;
+        let mod_common = self.a2l_file.project.module[0]
+            .mod_common
+            .get_or_insert_with(|| ModCommon::new(String::new()));
+        let byte_order = if target.big_endian {
+            ByteOrderEnum::MsbFirst
+        } else {
+            ByteOrderEnum::MsbLast
+        };
+        mod_common.byte_order = Some(ByteOrder::new(byte_order));
+        mod_common.alignment_byte = Some(AlignmentByte::new(alignment(1)));
+        mod_common.alignment_word = Some(AlignmentWord::new(alignment(2)));
+        mod_common.alignment_long = Some(AlignmentLong::new(alignment(4)));
+        mod_common.alignment_int64 = Some(AlignmentInt64::new(alignment(8)));
+        mod_common.alignment_float32_ieee = Some(AlignmentFloat32Ieee::new(alignment(4)));
+        mod_common.alignment_float64_ieee = Some(AlignmentFloat64Ieee::new(alignment(8)));
+    }
+
+    // set the addresses of the generated objects from the symbols of the linked firmware or its map file
+    // a variable which isn't in the firmware, e.g. because it was optimized away, is an error
+    // a variable whose size in the firmware differs from the size of its C type is a warning,
+    // the declaration doesn't match the build then, a size of 0 means that the size is unknown
+    pub fn set_addresses(
+        &mut self,
+        symbols: &HashMap<String, LinkerSymbol>,
+        errors: &mut Vec<GeneratorError>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for variable in &self.variables {
+            if let Some(symbol) = symbols.get(&variable.name)
+                && symbol.size != 0
+                && symbol.size != variable.size as u64
+            {
+                diagnostics.push(Diagnostic::new(
+                    variable.location.clone(),
+                    variable.length,
+                    format!(
+                        "'{}' has {} bytes in the firmware, but its C type has {} bytes",
+                        variable.name, symbol.size, variable.size
+                    ),
+                ));
+            }
+        }
+        let module = &mut self.a2l_file.project.module[0];
+        // the members of a missing struct are only reported once
+        let mut missing = HashSet::new();
+        for placement in &self.placements {
+            let Some(symbol) = symbols.get(&placement.symbol) else {
+                if missing.insert(placement.symbol.clone()) {
+                    errors.push(GeneratorError::Address {
+                        location: placement.location.clone(),
+                        message: format!(
+                            "'{}' is not in the symbol table of the firmware, it may have been optimized away",
+                            placement.symbol
+                        ),
+                    });
+                }
+                continue;
+            };
+            let Ok(address) = u32::try_from(symbol.address + placement.offset as u64) else {
+                errors.push(GeneratorError::Address {
+                    location: placement.location.clone(),
+                    message: format!("the address of '{}' doesn't fit into 32 bits", placement.name),
+                });
+                continue;
+            };
+            // the addresses are written in hex, the flag is part of the layout of the objects
+            if let Some(measurement) = module.measurement.iter_mut().find(|object| object.name == placement.name) {
+                let mut ecu_address = EcuAddress::new(address);
+                ecu_address.get_layout_mut().item_location.0.1 = true;
+                measurement.ecu_address = Some(ecu_address);
+            } else if let Some(characteristic) =
+                module.characteristic.iter_mut().find(|object| object.name == placement.name)
+            {
+                characteristic.address = address;
+                characteristic.get_layout_mut().item_location.3.1 = true;
+            } else if let Some(axis_pts) = module.axis_pts.iter_mut().find(|object| object.name == placement.name) {
+                axis_pts.address = address;
+                axis_pts.get_layout_mut().item_location.2.1 = true;
+            } else if let Some(instance) = module.instance.iter_mut().find(|object| object.name == placement.name) {
+                instance.start_address = address;
+                instance.get_layout_mut().item_location.3.1 = true;
+            }
+        }
+    }
+
+    // checks which need all declarations, call this after the last add_declaration
+    pub fn finish(&mut self, errors: &mut Vec<GeneratorError>) {
+        let module = &mut self.a2l_file.project.module[0];
+        for reference in self.axis_references.drain(..) {
+            // input quantity, conversion, number of points and limits of the referenced axis
+            let axis_pts = (module.axis_pts.iter())
+                .find(|axis_pts| axis_pts.name == reference.axis_pts)
+                .map(|axis| {
+                    let limits = (axis.lower_limit, axis.upper_limit);
+                    (axis.input_quantity.clone(), axis.conversion.clone(), axis.max_axis_points, limits)
+                });
+            let typedef_axis = (module.typedef_axis.iter())
+                .filter(|_| reference.typedef)
+                .find(|typedef_axis| typedef_axis.name == reference.axis_pts)
+                .map(|axis| {
+                    let limits = (axis.lower_limit, axis.upper_limit);
+                    (axis.input_quantity.clone(), axis.conversion.clone(), axis.max_axis_points, limits)
+                });
+            let Some((input_quantity, conversion, max_axis_points, (lower_limit, upper_limit))) = axis_pts.or(typedef_axis)
+            else {
+                let kind = if reference.typedef { "AXIS_PTS or TYPEDEF_AXIS" } else { "AXIS_PTS" };
+                errors.push(GeneratorError::Reference {
+                    location: reference.location,
+                    message: format!(
+                        "{} references the unknown {} '{}'",
+                        reference.characteristic, kind, reference.axis_pts
+                    ),
+                });
+                continue;
+            };
+            if let Some(table_dim) = reference.table_dim
+                && table_dim != max_axis_points as usize
+            {
+                errors.push(GeneratorError::Reference {
+                    location: reference.location,
+                    message: format!(
+                        "{} has {} values in the direction of axis {}, but the AXIS_PTS '{}' has {} points",
+                        reference.characteristic,
+                        table_dim,
+                        reference.axis_index + 1,
+                        reference.axis_pts,
+                        max_axis_points
+                    ),
+                });
+                continue;
+            }
+            // the axis description repeats the properties of the referenced axis
+            let axis_descr = if reference.typedef {
+                (module.typedef_characteristic.iter_mut())
+                    .find(|typedef| typedef.name == reference.characteristic)
+                    .map(|typedef| &mut typedef.axis_descr[reference.axis_index])
+            } else {
+                (module.characteristic.iter_mut())
+                    .find(|characteristic| characteristic.name == reference.characteristic)
+                    .map(|characteristic| &mut characteristic.axis_descr[reference.axis_index])
+            };
+            if let Some(axis_descr) = axis_descr {
+                axis_descr.input_quantity = input_quantity;
+                axis_descr.conversion = conversion;
+                axis_descr.max_axis_points = max_axis_points;
+                axis_descr.lower_limit = lower_limit;
+                axis_descr.upper_limit = upper_limit;
+            }
+        }
     }
+
+    // data type, conversion and limits from the C type and the annotation
+    fn get_scaling(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        datatype: DataType,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Scaling, GeneratorError> {
+        // an explicit verbal table replaces the one of the enum
+        let vtab: Vec<(f64, String)> = if a2l_code_comment.a2l_vtab.is_empty() {
+            declaration
+                .enumerators
+                .iter()
+                .map(|enumerator| (enumerator.value as f64, enumerator.name.clone()))
+                .collect()
+        } else {
+            a2l_code_comment.a2l_vtab.clone()
+        };
+        let conversion = self.get_compu_method(&declaration.name, a2l_code_comment, &vtab);
+        // use the limits of the data type or the verbal table for the limits which aren't given
+        let (default_min, default_max) = if conversion != "NO_COMPU_METHOD" && !vtab.is_empty() {
+            vtab.iter().fold((f64::MAX, f64::MIN), |(min, max), (value, _)| {
+                (min.min(*value), max.max(*value))
+            })
+        } else {
+            physical_limits(datatype, &a2l_code_comment.a2l_linear_coeffs)
+        };
+        Ok(Scaling {
+            datatype,
+            conversion,
+            min: a2l_code_comment.a2l_min.unwrap_or(default_min),
+            max: a2l_code_comment.a2l_max.unwrap_or(default_max),
+            matrix_dim: matrix_dim(declaration, a2l_code_comment, diagnostics)?,
+        })
+    }
+
+    fn add_characteristic(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        scaling: &Scaling,
+    ) -> Result<(), GeneratorError> {
+        let characteristic = self.build_characteristic(declaration, a2l_code_comment, scaling)?;
+        self.add_axis_references(declaration, a2l_code_comment, false);
+        self.a2l_file.project.module[0].characteristic.push(characteristic);
+        if !a2l_code_comment.a2l_group.is_empty() {
+            self.add_to_group(&a2l_code_comment.a2l_group, &declaration.name, true);
+        }
+        Ok(())
+    }
+
+    // the references to shared axis points are resolved by finish, once all AXIS_PTS are known
+    fn add_axis_references(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        typedef: bool,
+    ) {
+        for (axis_index, axis) in a2l_code_comment.a2l_axes.iter().enumerate() {
+            if let Some(axis_pts_ref) = &axis.axis_pts_ref {
+                self.axis_references.push(AxisReference {
+                    characteristic: declaration.name.clone(),
+                    axis_index,
+                    axis_pts: axis_pts_ref.clone(),
+                    location: declaration.location.clone(),
+                    table_dim: declaration.array_dims.get(axis_index).copied(),
+                    typedef,
+                });
+            }
+        }
+    }
+
+    fn build_characteristic(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        scaling: &Scaling,
+    ) -> Result<Characteristic, GeneratorError> {
+        // an array of values is a value block
+        let characteristic_type = match a2l_code_comment.a2l_characteristic_type {
+            CharacteristicType::Value if !scaling.matrix_dim.is_empty() => CharacteristicType::ValBlk,
+            characteristic_type => characteristic_type,
+        };
+        self.check_record_size(declaration, &a2l_code_comment.a2l_axes, scaling.datatype)?;
+        let deposit = self.get_record_layout(
+            characteristic_type,
+            scaling.datatype,
+            &a2l_code_comment.a2l_axes,
+            a2l_code_comment.a2l_index_mode,
+        );
+        let mut characteristic = self.create_characteristic(
+            &declaration.name,
+            &a2l_code_comment.a2l_description,
+            characteristic_type,
+            &deposit,
+            &scaling.conversion,
+            scaling.min,
+            scaling.max,
+        );
+        if !a2l_code_comment.a2l_unit.is_empty() {
+            characteristic.phys_unit = Some(PhysUnit::new(a2l_code_comment.a2l_unit.clone()));
+        }
+        if !a2l_code_comment.a2l_display_identifier.is_empty() {
+            characteristic.display_identifier = Some(DisplayIdentifier::new(
+                a2l_code_comment.a2l_display_identifier.clone(),
+            ));
+        }
+        if let Some(max_refresh) = parse_max_refresh(&a2l_code_comment.a2l_max_refresh) {
+            characteristic.max_refresh = Some(max_refresh);
+        }
+        if a2l_code_comment.a2l_read_only {
+            characteristic.read_only = Some(ReadOnly::new());
+        }
+        characteristic.byte_order = a2l_code_comment.a2l_byte_order.map(ByteOrder::new);
+        characteristic.annotation.push(owner_annotation());
+        for axis in &a2l_code_comment.a2l_axes {
+            let attribute = if axis.axis_pts_ref.is_some() {
+                AxisDescrAttribute::ComAxis
+            } else {
+                AxisDescrAttribute::StdAxis
+            };
+            let mut axis_descr = AxisDescr::new(
+                attribute,
+                axis.input_quantity.clone(),
+                axis.conversion.clone(),
+                axis.max_axis_points,
+                axis.lower_limit,
+                axis.upper_limit,
+            );
+            if let Some(axis_pts_ref) = &axis.axis_pts_ref {
+                axis_descr.axis_pts_ref = Some(AxisPtsRef::new(axis_pts_ref.clone()));
+            }
+            characteristic.axis_descr.push(axis_descr);
+        }
+        // the dimensions of curves and maps are given by their axes
+        let element_count = scaling.matrix_dim.iter().map(|dim| *dim as u32).product::<u32>();
+        match characteristic_type {
+            CharacteristicType::ValBlk if !scaling.matrix_dim.is_empty() => {
+                let mut matrix_dim = MatrixDim::new();
+                matrix_dim.dim_list = scaling.matrix_dim.clone();
+                characteristic.matrix_dim = Some(matrix_dim);
+                if self.legacy_arrays && element_count <= u16::MAX as u32 {
+                    characteristic.number = Some(Number::new(element_count as u16));
+                }
+            }
+            // the last dimension is the length of the strings, the others make an array of strings
+            CharacteristicType::Ascii if let Some((length, dims)) = scaling.matrix_dim.split_last() => {
+                characteristic.number = Some(Number::new(*length));
+                if !dims.is_empty() {
+                    let mut matrix_dim = MatrixDim::new();
+                    matrix_dim.dim_list = dims.to_vec();
+                    characteristic.matrix_dim = Some(matrix_dim);
+                }
+            }
+            _ => {}
+        }
+        Ok(characteristic)
+    }
+
+    // the points of a STD_AXIS are stored in the record in front of the values,
+    // so the record has to fill the variable exactly, otherwise the tool reads past it
+    fn check_record_size(
+        &self,
+        declaration: &AnnotatedDeclaration,
+        axes: &[A2lAxis],
+        datatype: DataType,
+    ) -> Result<(), GeneratorError> {
+        let axes = &axes[..axes.len().min(5)];
+        if axes.iter().all(|axis| axis.axis_pts_ref.is_some()) {
+            return Ok(());
+        }
+        // the record is laid out like its record layout, with the alignments of MOD_COMMON
+        let element_size = datatype_size(datatype);
+        let align = |position: usize, size: usize| position.next_multiple_of(size.min(self.target.max_alignment));
+        let std_axes = || axes.iter().filter(|axis| axis.axis_pts_ref.is_none());
+        let mut record_size = 0;
+        for _ in std_axes() {
+            record_size = align(record_size, 2) + 2;
+        }
+        for axis in std_axes() {
+            record_size = align(record_size, element_size) + axis.max_axis_points as usize * element_size;
+        }
+        // the points of a COM_AXIS are given by the dimension of the array
+        let value_count = axes
+            .iter()
+            .enumerate()
+            .map(|(index, axis)| match axis.axis_pts_ref {
+                Some(_) => declaration.array_dims.get(index).copied().unwrap_or(1),
+                None => axis.max_axis_points as usize,
+            })
+            .product::<usize>();
+        record_size = align(record_size, element_size) + value_count * element_size;
+        let variable_size = element_size * declaration.array_dims.iter().product::<usize>();
+        if record_size != variable_size {
+            return Err(GeneratorError::Declaration {
+                location: declaration.location.clone(),
+                message: format!(
+                    "the record of '{}' with the points of its STD_AXIS takes {} bytes, but the variable has {}; \
+                     use a2l-axis-ref if the axis points are stored apart",
+                    declaration.name, record_size, variable_size
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    fn add_axis_pts(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        scaling: &Scaling,
+    ) -> Result<(), GeneratorError> {
+        let axis_pts = self.build_axis_pts(declaration, a2l_code_comment, scaling)?;
+        self.a2l_file.project.module[0].axis_pts.push(axis_pts);
+        // axis points are referenced like characteristics in groups
+        if !a2l_code_comment.a2l_group.is_empty() {
+            self.add_to_group(&a2l_code_comment.a2l_group, &declaration.name, true);
+        }
+        Ok(())
+    }
+
+    // axis points are stored in a one dimensional array
+    fn build_axis_pts(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        scaling: &Scaling,
+    ) -> Result<AxisPts, GeneratorError> {
+        let max_axis_points = match declaration.array_dims.as_slice() {
+            [points] => u16::try_from(*points).ok(),
+            _ => None,
+        }
+        .ok_or_else(|| GeneratorError::Declaration {
+            location: declaration.location.clone(),
+            message: format!("the axis points {} must be a one dimensional array", declaration.name),
+        })?;
+        let deposit = self.get_axis_pts_record_layout(scaling.datatype);
+        let input_quantity = if a2l_code_comment.a2l_input_quantity.is_empty() {
+            "NO_INPUT_QUANTITY"
+        } else {
+            &a2l_code_comment.a2l_input_quantity
+        };
+        let mut axis_pts = AxisPts::new(
+            declaration.name.clone(),
+            a2l_code_comment.a2l_description.clone(),
+            0,
+            input_quantity.to_string(),
+            deposit,
+            0.0,
+            scaling.conversion.clone(),
+            max_axis_points,
+            scaling.min,
+            scaling.max,
+        );
+        if !a2l_code_comment.a2l_unit.is_empty() {
+            axis_pts.phys_unit = Some(PhysUnit::new(a2l_code_comment.a2l_unit.clone()));
+        }
+        if !a2l_code_comment.a2l_display_identifier.is_empty() {
+            axis_pts.display_identifier = Some(DisplayIdentifier::new(
+                a2l_code_comment.a2l_display_identifier.clone(),
+            ));
+        }
+        if let Some(max_refresh) = parse_max_refresh(&a2l_code_comment.a2l_max_refresh) {
+            axis_pts.max_refresh = Some(max_refresh);
+        }
+        if a2l_code_comment.a2l_read_only {
+            axis_pts.read_only = Some(ReadOnly::new());
+        }
+        axis_pts.byte_order = a2l_code_comment.a2l_byte_order.map(ByteOrder::new);
+        axis_pts.annotation.push(owner_annotation());
+        Ok(axis_pts)
+    }
+
+    fn add_measurement(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        scaling: &Scaling,
+    ) {
+        let measurement = self.build_measurement(declaration, a2l_code_comment, scaling);
+        self.a2l_file.project.module[0].measurement.push(measurement);
+        if !a2l_code_comment.a2l_group.is_empty() {
+            self.add_to_group(&a2l_code_comment.a2l_group, &declaration.name, false);
+        }
+    }
+
+    fn build_measurement(
+        &self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        scaling: &Scaling,
+    ) -> Measurement {
+        let mut measurement = self.create_measurement(
+            &declaration.name,
+            &a2l_code_comment.a2l_description,
+            scaling.datatype,
+            &scaling.conversion,
+            1,
+            scaling.min,
+            scaling.max,
+        );
+        if !a2l_code_comment.a2l_unit.is_empty() {
+            measurement.phys_unit = Some(PhysUnit::new(a2l_code_comment.a2l_unit.clone()));
+        }
+        if !a2l_code_comment.a2l_display_identifier.is_empty() {
+            measurement.display_identifier = Some(DisplayIdentifier::new(
+                a2l_code_comment.a2l_display_identifier.clone(),
+            ));
+        }
+        if let Some(max_refresh) = parse_max_refresh(&a2l_code_comment.a2l_max_refresh) {
+            measurement.max_refresh = Some(max_refresh);
+        }
+        if a2l_code_comment.a2l_read_write {
+            measurement.read_write = Some(ReadWrite::new());
+        }
+        measurement.byte_order = a2l_code_comment.a2l_byte_order.map(ByteOrder::new);
+        measurement.annotation.push(owner_annotation());
+        if !scaling.matrix_dim.is_empty() {
+            let mut matrix_dim = MatrixDim::new();
+            matrix_dim.dim_list = scaling.matrix_dim.clone();
+            measurement.matrix_dim = Some(matrix_dim);
+            // ARRAY_SIZE can only describe one dimensional arrays
+            if let [size] = scaling.matrix_dim[..]
+                && self.legacy_arrays
+            {
+                measurement.array_size = Some(ArraySize::new(size));
+            }
+        }
+        measurement
+    }
+
+    // add an INSTANCE of the TYPEDEF_STRUCTURE of the struct type
+    fn add_instance(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        struct_type: &StructType,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        let type_name = typedef_name(&declaration.c_type, &declaration.name);
+        let module = &self.a2l_file.project.module[0];
+        let is_new = !module.typedef_structure.iter().any(|typedef| typedef.name == type_name);
+        if !self.add_typedef_structure(&type_name, struct_type, diagnostics)? {
+            return Ok(());
+        }
+        // the layout of a typedef is checked once, with the first instance in the debug info
+        let debug_type = self
+            .debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.variable_type(&declaration.name))
+            .cloned();
+        if is_new && let Some(debug_type) = debug_type {
+            let debug_type = match debug_type {
+                DwarfType::Array { element, .. } => *element,
+                debug_type => debug_type,
+            };
+            let length = declaration.declaration.trim_end_matches(';').len();
+            self.check_typedef_structure(
+                &type_name,
+                struct_type,
+                &debug_type,
+                &declaration.location,
+                length,
+                diagnostics,
+            )?;
+        }
+        let mut instance = Instance::new(
+            declaration.name.clone(),
+            a2l_code_comment.a2l_description.clone(),
+            type_name,
+            0,
+        );
+        if !a2l_code_comment.a2l_display_identifier.is_empty() {
+            instance.display_identifier = Some(DisplayIdentifier::new(
+                a2l_code_comment.a2l_display_identifier.clone(),
+            ));
+        }
+        if a2l_code_comment.a2l_read_only {
+            instance.read_only = Some(ReadOnly::new());
+        }
+        instance.annotation.push(owner_annotation());
+        self.a2l_file.project.module[0].instance.push(instance);
+        self.placements.push(Placement {
+            name: declaration.name.clone(),
+            symbol: declaration.name.clone(),
+            offset: 0,
+            location: declaration.location.clone(),
+        });
+        Ok(())
+    }
+
+    // add a TYPEDEF_STRUCTURE with a component per annotated member, nested structs get their own typedef
+    // returns false if no member is annotated, the typedef is not needed then
+    fn add_typedef_structure(
+        &mut self,
+        type_name: &str,
+        struct_type: &StructType,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<bool, GeneratorError> {
+        if !has_annotated_members(struct_type) {
+            return Ok(false);
+        }
+        let module = &self.a2l_file.project.module[0];
+        if module.typedef_structure.iter().any(|typedef| typedef.name == type_name) {
+            return Ok(true);
+        }
+        let layout = self.struct_layout(struct_type)?;
+        let mut typedef_structure = TypedefStructure::new(type_name.to_string(), String::new(), layout.size as u32);
+        for (member, offset) in struct_type.members.iter().zip(layout.offsets) {
+            let component_name = format!("{}.{}", type_name, member.name);
+            let component_type = if let Some(nested) = &member.struct_type {
+                let nested_name = typedef_name(&member.c_type, &component_name);
+                if !self.add_typedef_structure(&nested_name, nested, diagnostics)? {
+                    continue;
+                }
+                nested_name
+            } else if let Some(member_declaration) = AnnotatedDeclaration::from_member(component_name, member) {
+                let a2l_code_comment = A2lCodeComment::parse(
+                    &member_declaration.comment,
+                    &member_declaration.comment_location,
+                    diagnostics,
+                );
+                if !a2l_code_comment.a2l_on {
+                    continue;
+                }
+                self.add_typedef_component(&member_declaration, &a2l_code_comment, diagnostics)?;
+                member_declaration.name
+            } else {
+                continue;
+            };
+            typedef_structure.structure_component.push(StructureComponent::new(
+                member.name.clone(),
+                component_type,
+                offset as u32,
+            ));
+        }
+        self.a2l_file.project.module[0].typedef_structure.push(typedef_structure);
+        Ok(true)
+    }
+
+    // add the TYPEDEF_MEASUREMENT, TYPEDEF_CHARACTERISTIC or TYPEDEF_AXIS of a struct member
+    fn add_typedef_component(
+        &mut self,
+        declaration: &AnnotatedDeclaration,
+        a2l_code_comment: &A2lCodeComment,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        let datatype = self.declaration_datatype(declaration)?;
+        let scaling = self.get_scaling(declaration, a2l_code_comment, datatype, diagnostics)?;
+        match a2l_code_comment.a2l_type {
+            A2lType::Characteristic => {
+                let characteristic = self.build_characteristic(declaration, a2l_code_comment, &scaling)?;
+                let mut typedef = TypedefCharacteristic::new(
+                    characteristic.name,
+                    characteristic.long_identifier,
+                    characteristic.characteristic_type,
+                    characteristic.deposit,
+                    characteristic.max_diff,
+                    characteristic.conversion,
+                    characteristic.lower_limit,
+                    characteristic.upper_limit,
+                );
+                typedef.axis_descr = characteristic.axis_descr;
+                typedef.matrix_dim = characteristic.matrix_dim;
+                typedef.number = characteristic.number;
+                typedef.phys_unit = characteristic.phys_unit;
+                typedef.byte_order = characteristic.byte_order;
+                self.add_axis_references(declaration, a2l_code_comment, true);
+                self.a2l_file.project.module[0].typedef_characteristic.push(typedef);
+            }
+            A2lType::AxisPts => {
+                let axis_pts = self.build_axis_pts(declaration, a2l_code_comment, &scaling)?;
+                let mut typedef = TypedefAxis::new(
+                    axis_pts.name,
+                    axis_pts.long_identifier,
+                    axis_pts.input_quantity,
+                    axis_pts.deposit_record,
+                    axis_pts.max_diff,
+                    axis_pts.conversion,
+                    axis_pts.max_axis_points,
+                    axis_pts.lower_limit,
+                    axis_pts.upper_limit,
+                );
+                typedef.phys_unit = axis_pts.phys_unit;
+                typedef.byte_order = axis_pts.byte_order;
+                self.a2l_file.project.module[0].typedef_axis.push(typedef);
+            }
+            A2lType::Measurement | A2lType::Unknown => {
+                let measurement = self.build_measurement(declaration, a2l_code_comment, &scaling);
+                let mut typedef = TypedefMeasurement::new(
+                    measurement.name,
+                    measurement.long_identifier,
+                    measurement.datatype,
+                    measurement.conversion,
+                    measurement.resolution,
+                    measurement.accuracy,
+                    measurement.lower_limit,
+                    measurement.upper_limit,
+                );
+                typedef.matrix_dim = measurement.matrix_dim;
+                typedef.phys_unit = measurement.phys_unit;
+                typedef.byte_order = measurement.byte_order;
+                self.a2l_file.project.module[0].typedef_measurement.push(typedef);
+            }
+        }
+        Ok(())
+    }
+
+    // offsets of the members of a struct instance by their dotted names, like the names of members()
+    fn member_offsets(
+        &self,
+        prefix: &str,
+        struct_type: &StructType,
+        offset: usize,
+        offsets: &mut HashMap<String, usize>,
+    ) -> Result<(), GeneratorError> {
+        let layout = self.struct_layout(struct_type)?;
+        for (member, member_offset) in struct_type.members.iter().zip(layout.offsets) {
+            let name = format!("{}.{}", prefix, member.name);
+            if let Some(nested) = &member.struct_type {
+                self.member_offsets(&name, nested, offset + member_offset, offsets)?;
+            }
+            offsets.insert(name, offset + member_offset);
+        }
+        Ok(())
+    }
+
+    // offsets of the members and the size of a struct or union
+    // members are aligned to their size, up to the largest alignment of the target
+    fn struct_layout(&self, struct_type: &StructType) -> Result<StructLayout, GeneratorError> {
+        let mut offsets = Vec::new();
+        let mut size = 0;
+        let mut alignment = 1;
+        for member in &struct_type.members {
+            // the offsets behind a bitfield can't be derived without knowing the compiler
+            if member.is_bitfield {
+                return Err(GeneratorError::Declaration {
+                    location: member.location.clone(),
+                    message: format!(
+                        "bitfields are not supported, the layout of the struct with '{}' is unknown",
+                        member.name
+                    ),
+                });
+            }
+            let (member_size, member_alignment) = match &member.struct_type {
+                Some(nested) => {
+                    let layout = self.struct_layout(nested)?;
+                    (layout.size, layout.alignment)
+                }
+                // enums have the size of an int
+                None if !member.enumerators.is_empty() => {
+                    (self.target.int_size, self.target.int_size.min(self.target.max_alignment))
+                }
+                None => {
+                    let datatype = self.match_c_type_to_a2l_type(&member.c_type, &member.base_type, &member.location)?;
+                    let size = datatype_size(datatype);
+                    (size, size.min(self.target.max_alignment))
+                }
+            };
+            let member_size = member_size * member.array_dims.iter().product::<usize>();
+            alignment = alignment.max(member_alignment);
+            if struct_type.is_union {
+                offsets.push(0);
+                size = size.max(member_size);
+            } else {
+                size = size.next_multiple_of(member_alignment);
+                offsets.push(size);
+                size += member_size;
+            }
+        }
+        Ok(StructLayout {
+            offsets,
+            size: size.next_multiple_of(alignment),
+            alignment,
+        })
+    }
+
     // match c variable types to a2l types
-    fn match_c_type_to_a2l_type(&self, c_type: &str) -> DataType {
+    // typedefs are resolved by the parser, the base type is matched
+    fn match_c_type_to_a2l_type(
+        &self,
+        c_type: &str,
+        base_type: &str,
+        location: &SourceLocation,
+    ) -> Result<DataType, GeneratorError> {
+        let target = &self.target;
+        // pointers are addresses of the size of the target
+        if base_type.ends_with('*') {
+            return Ok(integer_datatype(target.pointer_size, false));
+        }
         // Konvertiere in Kleinbuchstaben und erhalte einen String-Slice
-        match c_type.to_lowercase().as_str() {
+        Ok(match base_type.to_lowercase().as_str() {
             // Byte-Variablen
             "uint8_t" => DataType::Ubyte,
             "int8_t" => DataType::Sbyte,
             "uint8" => DataType::Ubyte,
             "int8" => DataType::Sbyte,
             "unsigned char" => DataType::Ubyte,
-            "char" => DataType::Sbyte,
+            "signed char" => DataType::Sbyte,
+            "char" => integer_datatype(1, target.char_signed),
+            "bool" | "_bool" => DataType::Ubyte,
             // Word-Variablen 16 Bit
             "unsigned short" => DataType::Uword,
             "short" => DataType::Sword,
@@ -31,8 +1162,6 @@ impl A2lCommentGenerator {
             "uint16" => DataType::Uword,
             "int16" => DataType::Sword,
             // Word-Variablen 32 Bit
-            "int" => DataType::Slong,
-            "unsigned int" => DataType::Ulong,
             "uint32_t" => DataType::Ulong,
             "int32_t" => DataType::Slong,
             "uint32" => DataType::Ulong,
@@ -47,16 +1176,191 @@ impl A2lCommentGenerator {
             // floating point variables
             "float" => DataType::Float32Ieee,
             "double" => DataType::Float64Ieee,
-            "long" => DataType::Slong,
-            // throw an error if the type is not found
+            // types with a size depending on the target
+            "int" => integer_datatype(target.int_size, true),
+            "unsigned int" => integer_datatype(target.int_size, false),
+            "long" => integer_datatype(target.long_size, true),
+            "unsigned long" => integer_datatype(target.long_size, false),
+            "size_t" => integer_datatype(target.size_t_size, false),
+            "ptrdiff_t" | "intptr_t" => integer_datatype(target.pointer_size, true),
+            "uintptr_t" => integer_datatype(target.pointer_size, false),
+            // the type given in the error is the declared one, with its base type if it's a typedef
             _ => {
-                println!("Error: Type {} not found", c_type);
-                // return a default type
-                DataType::Ubyte
+                let message = if c_type.split_whitespace().eq(base_type.split_whitespace()) {
+                    format!("unknown type '{}'", c_type)
+                } else {
+                    format!("unknown type '{}', resolved to '{}'", c_type, base_type)
+                };
+                return Err(GeneratorError::Declaration {
+                    location: location.clone(),
+                    message,
+                });
             }
+        })
+    }
+
+    // get the name of the conversion given by the annotation, create it if necessary
+    // variables with identical conversions share one COMPU_METHOD
+    fn get_compu_method(&mut self, name: &str, a2l_code_comment: &A2lCodeComment, vtab: &[(f64, String)]) -> String {
+        let format = if a2l_code_comment.a2l_format.is_empty() {
+            "%8.3"
+        } else {
+            &a2l_code_comment.a2l_format
+        };
+        let mut compu_method = CompuMethod::new(
+            format!("CM_{}", name),
+            String::new(),
+            ConversionType::Identical,
+            format.to_string(),
+            a2l_code_comment.a2l_unit.clone(),
+        );
+        if let [a, b] = a2l_code_comment.a2l_linear_coeffs[..] {
+            compu_method.conversion_type = ConversionType::Linear;
+            compu_method.coeffs_linear = Some(CoeffsLinear::new(a, b));
+        } else if let [a, b, c, d, e, f] = a2l_code_comment.a2l_rat_func_coeffs[..] {
+            compu_method.conversion_type = ConversionType::RatFunc;
+            compu_method.coeffs = Some(Coeffs::new(a, b, c, d, e, f));
+        } else if !vtab.is_empty() {
+            compu_method.conversion_type = ConversionType::TabVerb;
+            compu_method.compu_tab_ref = Some(CompuTabRef::new(self.get_compu_vtab(name, vtab)));
+        } else {
+            return "NO_COMPU_METHOD".to_string();
+        }
+
+        let module = &mut self.a2l_file.project.module[0];
+        if let Some(existing) = module.compu_method.iter().find(|existing| {
+            existing.conversion_type == compu_method.conversion_type
+                && existing.coeffs_linear == compu_method.coeffs_linear
+                && existing.coeffs == compu_method.coeffs
+                && existing.compu_tab_ref == compu_method.compu_tab_ref
+                && existing.format == compu_method.format
+                && existing.unit == compu_method.unit
+        }) {
+            return existing.name.clone();
         }
+        let name = compu_method.name.clone();
+        module.compu_method.push(compu_method);
+        name
     }
 
+    // get the name of a verbal table with the given values, create it if necessary
+    fn get_compu_vtab(&mut self, name: &str, vtab: &[(f64, String)]) -> String {
+        let value_pairs: Vec<ValuePairsStruct> = vtab
+            .iter()
+            .map(|(value, text)| ValuePairsStruct::new(*value, text.clone()))
+            .collect();
+        let module = &mut self.a2l_file.project.module[0];
+        if let Some(existing) = module
+            .compu_vtab
+            .iter()
+            .find(|existing| existing.value_pairs == value_pairs)
+        {
+            return existing.name.clone();
+        }
+        let mut compu_vtab = CompuVtab::new(
+            format!("VTAB_{}", name),
+            String::new(),
+            ConversionType::TabVerb,
+            value_pairs.len() as u16,
+        );
+        compu_vtab.value_pairs = value_pairs;
+        let name = compu_vtab.name.clone();
+        module.compu_vtab.push(compu_vtab);
+        name
+    }
+
+    // get the name of the record layout of a characteristic, create it if necessary
+    // the values are preceded by the number of points and the points of every STD_AXIS,
+    // the points of a COM_AXIS are stored in their AXIS_PTS, so it isn't part of the record
+    fn get_record_layout(
+        &mut self,
+        characteristic_type: CharacteristicType,
+        datatype: DataType,
+        axes: &[A2lAxis],
+        index_mode: IndexMode,
+    ) -> String {
+        let std_axes: Vec<usize> = (0..axes.len().min(5))
+            .filter(|index| axes[*index].axis_pts_ref.is_none())
+            .collect();
+        // e.g. RL_VALUE_UWORD, RL_MAP_UBYTE or RL_MAP_UBYTE_STD_Y if only the y axis is stored
+        let mut name = if std_axes.is_empty() {
+            format!("RL_VALUE_{}", datatype)
+        } else {
+            format!("RL_{}_{}", characteristic_type, datatype)
+        };
+        if !std_axes.is_empty() && std_axes.len() < axes.len() {
+            name.push_str("_STD_");
+            name.extend(std_axes.iter().map(|index| AXIS_LETTERS[*index]));
+        }
+        if index_mode == IndexMode::ColumnDir {
+            name.push_str("_COLUMN_DIR");
+        }
+        let module = &mut self.a2l_file.project.module[0];
+        if module.record_layout.iter().any(|record_layout| record_layout.name == name) {
+            return name;
+        }
+        let mut record_layout = RecordLayout::new(name.clone());
+        let mut position = 1;
+        for index in &std_axes {
+            let (no_axis_pts, _) = axis_layout(&mut record_layout, *index);
+            *no_axis_pts = Some(NoAxisPtsDim::new(position, DataType::Uword));
+            position += 1;
+        }
+        for index in &std_axes {
+            let (_, axis_pts) = axis_layout(&mut record_layout, *index);
+            *axis_pts = Some(AxisPtsDim::new(position, datatype, IndexOrder::IndexIncr, AddrType::Direct));
+            position += 1;
+        }
+        record_layout.fnc_values = Some(FncValues::new(position, datatype, index_mode, AddrType::Direct));
+        module.record_layout.push(record_layout);
+        name
+    }
+
+    // get the name of a record layout for axis points of the given data type, create it if necessary
+    fn get_axis_pts_record_layout(&mut self, datatype: DataType) -> String {
+        let name = format!("RL_AXIS_PTS_{}", datatype);
+        let module = &mut self.a2l_file.project.module[0];
+        if !module.record_layout.iter().any(|record_layout| record_layout.name == name) {
+            let mut record_layout = RecordLayout::new(name.clone());
+            record_layout.axis_pts_x = Some(AxisPtsDim::new(
+                1,
+                datatype,
+                IndexOrder::IndexIncr,
+                AddrType::Direct,
+            ));
+            module.record_layout.push(record_layout);
+        }
+        name
+    }
+
+    // add a measurement or characteristic to a group, create the group if necessary
+    fn add_to_group(&mut self, group_name: &str, name: &str, is_characteristic: bool) {
+        let module = &mut self.a2l_file.project.module[0];
+        let group = match module.group.iter().position(|group| group.name == group_name) {
+            Some(index) => &mut module.group[index],
+            None => {
+                let mut group = Group::new(group_name.to_string(), String::new());
+                group.annotation.push(owner_annotation());
+                module.group.push(group);
+                module.group.last_mut().unwrap()
+            }
+        };
+        if is_characteristic {
+            group
+                .ref_characteristic
+                .get_or_insert_with(RefCharacteristic::new)
+                .identifier_list
+                .push(name.to_string());
+        } else {
+            group
+                .ref_measurement
+                .get_or_insert_with(RefMeasurement::new)
+                .identifier_list
+                .push(name.to_string());
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn create_characteristic(
         &self,
         name: &str,
@@ -79,8 +1383,9 @@ impl A2lCommentGenerator {
             min,
             max
         )
-    }    
+    }
 
+    #[allow(clippy::too_many_arguments)]
     fn create_measurement(
         &self,
         name: &str,
@@ -103,3 +1408,957 @@ impl A2lCommentGenerator {
         )
     }
 }
+
+// marks an object as generated, see OWNER_LABEL
+fn owner_annotation() -> Annotation {
+    let mut annotation = Annotation::new();
+    annotation.annotation_label = Some(AnnotationLabel::new(OWNER_LABEL.to_string()));
+    annotation
+}
+
+// value range of an a2l data type
+fn datatype_limits(datatype: DataType) -> (f64, f64) {
+    match datatype {
+        DataType::Ubyte => (0.0, u8::MAX as f64),
+        DataType::Sbyte => (i8::MIN as f64, i8::MAX as f64),
+        DataType::Uword => (0.0, u16::MAX as f64),
+        DataType::Sword => (i16::MIN as f64, i16::MAX as f64),
+        DataType::Ulong => (0.0, u32::MAX as f64),
+        DataType::Slong => (i32::MIN as f64, i32::MAX as f64),
+        DataType::AUint64 => (0.0, u64::MAX as f64),
+        DataType::AInt64 => (i64::MIN as f64, i64::MAX as f64),
+        DataType::Float16Ieee => (-65504.0, 65504.0),
+        DataType::Float32Ieee => (f32::MIN as f64, f32::MAX as f64),
+        DataType::Float64Ieee => (f64::MIN, f64::MAX),
+    }
+}
+
+// dimensions of an array from the a2l-matrix-dim annotation or else from the declaration
+fn matrix_dim(
+    declaration: &AnnotatedDeclaration,
+    a2l_code_comment: &A2lCodeComment,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<u16>, GeneratorError> {
+    let declared: usize = declaration.array_dims.iter().product();
+    if !a2l_code_comment.a2l_matrix_dim.is_empty() {
+        let annotated: usize = a2l_code_comment.a2l_matrix_dim.iter().map(|dim| *dim as usize).product();
+        if !declaration.array_dims.is_empty() && annotated != declared {
+            diagnostics.push(Diagnostic::new(
+                declaration.location.clone(),
+                declaration.declaration.trim_end_matches(';').len(),
+                format!(
+                    "a2l-matrix-dim describes {} values, but {} has {}",
+                    annotated, declaration.name, declared
+                ),
+            ));
+        }
+        return Ok(a2l_code_comment.a2l_matrix_dim.clone());
+    }
+    declaration
+        .array_dims
+        .iter()
+        .map(|dim| {
+            u16::try_from(*dim).map_err(|_| GeneratorError::Declaration {
+                location: declaration.location.clone(),
+                message: format!("the array dimension {} of {} is too large for MATRIX_DIM", dim, declaration.name),
+            })
+        })
+        .collect()
+}
+
+// the integer data type of a size in bytes
+fn integer_datatype(size: usize, signed: bool) -> DataType {
+    match (size, signed) {
+        (1, true) => DataType::Sbyte,
+        (1, false) => DataType::Ubyte,
+        (2, true) => DataType::Sword,
+        (2, false) => DataType::Uword,
+        (4, true) => DataType::Slong,
+        (4, false) => DataType::Ulong,
+        (_, true) => DataType::AInt64,
+        (_, false) => DataType::AUint64,
+    }
+}
+
+// the axes of a characteristic in the order of AXIS_DESCR
+const AXIS_LETTERS: [char; 5] = ['X', 'Y', 'Z', '4', '5'];
+
+// the NO_AXIS_PTS and AXIS_PTS entries of a record layout for the axis with the given index
+fn axis_layout(
+    record_layout: &mut RecordLayout,
+    index: usize,
+) -> (&mut Option<NoAxisPtsDim>, &mut Option<AxisPtsDim>) {
+    match index {
+        0 => (&mut record_layout.no_axis_pts_x, &mut record_layout.axis_pts_x),
+        1 => (&mut record_layout.no_axis_pts_y, &mut record_layout.axis_pts_y),
+        2 => (&mut record_layout.no_axis_pts_z, &mut record_layout.axis_pts_z),
+        3 => (&mut record_layout.no_axis_pts_4, &mut record_layout.axis_pts_4),
+        _ => (&mut record_layout.no_axis_pts_5, &mut record_layout.axis_pts_5),
+    }
+}
+
+// data type of a scalar type of the debug info, None for other types
+fn debug_datatype(debug_type: &DwarfType) -> Option<DataType> {
+    match debug_type {
+        DwarfType::Base {
+            size: 4,
+            encoding: Encoding::Float,
+        } => Some(DataType::Float32Ieee),
+        DwarfType::Base {
+            size: 8,
+            encoding: Encoding::Float,
+        } => Some(DataType::Float64Ieee),
+        DwarfType::Base {
+            size,
+            encoding: Encoding::Signed,
+        }
+        | DwarfType::Enum { size } => Some(integer_datatype(*size, true)),
+        DwarfType::Base {
+            size,
+            encoding: Encoding::Unsigned,
+        }
+        | DwarfType::Pointer { size } => Some(integer_datatype(*size, false)),
+        _ => None,
+    }
+}
+
+// size of an a2l data type in bytes
+fn datatype_size(datatype: DataType) -> usize {
+    match datatype {
+        DataType::Ubyte | DataType::Sbyte => 1,
+        DataType::Uword | DataType::Sword | DataType::Float16Ieee => 2,
+        DataType::Ulong | DataType::Slong | DataType::Float32Ieee => 4,
+        DataType::AUint64 | DataType::AInt64 | DataType::Float64Ieee => 8,
+    }
+}
+
+// name of the TYPEDEF_STRUCTURE of a C type, "struct engine" becomes "engine"
+// anonymous structs are named after their instance or member
+fn typedef_name(c_type: &str, fallback: &str) -> String {
+    if c_type.contains('{') {
+        return format!("{}_t", fallback);
+    }
+    let words: Vec<&str> = c_type.split_whitespace().collect();
+    match words.as_slice() {
+        ["struct" | "union", name] => name.to_string(),
+        _ => words.join("_"),
+    }
+}
+
+fn has_annotated_members(struct_type: &StructType) -> bool {
+    struct_type.members.iter().any(|member| match &member.struct_type {
+        Some(nested) => has_annotated_members(nested),
+        None => member.comment.is_some(),
+    })
+}
+
+// limits of the physical values, the raw limits of the data type converted by linear coefficients
+fn physical_limits(datatype: DataType, linear_coeffs: &[f64]) -> (f64, f64) {
+    let (min, max) = datatype_limits(datatype);
+    match linear_coeffs {
+        [a, b] => {
+            let (min, max) = (a * min + b, a * max + b);
+            (min.min(max), min.max(max))
+        }
+        _ => (min, max),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::c_types::{Enumerator, StructMember};
+    use crate::dwarf::DwarfMember;
+    use crate::dwarf::tests::build_debug_info;
+
+    fn declaration(comment: &str, c_type: &str, name: &str) -> AnnotatedDeclaration {
+        AnnotatedDeclaration {
+            comment: comment.to_string(),
+            comment_location: SourceLocation::new("test.c", 1, 1),
+            declaration: format!("{} {};", c_type, name),
+            location: SourceLocation::new("test.c", 2, 1),
+            c_type: c_type.to_string(),
+            base_type: c_type.to_string(),
+            name: name.to_string(),
+            array_dims: Vec::new(),
+            enumerators: Vec::new(),
+            struct_type: None,
+        }
+    }
+
+    // add a declaration to the generator and return the diagnostics
+    fn add(generator: &mut A2lCommentGenerator, comment: &str, c_type: &str, name: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        generator
+            .add_declaration(&declaration(comment, c_type, name), &mut diagnostics)
+            .unwrap();
+        diagnostics
+    }
+
+    #[test]
+    fn test_add_measurement() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        add(
+            &mut generator,
+            "a2l on\na2l-type measurement\na2l-unit m/s\na2l-max-refresh 10ms\na2l-group Speeds\n",
+            "uint16_t",
+            "velo",
+        );
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!(module.measurement.len(), 1);
+        let measurement = &module.measurement[0];
+        assert_eq!(measurement.name, "velo");
+        assert_eq!(measurement.datatype, DataType::Uword);
+        assert_eq!(measurement.conversion, "NO_COMPU_METHOD");
+        assert_eq!(measurement.lower_limit, 0.0);
+        assert_eq!(measurement.upper_limit, 65535.0);
+        assert_eq!(measurement.phys_unit.as_ref().unwrap().unit, "m/s");
+        assert_eq!(measurement.max_refresh.as_ref().unwrap().rate, 10);
+        assert_eq!(
+            module.group[0].ref_measurement.as_ref().unwrap().identifier_list,
+            vec!["velo".to_string()]
+        );
+        // the generated objects are marked, so that a merge can remove them once they're stale
+        let label = &measurement.annotation[0].annotation_label;
+        assert_eq!(label.as_ref().unwrap().label, OWNER_LABEL);
+        assert_eq!(module.group[0].annotation, measurement.annotation);
+    }
+
+    #[test]
+    fn test_one_sided_limits() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        add(&mut generator, "a2l on\na2l-type measurement\na2l-max 100\n", "int16_t", "torque");
+        add(&mut generator, "a2l on\na2l-type measurement\na2l-min 0\n", "int8_t", "load");
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!((module.measurement[0].lower_limit, module.measurement[0].upper_limit), (-32768.0, 100.0));
+        assert_eq!((module.measurement[1].lower_limit, module.measurement[1].upper_limit), (0.0, 127.0));
+    }
+
+    #[test]
+    fn test_add_characteristic() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        add(
+            &mut generator,
+            "a2l on\na2l-type characteristic\na2l-min -5\na2l-max 5\n",
+            "float",
+            "gain",
+        );
+        add(&mut generator, "a2l on\na2l-type characteristic\n", "float", "offset");
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!(module.characteristic.len(), 2);
+        let characteristic = &module.characteristic[0];
+        assert_eq!(characteristic.name, "gain");
+        assert_eq!(characteristic.deposit, "RL_VALUE_FLOAT32_IEEE");
+        assert_eq!(characteristic.lower_limit, -5.0);
+        assert_eq!(characteristic.upper_limit, 5.0);
+        // the record layout is shared by both characteristics
+        assert_eq!(module.record_layout.len(), 1);
+        assert_eq!(module.record_layout[0].name, "RL_VALUE_FLOAT32_IEEE");
+    }
+
+    #[test]
+    fn test_add_curve_and_val_blk() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        // the record holds the number of points, the points and the values
+        let mut curve = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type curve\na2l-axis speed NO_COMPU_METHOD 16 0 250\n",
+            "uint8_t",
+            "speed_curve",
+        );
+        curve.array_dims = vec![34];
+        generator.add_declaration(&curve, &mut Vec::new()).unwrap();
+        add(
+            &mut generator,
+            "a2l on\na2l-type characteristic\na2l-characteristic-type val_blk\na2l-matrix-dim 4 8\n",
+            "int16_t",
+            "block",
+        );
+        let module = &generator.a2l_file().project.module[0];
+        let curve = &module.characteristic[0];
+        assert_eq!(curve.characteristic_type, CharacteristicType::Curve);
+        assert_eq!(curve.axis_descr.len(), 1);
+        assert_eq!(curve.axis_descr[0].attribute, AxisDescrAttribute::StdAxis);
+        assert_eq!(curve.axis_descr[0].input_quantity, "speed");
+        assert_eq!(curve.axis_descr[0].max_axis_points, 16);
+        assert_eq!(curve.axis_descr[0].upper_limit, 250.0);
+        let block = &module.characteristic[1];
+        assert_eq!(block.characteristic_type, CharacteristicType::ValBlk);
+        assert_eq!(block.matrix_dim.as_ref().unwrap().dim_list, vec![4, 8]);
+    }
+
+    #[test]
+    fn test_add_arrays() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.set_legacy_arrays(true);
+        let mut samples = declaration("a2l on\n", "uint32_t", "samples");
+        samples.array_dims = vec![32];
+        let mut table = declaration("a2l on\na2l-type characteristic\n", "uint8_t", "table");
+        table.array_dims = vec![4, 8];
+        let mut name = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type ascii\n",
+            "char",
+            "name",
+        );
+        name.array_dims = vec![16];
+        let mut names = name.clone();
+        names.name = "names".to_string();
+        names.array_dims = vec![3, 16];
+        let mut mismatch = declaration("a2l on\na2l-matrix-dim 2 2\n", "uint8_t", "grid");
+        mismatch.array_dims = vec![3, 3];
+        for declaration in [&samples, &table, &name, &names] {
+            generator.add_declaration(declaration, &mut Vec::new()).unwrap();
+        }
+        let mut diagnostics = Vec::new();
+        generator.add_declaration(&mismatch, &mut diagnostics).unwrap();
+
+        let module = &generator.a2l_file().project.module[0];
+        let measurement = &module.measurement[0];
+        assert_eq!(measurement.matrix_dim.as_ref().unwrap().dim_list, vec![32]);
+        assert_eq!(measurement.array_size, Some(ArraySize::new(32)));
+        let table = &module.characteristic[0];
+        assert_eq!(table.characteristic_type, CharacteristicType::ValBlk);
+        assert_eq!(table.matrix_dim.as_ref().unwrap().dim_list, vec![4, 8]);
+        assert_eq!(table.number, Some(Number::new(32)));
+        let name = &module.characteristic[1];
+        assert_eq!(name.characteristic_type, CharacteristicType::Ascii);
+        assert_eq!(name.number, Some(Number::new(16)));
+        assert!(name.matrix_dim.is_none());
+        let names = &module.characteristic[2];
+        assert_eq!(names.number, Some(Number::new(16)));
+        assert_eq!(names.matrix_dim.as_ref().unwrap().dim_list, vec![3]);
+        // the annotation wins, but the mismatch is reported
+        assert_eq!(module.measurement[1].matrix_dim.as_ref().unwrap().dim_list, vec![2, 2]);
+        assert_eq!(module.measurement[1].array_size, None);
+        assert_eq!(diagnostics[0].message, "a2l-matrix-dim describes 4 values, but grid has 9");
+
+        // without the legacy keywords only MATRIX_DIM is written
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.add_declaration(&samples, &mut Vec::new()).unwrap();
+        let measurement = &generator.a2l_file().project.module[0].measurement[0];
+        assert!(measurement.matrix_dim.is_some());
+        assert_eq!(measurement.array_size, None);
+    }
+
+    #[test]
+    fn test_typedef_base_types() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        let mut speed = declaration("a2l on\n", "Speed_t", "speed");
+        speed.base_type = "unsigned short".to_string();
+        generator.add_declaration(&speed, &mut Vec::new()).unwrap();
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!(module.measurement[0].datatype, DataType::Uword);
+
+        let mut unknown = declaration("a2l on\n", "Unknown_t", "unknown");
+        let error = generator.add_declaration(&unknown, &mut Vec::new()).unwrap_err();
+        assert!(error.to_string().ends_with("unknown type 'Unknown_t'"));
+        unknown.base_type = "long double".to_string();
+        let error = generator.add_declaration(&unknown, &mut Vec::new()).unwrap_err();
+        assert!(error.to_string().ends_with("unknown type 'Unknown_t', resolved to 'long double'"));
+        assert_eq!(generator.a2l_file().project.module[0].measurement.len(), 1);
+    }
+
+    #[test]
+    fn test_target_profiles() {
+        let datatypes = |target: TargetProfile| {
+            let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+            generator.set_target(target);
+            for (c_type, name) in [("int", "a"), ("unsigned long", "b"), ("char", "c"), ("size_t", "d")] {
+                add(&mut generator, "a2l on\n", c_type, name);
+            }
+            let mut pointer = declaration("a2l on\n", "uint8_t", "e");
+            pointer.base_type = "uint8_t *".to_string();
+            generator.add_declaration(&pointer, &mut Vec::new()).unwrap();
+            let module = &generator.a2l_file().project.module[0];
+            module.measurement.iter().map(|measurement| measurement.datatype).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            datatypes(TargetProfile::default()),
+            vec![DataType::Slong, DataType::Ulong, DataType::Sbyte, DataType::Ulong, DataType::Ulong]
+        );
+        assert_eq!(
+            datatypes(TargetProfile::from_data_model("lp64").unwrap()),
+            vec![DataType::Slong, DataType::AUint64, DataType::Sbyte, DataType::AUint64, DataType::AUint64]
+        );
+        let mut ip16 = TargetProfile::from_data_model("ip16").unwrap();
+        ip16.char_signed = false;
+        assert_eq!(
+            datatypes(ip16),
+            vec![DataType::Sword, DataType::Ulong, DataType::Ubyte, DataType::Uword, DataType::Uword]
+        );
+    }
+
+    #[test]
+    fn test_byte_order_and_alignment() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        let mod_common = generator.a2l_file().project.module[0].mod_common.as_ref().unwrap();
+        assert_eq!(mod_common.byte_order.as_ref().unwrap().byte_order, ByteOrderEnum::MsbLast);
+        assert_eq!(mod_common.alignment_int64.as_ref().unwrap().alignment_border, 8);
+
+        let mut target = TargetProfile::from_data_model("ip16").unwrap();
+        target.big_endian = true;
+        generator.set_target(target);
+        add(&mut generator, "a2l on\na2l-byte-order little_endian\n", "uint16_t", "sensor");
+        add(&mut generator, "a2l on\na2l-type characteristic\n", "uint16_t", "limit");
+        let module = &generator.a2l_file().project.module[0];
+        let mod_common = module.mod_common.as_ref().unwrap();
+        assert_eq!(mod_common.byte_order.as_ref().unwrap().byte_order, ByteOrderEnum::MsbFirst);
+        assert_eq!(mod_common.alignment_byte.as_ref().unwrap().alignment_border, 1);
+        assert_eq!(mod_common.alignment_long.as_ref().unwrap().alignment_border, 2);
+        assert_eq!(mod_common.alignment_float64_ieee.as_ref().unwrap().alignment_border, 2);
+        assert_eq!(module.measurement[0].byte_order.as_ref().unwrap().byte_order, ByteOrderEnum::MsbLast);
+        assert!(module.characteristic[0].byte_order.is_none());
+    }
+
+    #[test]
+    fn test_record_layouts() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        add(&mut generator, "a2l on\na2l-type characteristic\n", "uint16_t", "value");
+        add(&mut generator, "a2l on\na2l-type characteristic\na2l-matrix-dim 4\n", "uint16_t", "block");
+        let mut full_map = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type map\n\
+             a2l-axis speed NO_COMPU_METHOD 8 0 8000\na2l-axis load NO_COMPU_METHOD 4 0 100\n",
+            "uint8_t",
+            "full_map",
+        );
+        full_map.array_dims = vec![48];
+        generator.add_declaration(&full_map, &mut Vec::new()).unwrap();
+        add(
+            &mut generator,
+            "a2l on\na2l-type characteristic\na2l-characteristic-type curve\na2l-axis-ref speed_axis\n",
+            "uint16_t",
+            "com_curve",
+        );
+        let mixed = A2lCodeComment::parse(
+            "a2l on\na2l-axis-ref speed_axis\na2l-axis load NO_COMPU_METHOD 4 0 100\n",
+            &SourceLocation::new("test.c", 1, 1),
+            &mut Vec::new(),
+        );
+        let mixed_map =
+            generator.get_record_layout(CharacteristicType::Map, DataType::Ubyte, &mixed.a2l_axes, IndexMode::ColumnDir);
+        assert_eq!(mixed_map, "RL_MAP_UBYTE_STD_Y_COLUMN_DIR");
+
+        let module = &generator.a2l_file().project.module[0];
+        let deposits: Vec<&str> = module.characteristic.iter().map(|c| c.deposit.as_str()).collect();
+        assert_eq!(
+            deposits,
+            vec![
+                "RL_VALUE_UWORD",
+                "RL_VALUE_UWORD",
+                "RL_MAP_UBYTE",
+                "RL_VALUE_UWORD"
+            ]
+        );
+        assert_eq!(module.record_layout.len(), 3);
+        let value = &module.record_layout[0];
+        let fnc_values = value.fnc_values.as_ref().unwrap();
+        assert_eq!((fnc_values.position, fnc_values.datatype), (1, DataType::Uword));
+        assert_eq!(fnc_values.index_mode, IndexMode::RowDir);
+        let map = &module.record_layout[1];
+        assert_eq!(map.no_axis_pts_x.as_ref().unwrap().position, 1);
+        assert_eq!(map.no_axis_pts_y.as_ref().unwrap().position, 2);
+        assert_eq!(map.axis_pts_x.as_ref().unwrap().position, 3);
+        assert_eq!(map.axis_pts_y.as_ref().unwrap().datatype, DataType::Ubyte);
+        assert_eq!(map.fnc_values.as_ref().unwrap().position, 5);
+        let mixed = &module.record_layout[2];
+        assert!(mixed.axis_pts_x.is_none());
+        assert_eq!(mixed.no_axis_pts_y.as_ref().unwrap().position, 1);
+        assert_eq!(mixed.axis_pts_y.as_ref().unwrap().position, 2);
+        let fnc_values = mixed.fnc_values.as_ref().unwrap();
+        assert_eq!((fnc_values.position, fnc_values.index_mode), (3, IndexMode::ColumnDir));
+    }
+
+    #[test]
+    fn test_std_axis_record_size() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        // the count and the points of the axis don't fit into the 8 values of the array
+        let mut curve = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type curve\na2l-axis speed NO_COMPU_METHOD 8 0 100\n",
+            "uint8_t",
+            "curve",
+        );
+        curve.array_dims = vec![8];
+        let error = generator.add_declaration(&curve, &mut Vec::new()).unwrap_err();
+        assert!(matches!(&error, GeneratorError::Declaration { location, .. } if location.line == 2));
+        assert!(error.to_string().contains("takes 18 bytes, but the variable has 8"));
+        assert!(generator.a2l_file().project.module[0].characteristic.is_empty());
+        assert!(generator.a2l_file().project.module[0].record_layout.is_empty());
+
+        // the count is padded to the alignment of the uint32_t points
+        let mut curve = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type curve\na2l-axis speed NO_COMPU_METHOD 4 0 100\n",
+            "uint32_t",
+            "aligned_curve",
+        );
+        curve.array_dims = vec![9];
+        generator.add_declaration(&curve, &mut Vec::new()).unwrap();
+    }
+
+    #[test]
+    fn test_add_axis_pts_and_com_axis() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        let mut axis = declaration(
+            "a2l on\na2l-type axis_pts\na2l-input-quantity engine_speed\na2l-min 0\na2l-max 8000\n",
+            "uint16_t",
+            "speed_axis",
+        );
+        axis.array_dims = vec![8];
+        let mut map = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type map\na2l-axis-ref speed_axis\na2l-axis-ref speed_axis\n",
+            "uint8_t",
+            "ignition_map",
+        );
+        map.array_dims = vec![8, 8];
+        generator.add_declaration(&map, &mut Vec::new()).unwrap();
+        generator.add_declaration(&axis, &mut Vec::new()).unwrap();
+        let mut errors = Vec::new();
+        generator.finish(&mut errors);
+        assert!(errors.is_empty());
+
+        let module = &generator.a2l_file().project.module[0];
+        let axis_pts = &module.axis_pts[0];
+        assert_eq!(axis_pts.name, "speed_axis");
+        assert_eq!(axis_pts.input_quantity, "engine_speed");
+        assert_eq!(axis_pts.max_axis_points, 8);
+        assert_eq!(axis_pts.deposit_record, "RL_AXIS_PTS_UWORD");
+        let axis_descr = &module.characteristic[0].axis_descr[1];
+        assert_eq!(axis_descr.attribute, AxisDescrAttribute::ComAxis);
+        assert_eq!(axis_descr.axis_pts_ref.as_ref().unwrap().axis_points, "speed_axis");
+        assert_eq!(axis_descr.input_quantity, "engine_speed");
+        assert_eq!(axis_descr.max_axis_points, 8);
+        assert_eq!(axis_descr.upper_limit, 8000.0);
+    }
+
+    #[test]
+    fn test_invalid_axis_references() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        let mut axis = declaration("a2l on\na2l-type axis_pts\n", "float", "short_axis");
+        axis.array_dims = vec![4];
+        let mut curve = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type curve\na2l-axis-ref short_axis\n",
+            "float",
+            "curve",
+        );
+        curve.array_dims = vec![6];
+        let mut map = declaration(
+            "a2l on\na2l-type characteristic\na2l-characteristic-type curve\na2l-axis-ref missing_axis\n",
+            "float",
+            "other_curve",
+        );
+        map.array_dims = vec![4];
+        generator.add_declaration(&axis, &mut Vec::new()).unwrap();
+        generator.add_declaration(&curve, &mut Vec::new()).unwrap();
+        generator.add_declaration(&map, &mut Vec::new()).unwrap();
+        let mut errors = Vec::new();
+        generator.finish(&mut errors);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].to_string().contains("curve has 6 values in the direction of axis 1"));
+        assert!(errors[1].to_string().contains("unknown AXIS_PTS 'missing_axis'"));
+
+        // axis points must be arrays
+        let scalar_axis = declaration("a2l on\na2l-type axis_pts\n", "float", "scalar");
+        assert!(generator.add_declaration(&scalar_axis, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_add_compu_methods() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        add(&mut generator, "a2l on\na2l-linear-coeffs 0.1 -40\na2l-unit degC\n", "uint8_t", "oil_temp");
+        add(&mut generator, "a2l on\na2l-linear-coeffs 0.1 -40\na2l-unit degC\n", "uint8_t", "water_temp");
+        add(
+            &mut generator,
+            "a2l on\na2l-type characteristic\na2l-rat-func-coeffs 0 2 0 0 0 1\na2l-format %6.1\n",
+            "uint16_t",
+            "gain",
+        );
+        add(&mut generator, "a2l on\n", "uint8_t", "raw");
+
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!(module.compu_method.len(), 2);
+        let linear = &module.compu_method[0];
+        assert_eq!(linear.name, "CM_oil_temp");
+        assert_eq!(linear.conversion_type, ConversionType::Linear);
+        assert_eq!(linear.coeffs_linear, Some(CoeffsLinear::new(0.1, -40.0)));
+        assert_eq!(linear.unit, "degC");
+        assert_eq!(linear.format, "%8.3");
+        let rat_func = &module.compu_method[1];
+        assert_eq!(rat_func.conversion_type, ConversionType::RatFunc);
+        assert_eq!(rat_func.coeffs, Some(Coeffs::new(0.0, 2.0, 0.0, 0.0, 0.0, 1.0)));
+        assert_eq!(rat_func.format, "%6.1");
+
+        assert_eq!(module.measurement[0].conversion, "CM_oil_temp");
+        assert_eq!(module.measurement[1].conversion, "CM_oil_temp");
+        assert_eq!(module.measurement[2].conversion, "NO_COMPU_METHOD");
+        assert_eq!(module.characteristic[0].conversion, rat_func.name);
+        // the raw limits 0..255 are converted to physical values
+        assert_eq!(module.measurement[0].lower_limit, -40.0);
+        assert!((module.measurement[0].upper_limit + 14.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_add_verbal_tables() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        let mut gear = declaration("a2l on\n", "gear_t", "gear");
+        gear.enumerators = vec![
+            Enumerator { name: "REVERSE".to_string(), value: -1 },
+            Enumerator { name: "NEUTRAL".to_string(), value: 0 },
+            Enumerator { name: "FIRST".to_string(), value: 1 },
+        ];
+        generator.add_declaration(&gear, &mut Vec::new()).unwrap();
+        add(&mut generator, "a2l on\na2l-vtab 0 \"Off\" 1 \"On\"\n", "uint8_t", "fan");
+        add(&mut generator, "a2l on\na2l-vtab 0 \"Off\" 1 \"On\"\n", "uint8_t", "pump");
+
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!(module.compu_vtab.len(), 2);
+        let gear_vtab = &module.compu_vtab[0];
+        assert_eq!(gear_vtab.name, "VTAB_gear");
+        assert_eq!(gear_vtab.number_value_pairs, 3);
+        assert_eq!(gear_vtab.value_pairs[0], ValuePairsStruct::new(-1.0, "REVERSE".to_string()));
+        assert_eq!(module.compu_method[0].conversion_type, ConversionType::TabVerb);
+        assert_eq!(
+            module.compu_method[0].compu_tab_ref,
+            Some(CompuTabRef::new("VTAB_gear".to_string()))
+        );
+
+        let gear = &module.measurement[0];
+        assert_eq!(gear.datatype, DataType::Slong);
+        assert_eq!(gear.conversion, "CM_gear");
+        assert_eq!((gear.lower_limit, gear.upper_limit), (-1.0, 1.0));
+        // fan and pump share the same verbal table
+        assert_eq!(module.measurement[1].conversion, "CM_fan");
+        assert_eq!(module.measurement[2].conversion, "CM_fan");
+        assert_eq!(module.compu_vtab[1].value_pairs[1].out_val, "On");
+    }
+
+    fn member(name: &str, c_type: &str, comment: Option<&str>) -> StructMember {
+        StructMember {
+            name: name.to_string(),
+            c_type: c_type.to_string(),
+            base_type: c_type.to_string(),
+            array_dims: Vec::new(),
+            is_bitfield: false,
+            declaration: format!("{} {};", c_type, name),
+            location: SourceLocation::default(),
+            comment: comment.map(|comment| (comment.to_string(), SourceLocation::default())),
+            enumerators: Vec::new(),
+            struct_type: None,
+        }
+    }
+
+    fn engine() -> AnnotatedDeclaration {
+        let mut oil = member("oil", "struct sensor", None);
+        oil.struct_type = Some(StructType {
+            is_union: false,
+            members: vec![
+                member("raw", "uint8_t", None),
+                member("temp", "float", Some("a2l on\na2l-unit degC\n")),
+            ],
+        });
+        let mut map = member("ignition", "uint8_t", Some("a2l on\na2l-type characteristic\n"));
+        map.array_dims = vec![2];
+        let mut engine = declaration("a2l on\na2l-description main engine\n", "engine_t", "engine");
+        engine.struct_type = Some(StructType {
+            is_union: false,
+            members: vec![
+                member("state", "uint8_t", None),
+                member("rpm", "uint16_t", Some("a2l on\n")),
+                oil,
+                map,
+            ],
+        });
+        engine
+    }
+
+    #[test]
+    fn test_add_struct_members() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.add_declaration(&engine(), &mut Vec::new()).unwrap();
+        let module = &generator.a2l_file().project.module[0];
+        let names: Vec<_> = module.measurement.iter().map(|measurement| measurement.name.as_str()).collect();
+        assert_eq!(names, vec!["engine.rpm", "engine.oil.temp"]);
+        assert_eq!(module.characteristic[0].name, "engine.ignition");
+        assert!(module.instance.is_empty());
+    }
+
+    #[test]
+    fn test_add_typedef_structures() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.set_typedef_structures(true);
+        generator.add_declaration(&engine(), &mut Vec::new()).unwrap();
+        let mut second = engine();
+        second.name = "second_engine".to_string();
+        generator.add_declaration(&second, &mut Vec::new()).unwrap();
+
+        let module = &generator.a2l_file().project.module[0];
+        assert!(module.measurement.is_empty());
+        assert_eq!(module.instance.len(), 2);
+        assert_eq!(module.instance[0].type_ref, "engine_t");
+        assert_eq!(module.instance[0].long_identifier, "main engine");
+        assert_eq!(module.instance[1].name, "second_engine");
+
+        // the nested struct is added first, the typedefs are shared by both instances
+        assert_eq!(module.typedef_structure.len(), 2);
+        let sensor = &module.typedef_structure[0];
+        assert_eq!((sensor.name.as_str(), sensor.total_size), ("sensor", 8));
+        assert_eq!(sensor.structure_component[0].component_name, "temp");
+        assert_eq!(sensor.structure_component[0].component_type, "sensor.temp");
+        assert_eq!(sensor.structure_component[0].address_offset, 4);
+        let engine = &module.typedef_structure[1];
+        assert_eq!(engine.total_size, 16);
+        let components: Vec<_> = engine
+            .structure_component
+            .iter()
+            .map(|component| {
+                (
+                    component.component_name.as_str(),
+                    component.component_type.as_str(),
+                    component.address_offset,
+                )
+            })
+            .collect();
+        assert_eq!(
+            components,
+            vec![
+                ("rpm", "engine_t.rpm", 2),
+                ("oil", "sensor", 4),
+                ("ignition", "engine_t.ignition", 12),
+            ]
+        );
+        assert_eq!(module.typedef_measurement.len(), 2);
+        assert_eq!(module.typedef_measurement[0].datatype, DataType::Uword);
+        assert_eq!(module.typedef_characteristic[0].name, "engine_t.ignition");
+    }
+
+    #[test]
+    fn test_bitfields() {
+        let mut a = member("a", "uint8_t", None);
+        a.is_bitfield = true;
+        let mut b = member("b", "uint8_t", Some("a2l on\n"));
+        b.is_bitfield = true;
+        let mut flags = declaration("a2l on\n", "struct flags", "f");
+        flags.struct_type = Some(StructType {
+            is_union: false,
+            members: vec![a, b, member("c", "uint16_t", Some("a2l on\n"))],
+        });
+        // the offsets of the members can't be derived, with and without typedef structures
+        for typedef_structures in [false, true] {
+            let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+            generator.set_typedef_structures(typedef_structures);
+            let error = generator.add_declaration(&flags, &mut Vec::new()).unwrap_err();
+            assert!(error.to_string().contains("bitfields are not supported"), "{}", error);
+            let module = &generator.a2l_file().project.module[0];
+            assert!(module.measurement.is_empty() && module.typedef_structure.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_typedef_axis_references() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.set_typedef_structures(true);
+        let mut axis = member("axis", "float", Some("a2l on\na2l-type axis_pts\n"));
+        axis.array_dims = vec![4];
+        let curve_comment = "a2l on\na2l-type characteristic\na2l-characteristic-type curve\na2l-axis-ref ";
+        let mut curve = member("curve", "float", Some(&format!("{}table_t.axis\n", curve_comment)));
+        curve.array_dims = vec![4];
+        let mut broken = member("broken", "float", Some(&format!("{}missing_axis\n", curve_comment)));
+        broken.array_dims = vec![4];
+        let mut table = declaration("a2l on\n", "table_t", "table");
+        table.struct_type = Some(StructType {
+            is_union: false,
+            members: vec![axis, curve, broken],
+        });
+        generator.add_declaration(&table, &mut Vec::new()).unwrap();
+        let mut errors = Vec::new();
+        generator.finish(&mut errors);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("unknown AXIS_PTS or TYPEDEF_AXIS 'missing_axis'"));
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!(module.typedef_characteristic[0].axis_descr[0].max_axis_points, 4);
+    }
+
+    #[test]
+    fn test_set_addresses() {
+        let symbols = HashMap::from([
+            ("engine".to_string(), LinkerSymbol { address: 0x2000_0100, size: 16 }),
+            ("speed".to_string(), LinkerSymbol { address: 0x2000_0010, size: 2 }),
+        ]);
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.add_declaration(&engine(), &mut Vec::new()).unwrap();
+        add(&mut generator, "a2l on\n", "uint16_t", "speed");
+        add(&mut generator, "a2l on\na2l-type characteristic\n", "uint8_t", "removed");
+        let mut errors = Vec::new();
+        let mut diagnostics = Vec::new();
+        generator.set_addresses(&symbols, &mut errors, &mut diagnostics);
+
+        let module = &generator.a2l_file().project.module[0];
+        let address = |name: &str| {
+            module
+                .measurement
+                .iter()
+                .find(|measurement| measurement.name == name)
+                .map(|measurement| measurement.ecu_address.as_ref().unwrap().address)
+        };
+        assert_eq!(address("engine.rpm"), Some(0x2000_0102));
+        assert_eq!(address("engine.oil.temp"), Some(0x2000_0108));
+        assert_eq!(address("speed"), Some(0x2000_0010));
+        assert_eq!(module.characteristic[0].name, "engine.ignition");
+        assert_eq!(module.characteristic[0].address, 0x2000_010c);
+        assert_eq!(module.characteristic[1].address, 0);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("'removed' is not in the symbol table of the firmware"));
+        assert!(diagnostics.is_empty());
+
+        // the size of speed in the firmware doesn't match uint16_t, an unknown size isn't checked
+        let symbols = HashMap::from([
+            ("engine".to_string(), LinkerSymbol { address: 0x2000_0100, size: 0 }),
+            ("speed".to_string(), LinkerSymbol { address: 0x2000_0010, size: 4 }),
+        ]);
+        generator.set_addresses(&symbols, &mut Vec::new(), &mut diagnostics);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "'speed' has 4 bytes in the firmware, but its C type has 2 bytes");
+
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.set_typedef_structures(true);
+        generator.add_declaration(&engine(), &mut Vec::new()).unwrap();
+        generator.set_addresses(&symbols, &mut errors, &mut diagnostics);
+        assert_eq!(generator.a2l_file().project.module[0].instance[0].start_address, 0x2000_0100);
+    }
+
+    #[test]
+    fn test_debug_info() {
+        // the layout of engine with packed structs, ignition has 3 elements and speed 4 bytes
+        let base = |size, encoding| DwarfType::Base { size, encoding };
+        let member = |name: &str, offset, member_type| DwarfMember {
+            name: name.to_string(),
+            offset,
+            member_type,
+        };
+        let oil = DwarfType::Struct {
+            size: 5,
+            members: vec![
+                member("raw", 0, base(1, Encoding::Unsigned)),
+                member("temp", 1, base(4, Encoding::Float)),
+            ],
+        };
+        let ignition = DwarfType::Array {
+            element: Box::new(base(1, Encoding::Unsigned)),
+            dims: vec![3],
+        };
+        let engine_type = DwarfType::Struct {
+            size: 11,
+            members: vec![
+                member("state", 0, base(1, Encoding::Unsigned)),
+                member("rpm", 1, base(2, Encoding::Unsigned)),
+                member("oil", 3, oil),
+                member("ignition", 8, ignition),
+            ],
+        };
+        let debug_info = || {
+            build_debug_info(vec![
+                ("engine", engine_type.clone()),
+                ("speed", base(4, Encoding::Unsigned)),
+            ])
+        };
+
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.set_debug_info(debug_info(), false);
+        let mut diagnostics = Vec::new();
+        generator.add_declaration(&engine(), &mut diagnostics).unwrap();
+        diagnostics.extend(add(&mut generator, "a2l on\n", "uint16_t", "speed"));
+        diagnostics.extend(add(&mut generator, "a2l on\n", "uint8_t", "missing"));
+        let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "'engine.rpm' differs from the debug info of the firmware: offset 1 instead of 2, the C source is used",
+                "'engine.oil.temp' differs from the debug info of the firmware: offset 4 instead of 8, the C source is used",
+                "'engine.ignition' differs from the debug info of the firmware: offset 8 instead of 12, \
+                 dimensions [3] instead of [2], the C source is used",
+                "'speed' differs from the debug info of the firmware: data type ULONG instead of UWORD, \
+                 the C source is used",
+                "'missing' is not in the debug info of the firmware, its layout isn't verified",
+            ]
+        );
+        assert_eq!(generator.a2l_file().project.module[0].measurement[2].datatype, DataType::Uword);
+
+        // the debug info wins
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.set_debug_info(debug_info(), true);
+        generator.add_declaration(&engine(), &mut Vec::new()).unwrap();
+        add(&mut generator, "a2l on\n", "uint16_t", "speed");
+        let symbols = HashMap::from([
+            ("engine".to_string(), LinkerSymbol { address: 0x1000, size: 11 }),
+            ("speed".to_string(), LinkerSymbol { address: 0x2000, size: 4 }),
+        ]);
+        generator.set_addresses(&symbols, &mut Vec::new(), &mut Vec::new());
+        let module = &generator.a2l_file().project.module[0];
+        assert_eq!(module.measurement[0].ecu_address.as_ref().unwrap().address, 0x1001);
+        assert_eq!(module.measurement[1].ecu_address.as_ref().unwrap().address, 0x1004);
+        assert_eq!(module.measurement[2].datatype, DataType::Ulong);
+        assert_eq!(module.characteristic[0].address, 0x1008);
+        assert_eq!(module.characteristic[0].matrix_dim.as_ref().unwrap().dim_list, vec![3]);
+
+        // the layout of a typedef structure
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        generator.set_typedef_structures(true);
+        generator.set_debug_info(debug_info(), true);
+        let mut diagnostics = Vec::new();
+        generator.add_declaration(&engine(), &mut diagnostics).unwrap();
+        // the sizes of both structs and the offsets of rpm, oil, temp and ignition
+        assert_eq!(diagnostics.len(), 6);
+        let module = &generator.a2l_file().project.module[0];
+        let offsets = |name: &str| {
+            let typedef = module.typedef_structure.iter().find(|typedef| typedef.name == name).unwrap();
+            let offsets: Vec<u32> = typedef
+                .structure_component
+                .iter()
+                .map(|component| component.address_offset)
+                .collect();
+            (typedef.total_size, offsets)
+        };
+        assert_eq!(offsets("engine_t"), (11, vec![1, 3, 8]));
+        assert_eq!(offsets("sensor"), (5, vec![1]));
+    }
+
+    #[test]
+    fn test_typedef_name() {
+        assert_eq!(typedef_name("engine_t", "engine"), "engine_t");
+        assert_eq!(typedef_name("struct  sensor", "oil"), "sensor");
+        assert_eq!(typedef_name("structure_t", "x"), "structure_t");
+        assert_eq!(typedef_name("struct { int a; }", "engine.oil"), "engine.oil_t");
+    }
+
+    #[test]
+    fn test_invalid_annotation() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        let diagnostics = add(&mut generator, "a2l on\na2l-max high\n", "uint8_t", "x");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, SourceLocation::new("test.c", 2, 9));
+        // the object is still created with the default limits
+        assert_eq!(generator.a2l_file().project.module[0].measurement[0].upper_limit, 255.0);
+    }
+
+    #[test]
+    fn test_project_and_module_name() {
+        let generator = A2lCommentGenerator::new("Ecu", "Main");
+        assert_eq!(generator.a2l_file().project.name, "Ecu");
+        assert_eq!(generator.a2l_file().project.module[0].name, "Main");
+    }
+
+    #[test]
+    fn test_skip_without_a2l_on() {
+        let mut generator = A2lCommentGenerator::new("new_project", "new_module");
+        add(&mut generator, "a2l off\n", "int", "hidden");
+        let module = &generator.a2l_file().project.module[0];
+        assert!(module.measurement.is_empty());
+        assert!(module.characteristic.is_empty());
+    }
+
+    #[test]
+    fn test_parse_max_refresh() {
+        let max_refresh = parse_max_refresh("50ms").unwrap();
+        assert_eq!((max_refresh.scaling_unit, max_refresh.rate), (3, 50));
+        let max_refresh = parse_max_refresh("2 s").unwrap();
+        assert_eq!((max_refresh.scaling_unit, max_refresh.rate), (6, 2));
+        assert!(parse_max_refresh("fast").is_none());
+    }
+}
@@ -0,0 +1,244 @@
+// tokenizer and parser of the a2l annotations inside C comments
+// the comment markers are already replaced by spaces, every line of the comment is parsed on its own:
+//
+//   annotation    = { line } ;
+//   line          = statement | text ;
+//   statement     = switch | keyword { argument } [ continuation ] ;
+//   switch        = "a2l" ( "on" | "off" ) ;
+//   keyword       = "a2l-" name ;                   e.g. a2l-max-refresh
+//   name          = letter { letter | digit | "-" | "_" } ;
+//   argument      = word | quoted-string ;
+//   word          = any characters except whitespace, not starting with '"' ;
+//   quoted-string = '"' { character | '\"' | '\\' } '"' ;
+//   continuation  = "\" end-of-line ;               the statement goes on in the next line
+//   text          = any line not starting with "a2l" or "a2l-" ;
+//
+// a keyword is always the first token of a statement and is compared as a whole, so a2l-max-refresh
+// never matches a2l-max and keywords inside the arguments, e.g. in a description, are plain text
+
+use crate::diagnostics::Diagnostic;
+use crate::error::SourceLocation;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    // text of the token, quoted strings without quotes and escapes
+    pub text: String,
+    // line inside the comment, starting at 0
+    pub line: usize,
+    // column inside the line in characters, starting at 0
+    pub column: usize,
+    // number of characters of the token in the source
+    pub length: usize,
+}
+
+impl Token {
+    // get the position of the token in the source file
+    pub fn location(&self, comment_location: &SourceLocation) -> SourceLocation {
+        comment_location.advance(self.line, self.column)
+    }
+
+    pub fn diagnostic(&self, comment_location: &SourceLocation, message: String) -> Diagnostic {
+        Diagnostic::new(self.location(comment_location), self.length, message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub keyword: Token,
+    pub arguments: Vec<Token>,
+}
+
+impl Statement {
+    // all arguments joined by a single space, used for free text like descriptions
+    pub fn text(&self) -> String {
+        self.arguments
+            .iter()
+            .map(|argument| argument.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// split a comment into its annotation statements, text lines are skipped
+pub fn parse_statements(
+    comment: &str,
+    location: &SourceLocation,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    // statement which is continued in the next line
+    let mut current: Option<Statement> = None;
+
+    for (line_index, line) in comment.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut position = skip_whitespace(&chars, 0);
+        let mut statement = match current.take() {
+            Some(statement) => statement,
+            None => {
+                let keyword = read_word(&chars, position, line_index);
+                if keyword.text != "a2l" && !keyword.text.starts_with("a2l-") {
+                    // free text
+                    continue;
+                }
+                position += keyword.length;
+                Statement {
+                    keyword,
+                    arguments: Vec::new(),
+                }
+            }
+        };
+
+        loop {
+            position = skip_whitespace(&chars, position);
+            if position >= chars.len() {
+                statements.push(statement);
+                break;
+            }
+            if chars[position] == '\\' && skip_whitespace(&chars, position + 1) >= chars.len() {
+                current = Some(statement);
+                break;
+            }
+            let argument = if chars[position] == '"' {
+                read_quoted_string(&chars, position, line_index, location, diagnostics)
+            } else {
+                read_word(&chars, position, line_index)
+            };
+            position += argument.length;
+            statement.arguments.push(argument);
+        }
+    }
+    // a continuation in the last line
+    if let Some(statement) = current {
+        statements.push(statement);
+    }
+    statements
+}
+
+// true if the comment contains the switch "a2l on", which starts an annotation block
+pub fn is_a2l_on(comment: &str) -> bool {
+    let statements = parse_statements(comment, &SourceLocation::default(), &mut Vec::new());
+    (statements.iter()).any(|statement| statement.keyword.text == "a2l" && statement.text() == "on")
+}
+
+fn skip_whitespace(chars: &[char], mut position: usize) -> usize {
+    while position < chars.len() && chars[position].is_whitespace() {
+        position += 1;
+    }
+    position
+}
+
+fn read_word(chars: &[char], start: usize, line_index: usize) -> Token {
+    let mut end = start;
+    while end < chars.len() && !chars[end].is_whitespace() {
+        end += 1;
+    }
+    Token {
+        text: chars[start..end].iter().collect(),
+        line: line_index,
+        column: start,
+        length: end - start,
+    }
+}
+
+fn read_quoted_string(
+    chars: &[char],
+    start: usize,
+    line_index: usize,
+    location: &SourceLocation,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Token {
+    let mut text = String::new();
+    let mut position = start + 1;
+    let mut terminated = false;
+    while position < chars.len() {
+        match chars[position] {
+            '"' => {
+                terminated = true;
+                position += 1;
+                break;
+            }
+            '\\' if position + 1 < chars.len() && matches!(chars[position + 1], '"' | '\\') => {
+                text.push(chars[position + 1]);
+                position += 2;
+            }
+            c => {
+                text.push(c);
+                position += 1;
+            }
+        }
+    }
+    let token = Token {
+        text,
+        line: line_index,
+        column: start,
+        length: position - start,
+    };
+    if !terminated {
+        diagnostics.push(token.diagnostic(location, "unterminated string".to_string()));
+    }
+    token
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(comment: &str) -> (Vec<Statement>, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let statements = parse_statements(comment, &SourceLocation::new("", 1, 1), &mut diagnostics);
+        (statements, diagnostics)
+    }
+
+    #[test]
+    fn test_keywords_and_text() {
+        let (statements, diagnostics) = parse(
+            "  some text before\n  a2l on\n  a2l-max-refresh 50ms\n  a2l-description speed, see a2l-unit\n",
+        );
+        assert!(diagnostics.is_empty());
+        assert_eq!(statements.len(), 3);
+        assert_eq!(statements[0].keyword.text, "a2l");
+        assert_eq!(statements[0].text(), "on");
+        assert_eq!(statements[1].keyword.text, "a2l-max-refresh");
+        assert_eq!(statements[1].arguments[0].text, "50ms");
+        assert_eq!(statements[1].arguments[0].line, 2);
+        assert_eq!(statements[1].arguments[0].column, 18);
+        assert_eq!(statements[2].keyword.text, "a2l-description");
+        assert_eq!(statements[2].text(), "speed, see a2l-unit");
+    }
+
+    #[test]
+    fn test_quoted_strings() {
+        let (statements, diagnostics) = parse(r#"a2l-vtab 0 "Off  state" 1 "say \"on\"""#);
+        assert!(diagnostics.is_empty());
+        let arguments: Vec<_> = statements[0].arguments.iter().map(|a| a.text.as_str()).collect();
+        assert_eq!(arguments, vec!["0", "Off  state", "1", r#"say "on""#]);
+        assert_eq!(statements[0].arguments[1].column, 11);
+        assert_eq!(statements[0].arguments[1].length, 12);
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let (statements, _) = parse("a2l-description first line \\\n    second line\na2l-unit m\n");
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].text(), "first line second line");
+        assert_eq!(statements[0].arguments[2].line, 1);
+        assert_eq!(statements[1].keyword.text, "a2l-unit");
+    }
+
+    #[test]
+    fn test_is_a2l_on() {
+        assert!(is_a2l_on("  some text\n  a2l on\n"));
+        assert!(!is_a2l_on("  a2l online calibration"));
+        assert!(!is_a2l_on("  a2l off"));
+        assert!(!is_a2l_on("  see the a2l on switch"));
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let (statements, diagnostics) = parse("a2l-unit \"m/s\n");
+        assert_eq!(statements[0].text(), "m/s");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unterminated string");
+        assert_eq!(diagnostics[0].location, SourceLocation::new("", 1, 10));
+    }
+}
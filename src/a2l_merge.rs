@@ -0,0 +1,1100 @@
+// merging the generated objects into an existing a2l file
+// objects are matched by their names, objects which weren't generated are left as they are
+// generated objects carry the OWNER_LABEL annotation, only those are removed once they're stale
+
+use std::collections::HashSet;
+
+use a2lfile::*;
+
+use crate::a2l_comment_generator::OWNER_LABEL;
+use crate::diagnostics::Diagnostic;
+use crate::error::{GeneratorError, SourceLocation};
+
+// names of the merged objects like "MEASUREMENT engine.rpm"
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    // the existing object differs from the generated one but is used by objects written by hand, it is kept
+    // the line of the existing object is given with the name
+    pub conflicts: Vec<(String, u32)>,
+    // settings of the existing MOD_COMMON which don't match the target, with the line of MOD_COMMON
+    // the generated objects have no BYTE_ORDER or alignment of their own, so they're read with these
+    pub mod_common_mismatches: Vec<(String, u32)>,
+}
+
+// merge the generated objects into the a2l file at merge_path and write the result to output_path
+pub fn merge_file(
+    merge_path: &str,
+    generated: &A2lFile,
+    output_path: &str,
+    remove_stale: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<MergeReport, GeneratorError> {
+    let mut a2l_file = load(merge_path, diagnostics)?;
+    let report = merge(
+        &mut a2l_file.project.module[0],
+        &generated.project.module[0],
+        remove_stale,
+    );
+    for (message, line) in &report.mod_common_mismatches {
+        diagnostics.push(Diagnostic::new(
+            SourceLocation::new(merge_path, *line as usize, 1),
+            0,
+            message.clone(),
+        ));
+    }
+    for (name, line) in &report.conflicts {
+        diagnostics.push(Diagnostic::new(
+            SourceLocation::new(merge_path, *line as usize, 1),
+            0,
+            format!(
+                "the generated {} was not applied, the existing one differs and is used by objects written by hand",
+                name
+            ),
+        ));
+    }
+    // new objects are placed behind the existing objects of their kind
+    a2l_file.sort_new_items();
+    a2l_file
+        .write(output_path, None)
+        .map_err(|error| GeneratorError::Write {
+            file: output_path.to_string(),
+            message: error.to_string(),
+        })?;
+    Ok(report)
+}
+
+// load an existing a2l file for merging or seeding, the warnings of the a2l parser are reported as diagnostics
+// the generated objects belong to a single module, so the file must have exactly one MODULE
+pub fn load(path: &str, diagnostics: &mut Vec<Diagnostic>) -> Result<A2lFile, GeneratorError> {
+    let mut log_msgs = Vec::new();
+    let a2l_file = a2lfile::load(path, None, &mut log_msgs, false).map_err(|error| GeneratorError::Read {
+        file: path.to_string(),
+        message: error.to_string(),
+    })?;
+    diagnostics.extend(log_msgs.iter().map(|log_msg| load_diagnostic(path, log_msg)));
+    let modules = a2l_file.project.module.len();
+    if modules != 1 {
+        return Err(GeneratorError::Read {
+            file: path.to_string(),
+            message: format!("expected one MODULE, found {}", modules),
+        });
+    }
+    Ok(a2l_file)
+}
+
+// the messages of the a2l parser start with the file and line like "ecu.a2l:12: "
+fn load_diagnostic(path: &str, log_msg: &A2lError) -> Diagnostic {
+    let message = match log_msg {
+        A2lError::ParserError { parser_error } => parser_error.to_string(),
+        _ => log_msg.to_string(),
+    };
+    if let Some((position, text)) = message.split_once(": ")
+        && let Some((file, line)) = position.rsplit_once(':')
+        && let Ok(line) = line.parse()
+    {
+        return Diagnostic::new(SourceLocation::new(file, line, 1), 0, text.to_string());
+    }
+    Diagnostic::new(SourceLocation::new(path, 1, 1), 0, message)
+}
+
+// remove_stale: remove the generated objects of the existing file which weren't generated again
+pub fn merge(module: &mut Module, generated_module: &Module, remove_stale: bool) -> MergeReport {
+    let mut report = MergeReport::default();
+    let generated = generated_module.clone();
+    // the dependencies are collected before the generated objects replace the existing ones
+    let dependencies = object_dependencies(module, true);
+    let hand_written = object_dependencies(module, false);
+    let no_dependencies = HashSet::new();
+    match (&module.mod_common, &generated.mod_common) {
+        (Some(existing), Some(target)) => {
+            report.mod_common_mismatches = mod_common_mismatches(existing, target)
+        }
+        (None, _) => module.mod_common = generated.mod_common,
+        _ => {}
+    }
+    merge_objects(
+        &mut module.measurement,
+        generated.measurement,
+        "MEASUREMENT",
+        &no_dependencies,
+        &mut report,
+        |existing, object| {
+            if object.ecu_address.is_none() {
+                object.ecu_address = existing.ecu_address.clone();
+            }
+            object.if_data = existing.if_data.clone();
+            keep_annotations(&existing.annotation, &mut object.annotation);
+        },
+    );
+    merge_objects(
+        &mut module.characteristic,
+        generated.characteristic,
+        "CHARACTERISTIC",
+        &no_dependencies,
+        &mut report,
+        |existing, object| {
+            if object.address == 0 {
+                object.address = existing.address;
+            }
+            object.if_data = existing.if_data.clone();
+            keep_annotations(&existing.annotation, &mut object.annotation);
+        },
+    );
+    merge_objects(
+        &mut module.axis_pts,
+        generated.axis_pts,
+        "AXIS_PTS",
+        &no_dependencies,
+        &mut report,
+        |existing, object| {
+            if object.address == 0 {
+                object.address = existing.address;
+            }
+            object.if_data = existing.if_data.clone();
+            keep_annotations(&existing.annotation, &mut object.annotation);
+        },
+    );
+    merge_objects(
+        &mut module.instance,
+        generated.instance,
+        "INSTANCE",
+        &no_dependencies,
+        &mut report,
+        |existing, object| {
+            if object.start_address == 0 {
+                object.start_address = existing.start_address;
+            }
+            object.if_data = existing.if_data.clone();
+            keep_annotations(&existing.annotation, &mut object.annotation);
+        },
+    );
+    merge_objects(
+        &mut module.typedef_structure,
+        generated.typedef_structure,
+        "TYPEDEF_STRUCTURE",
+        &hand_written,
+        &mut report,
+        |_, _| {},
+    );
+    merge_objects(
+        &mut module.typedef_measurement,
+        generated.typedef_measurement,
+        "TYPEDEF_MEASUREMENT",
+        &hand_written,
+        &mut report,
+        |_, _| {},
+    );
+    merge_objects(
+        &mut module.typedef_characteristic,
+        generated.typedef_characteristic,
+        "TYPEDEF_CHARACTERISTIC",
+        &hand_written,
+        &mut report,
+        |_, _| {},
+    );
+    merge_objects(
+        &mut module.typedef_axis,
+        generated.typedef_axis,
+        "TYPEDEF_AXIS",
+        &hand_written,
+        &mut report,
+        |_, _| {},
+    );
+    merge_objects(
+        &mut module.compu_method,
+        generated.compu_method,
+        "COMPU_METHOD",
+        &hand_written,
+        &mut report,
+        |_, _| {},
+    );
+    merge_objects(
+        &mut module.compu_vtab,
+        generated.compu_vtab,
+        "COMPU_VTAB",
+        &hand_written,
+        &mut report,
+        |_, _| {},
+    );
+    merge_objects(
+        &mut module.record_layout,
+        generated.record_layout,
+        "RECORD_LAYOUT",
+        &hand_written,
+        &mut report,
+        |_, _| {},
+    );
+    merge_groups(&mut module.group, generated.group, &mut report);
+    if remove_stale {
+        remove_stale_objects(module, generated_module, &dependencies, &mut report);
+    }
+    report
+}
+
+// the byte order and alignments of the existing MOD_COMMON which differ from the ones of the target
+fn mod_common_mismatches(existing: &ModCommon, target: &ModCommon) -> Vec<(String, u32)> {
+    let compare =
+        |keyword, value: fn(&ModCommon) -> Option<String>| (keyword, value(existing), value(target));
+    let settings = [
+        compare("BYTE_ORDER", |mod_common| {
+            mod_common.byte_order.as_ref().map(|item| item.byte_order.to_string())
+        }),
+        compare("ALIGNMENT_BYTE", |mod_common| {
+            mod_common.alignment_byte.as_ref().map(|item| item.alignment_border.to_string())
+        }),
+        compare("ALIGNMENT_WORD", |mod_common| {
+            mod_common.alignment_word.as_ref().map(|item| item.alignment_border.to_string())
+        }),
+        compare("ALIGNMENT_LONG", |mod_common| {
+            mod_common.alignment_long.as_ref().map(|item| item.alignment_border.to_string())
+        }),
+        compare("ALIGNMENT_INT64", |mod_common| {
+            mod_common.alignment_int64.as_ref().map(|item| item.alignment_border.to_string())
+        }),
+        compare("ALIGNMENT_FLOAT32_IEEE", |mod_common| {
+            mod_common.alignment_float32_ieee.as_ref().map(|item| item.alignment_border.to_string())
+        }),
+        compare("ALIGNMENT_FLOAT64_IEEE", |mod_common| {
+            mod_common.alignment_float64_ieee.as_ref().map(|item| item.alignment_border.to_string())
+        }),
+    ];
+    let mut mismatches = Vec::new();
+    for (keyword, existing_value, target_value) in settings {
+        if let (Some(existing_value), Some(target_value)) = (existing_value, target_value)
+            && existing_value != target_value
+        {
+            let message = format!(
+                "{} {} of MOD_COMMON doesn't match the target, which has {}; the generated objects are read with it",
+                keyword, existing_value, target_value
+            );
+            mismatches.push((message, existing.get_layout().line));
+        }
+    }
+    mismatches
+}
+
+// add the generated objects or replace the existing objects of the same name, unless the existing object is
+// one of the hand_written dependencies
+// keep copies what is only maintained in the existing object, like the IF_DATA of the calibration tools
+// or an address which wasn't resolved by the generator
+fn merge_objects<T, L>(
+    existing: &mut Vec<T>,
+    generated: Vec<T>,
+    kind: &str,
+    hand_written: &HashSet<String>,
+    report: &mut MergeReport,
+    keep: fn(&T, &mut T),
+) where
+    T: A2lObject<L> + A2lObjectName + PartialEq,
+{
+    for mut object in generated {
+        let name = format!("{} {}", kind, object.get_name());
+        let Some(existing_object) = existing
+            .iter_mut()
+            .find(|existing| existing.get_name() == object.get_name())
+        else {
+            existing.push(object);
+            report.added.push(name);
+            continue;
+        };
+        keep(existing_object, &mut object);
+        if *existing_object == object {
+            report.unchanged.push(name);
+            continue;
+        }
+        if hand_written.contains(object.get_name()) {
+            report.conflicts.push((name, existing_object.get_layout().line));
+            continue;
+        }
+        // the updated object stays at the position of the existing one
+        let layout = existing_object.get_layout();
+        let (incfile, line, uid) = (layout.incfile.clone(), layout.line, layout.uid);
+        let (start_offset, end_offset) = (layout.start_offset, layout.end_offset);
+        let new_layout = object.get_layout_mut();
+        new_layout.incfile = incfile;
+        new_layout.line = line;
+        new_layout.uid = uid;
+        new_layout.start_offset = start_offset;
+        new_layout.end_offset = end_offset;
+        *existing_object = object;
+        report.updated.push(name);
+    }
+}
+
+// annotations which were added to a generated object by hand are kept
+fn keep_annotations(existing: &[Annotation], annotations: &mut Vec<Annotation>) {
+    for annotation in existing {
+        if !annotations.contains(annotation) {
+            annotations.push(annotation.clone());
+        }
+    }
+}
+
+// groups may also contain objects which weren't generated, the generated references are added to them
+fn merge_groups(existing: &mut Vec<Group>, generated: Vec<Group>, report: &mut MergeReport) {
+    for group in generated {
+        let name = format!("GROUP {}", group.name);
+        let Some(existing_group) = existing.iter_mut().find(|existing| existing.name == group.name) else {
+            existing.push(group);
+            report.added.push(name);
+            continue;
+        };
+        let mut changed = false;
+        if let Some(references) = group.ref_characteristic {
+            let existing_references = existing_group
+                .ref_characteristic
+                .get_or_insert_with(RefCharacteristic::new);
+            changed |= add_references(
+                &mut existing_references.identifier_list,
+                references.identifier_list,
+            );
+        }
+        if let Some(references) = group.ref_measurement {
+            let existing_references = existing_group
+                .ref_measurement
+                .get_or_insert_with(RefMeasurement::new);
+            changed |= add_references(
+                &mut existing_references.identifier_list,
+                references.identifier_list,
+            );
+        }
+        if changed {
+            report.updated.push(name);
+        } else {
+            report.unchanged.push(name);
+        }
+    }
+}
+
+// returns true if a reference was added
+fn add_references(existing: &mut Vec<String>, references: Vec<String>) -> bool {
+    let count = existing.len();
+    for reference in references {
+        if !existing.contains(&reference) {
+            existing.push(reference);
+        }
+    }
+    existing.len() > count
+}
+
+fn is_generated(annotations: &[Annotation]) -> bool {
+    annotations.iter().any(|annotation| {
+        annotation
+            .annotation_label
+            .as_ref()
+            .is_some_and(|label| label.label == OWNER_LABEL)
+    })
+}
+
+// remove the generated objects which weren't generated again, because their variable was deleted or
+// lost its annotation, and the dependencies of generated objects which aren't used anymore
+fn remove_stale_objects(
+    module: &mut Module,
+    generated: &Module,
+    dependencies: &HashSet<String>,
+    report: &mut MergeReport,
+) {
+    let measurements = remove_objects(&mut module.measurement, "MEASUREMENT", report, |object| {
+        is_generated(&object.annotation) && !generated.measurement.iter().any(|new| new.name == object.name)
+    });
+    let characteristics = remove_objects(&mut module.characteristic, "CHARACTERISTIC", report, |object| {
+        is_generated(&object.annotation)
+            && !generated.characteristic.iter().any(|new| new.name == object.name)
+    });
+    let axis_pts = remove_objects(&mut module.axis_pts, "AXIS_PTS", report, |object| {
+        is_generated(&object.annotation) && !generated.axis_pts.iter().any(|new| new.name == object.name)
+    });
+    let instances = remove_objects(&mut module.instance, "INSTANCE", report, |object| {
+        is_generated(&object.annotation) && !generated.instance.iter().any(|new| new.name == object.name)
+    });
+    let removed: HashSet<&str> = (measurements.iter().map(|object| object.name.as_str()))
+        .chain(characteristics.iter().map(|object| object.name.as_str()))
+        .chain(axis_pts.iter().map(|object| object.name.as_str()))
+        .chain(instances.iter().map(|object| object.name.as_str()))
+        .collect();
+    remove_unused_dependencies(module, dependencies, report);
+    remove_stale_references(module, generated, &removed, report);
+}
+
+// remove the objects for which is_stale is true and return them
+fn remove_objects<T: A2lObjectName>(
+    objects: &mut Vec<T>,
+    kind: &str,
+    report: &mut MergeReport,
+    is_stale: impl Fn(&T) -> bool,
+) -> Vec<T> {
+    let (removed, kept): (Vec<T>, Vec<T>) = std::mem::take(objects)
+        .into_iter()
+        .partition(|object| is_stale(object));
+    *objects = kept;
+    for object in &removed {
+        report.removed.push(format!("{} {}", kind, object.get_name()));
+    }
+    removed
+}
+
+// names of the conversions, record layouts and typedefs used by the generated objects or by the objects
+// written by hand, directly or through other dependencies; the conversions of the axes of the generated
+// objects may have been written by hand, they are only followed for the objects written by hand
+fn object_dependencies(module: &Module, generated: bool) -> HashSet<String> {
+    let mut dependencies = Vec::new();
+    for measurement in module
+        .measurement
+        .iter()
+        .filter(|object| is_generated(&object.annotation) == generated)
+    {
+        dependencies.push(measurement.conversion.clone());
+    }
+    for characteristic in module
+        .characteristic
+        .iter()
+        .filter(|object| is_generated(&object.annotation) == generated)
+    {
+        dependencies.extend([characteristic.deposit.clone(), characteristic.conversion.clone()]);
+        if !generated {
+            dependencies.extend(characteristic.axis_descr.iter().map(|axis| axis.conversion.clone()));
+        }
+    }
+    for axis_pts in module
+        .axis_pts
+        .iter()
+        .filter(|object| is_generated(&object.annotation) == generated)
+    {
+        dependencies.extend([axis_pts.deposit_record.clone(), axis_pts.conversion.clone()]);
+    }
+    for instance in module
+        .instance
+        .iter()
+        .filter(|object| is_generated(&object.annotation) == generated)
+    {
+        dependencies.push(instance.type_ref.clone());
+    }
+    let mut index = 0;
+    while let Some(name) = dependencies.get(index).cloned() {
+        index += 1;
+        let mut next = Vec::new();
+        for typedef in module
+            .typedef_structure
+            .iter()
+            .filter(|typedef| typedef.name == name)
+        {
+            next.extend(
+                typedef
+                    .structure_component
+                    .iter()
+                    .map(|component| component.component_type.clone()),
+            );
+        }
+        for typedef in module
+            .typedef_characteristic
+            .iter()
+            .filter(|typedef| typedef.name == name)
+        {
+            next.extend([typedef.record_layout.clone(), typedef.conversion.clone()]);
+        }
+        for typedef in module.typedef_axis.iter().filter(|typedef| typedef.name == name) {
+            next.extend([typedef.record_layout.clone(), typedef.conversion.clone()]);
+        }
+        for typedef in module
+            .typedef_measurement
+            .iter()
+            .filter(|typedef| typedef.name == name)
+        {
+            next.push(typedef.conversion.clone());
+        }
+        for compu_method in module
+            .compu_method
+            .iter()
+            .filter(|compu_method| compu_method.name == name)
+        {
+            next.extend(
+                compu_method
+                    .compu_tab_ref
+                    .iter()
+                    .map(|tab_ref| tab_ref.conversion_table.clone()),
+            );
+        }
+        for name in next {
+            if !dependencies.contains(&name) {
+                dependencies.push(name);
+            }
+        }
+    }
+    dependencies.into_iter().collect()
+}
+
+// remove the dependencies which aren't referenced anymore, until the removals free no more objects
+fn remove_unused_dependencies(module: &mut Module, dependencies: &HashSet<String>, report: &mut MergeReport) {
+    loop {
+        let count = report.removed.len();
+        let references = references(module);
+        let is_unused = |name: &String| dependencies.contains(name) && !references.contains(name);
+        remove_objects(
+            &mut module.typedef_structure,
+            "TYPEDEF_STRUCTURE",
+            report,
+            |object| is_unused(&object.name),
+        );
+        remove_objects(
+            &mut module.typedef_characteristic,
+            "TYPEDEF_CHARACTERISTIC",
+            report,
+            |object| is_unused(&object.name),
+        );
+        remove_objects(&mut module.typedef_axis, "TYPEDEF_AXIS", report, |object| {
+            is_unused(&object.name)
+        });
+        remove_objects(
+            &mut module.typedef_measurement,
+            "TYPEDEF_MEASUREMENT",
+            report,
+            |object| is_unused(&object.name),
+        );
+        remove_objects(&mut module.compu_method, "COMPU_METHOD", report, |object| {
+            is_unused(&object.name)
+        });
+        remove_objects(&mut module.compu_vtab, "COMPU_VTAB", report, |object| {
+            is_unused(&object.name)
+        });
+        remove_objects(&mut module.record_layout, "RECORD_LAYOUT", report, |object| {
+            is_unused(&object.name)
+        });
+        if report.removed.len() == count {
+            break;
+        }
+    }
+}
+
+// names of the conversions, record layouts, typedefs and axis points used by the objects of the module
+fn references(module: &Module) -> HashSet<String> {
+    let mut references = HashSet::new();
+    for measurement in &module.measurement {
+        references.insert(measurement.conversion.as_str());
+    }
+    for characteristic in &module.characteristic {
+        references.extend([
+            characteristic.deposit.as_str(),
+            characteristic.conversion.as_str(),
+        ]);
+        add_axis_references(&characteristic.axis_descr, &mut references);
+    }
+    for axis_pts in &module.axis_pts {
+        references.extend([axis_pts.deposit_record.as_str(), axis_pts.conversion.as_str()]);
+    }
+    for instance in &module.instance {
+        references.insert(instance.type_ref.as_str());
+    }
+    for typedef in &module.typedef_structure {
+        references.extend(
+            typedef
+                .structure_component
+                .iter()
+                .map(|component| component.component_type.as_str()),
+        );
+    }
+    for typedef in &module.typedef_characteristic {
+        references.extend([typedef.record_layout.as_str(), typedef.conversion.as_str()]);
+        add_axis_references(&typedef.axis_descr, &mut references);
+    }
+    for typedef in &module.typedef_axis {
+        references.extend([typedef.record_layout.as_str(), typedef.conversion.as_str()]);
+    }
+    for typedef in &module.typedef_measurement {
+        references.insert(typedef.conversion.as_str());
+    }
+    for compu_method in &module.compu_method {
+        references.extend(
+            compu_method
+                .compu_tab_ref
+                .iter()
+                .map(|tab_ref| tab_ref.conversion_table.as_str()),
+        );
+        references.extend(
+            compu_method
+                .status_string_ref
+                .iter()
+                .map(|tab_ref| tab_ref.conversion_table.as_str()),
+        );
+    }
+    references.into_iter().map(str::to_string).collect()
+}
+
+fn add_axis_references<'a>(axis_descr: &'a [AxisDescr], references: &mut HashSet<&'a str>) {
+    for axis_descr in axis_descr {
+        references.insert(axis_descr.conversion.as_str());
+        if let Some(axis_pts_ref) = &axis_descr.axis_pts_ref {
+            references.insert(axis_pts_ref.axis_points.as_str());
+        }
+    }
+}
+
+// drop the removed objects from all groups and functions, the generated groups only keep the generated
+// objects which are still assigned to them, generated groups without any content are removed
+fn remove_stale_references(
+    module: &mut Module,
+    generated: &Module,
+    removed: &HashSet<&str>,
+    report: &mut MergeReport,
+) {
+    let objects = (module
+        .measurement
+        .iter()
+        .map(|object| (&object.name, &object.annotation)))
+    .chain(
+        module
+            .characteristic
+            .iter()
+            .map(|object| (&object.name, &object.annotation)),
+    )
+    .chain(
+        module
+            .axis_pts
+            .iter()
+            .map(|object| (&object.name, &object.annotation)),
+    )
+    .chain(
+        module
+            .instance
+            .iter()
+            .map(|object| (&object.name, &object.annotation)),
+    );
+    let generated_objects: HashSet<String> = objects
+        .filter(|(_, annotation)| is_generated(annotation))
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut empty_groups = Vec::new();
+    for group in &mut module.group {
+        let is_generated_group = is_generated(&group.annotation);
+        let new_group = generated.group.iter().find(|new| new.name == group.name);
+        let keep = |name: &str| {
+            let is_assigned =
+                new_group.is_some_and(|new| group_references(new).any(|reference| reference == name));
+            !removed.contains(name)
+                && (!is_generated_group || !generated_objects.contains(name) || is_assigned)
+        };
+        let mut changed = false;
+        if let Some(references) = &mut group.ref_characteristic {
+            changed |= retain_references(&mut references.identifier_list, keep);
+        }
+        if let Some(references) = &mut group.ref_measurement {
+            changed |= retain_references(&mut references.identifier_list, keep);
+        }
+        if is_generated_group
+            && group_references(group).next().is_none()
+            && group
+                .sub_group
+                .as_ref()
+                .is_none_or(|sub_group| sub_group.identifier_list.is_empty())
+            && group
+                .function_list
+                .as_ref()
+                .is_none_or(|functions| functions.name_list.is_empty())
+        {
+            empty_groups.push(group.name.clone());
+        } else if changed {
+            report_update(report, format!("GROUP {}", group.name));
+        }
+    }
+    for name in &empty_groups {
+        let name = format!("GROUP {}", name);
+        report.updated.retain(|updated| *updated != name);
+        report.unchanged.retain(|unchanged| *unchanged != name);
+        report.removed.push(name);
+    }
+    module.group.retain(|group| !empty_groups.contains(&group.name));
+    for group in &mut module.group {
+        if let Some(sub_group) = &mut group.sub_group
+            && retain_references(&mut sub_group.identifier_list, |name| {
+                !empty_groups.iter().any(|empty| empty == name)
+            })
+        {
+            report_update(report, format!("GROUP {}", group.name));
+        }
+    }
+    for function in &mut module.function {
+        let references = [
+            function
+                .def_characteristic
+                .as_mut()
+                .map(|references| &mut references.identifier_list),
+            function
+                .ref_characteristic
+                .as_mut()
+                .map(|references| &mut references.identifier_list),
+            function
+                .in_measurement
+                .as_mut()
+                .map(|references| &mut references.identifier_list),
+            function
+                .out_measurement
+                .as_mut()
+                .map(|references| &mut references.identifier_list),
+            function
+                .loc_measurement
+                .as_mut()
+                .map(|references| &mut references.identifier_list),
+        ];
+        let mut changed = false;
+        for references in references.into_iter().flatten() {
+            changed |= retain_references(references, |name| !removed.contains(name));
+        }
+        if changed {
+            report_update(report, format!("FUNCTION {}", function.name));
+        }
+    }
+}
+
+fn group_references(group: &Group) -> impl Iterator<Item = &String> {
+    let characteristics = group
+        .ref_characteristic
+        .iter()
+        .flat_map(|references| &references.identifier_list);
+    let measurements = group
+        .ref_measurement
+        .iter()
+        .flat_map(|references| &references.identifier_list);
+    characteristics.chain(measurements)
+}
+
+// returns true if a reference was dropped
+fn retain_references(references: &mut Vec<String>, keep: impl Fn(&str) -> bool) -> bool {
+    let count = references.len();
+    references.retain(|name| keep(name));
+    references.len() < count
+}
+
+fn report_update(report: &mut MergeReport, name: String) {
+    report.unchanged.retain(|unchanged| *unchanged != name);
+    if !report.updated.contains(&name) {
+        report.updated.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    const EXISTING: &str = r#"
+ASAP2_VERSION 1 71
+/begin PROJECT ecu ""
+  /begin MODULE main ""
+    /begin MOD_PAR ""
+      CPU_TYPE "hand written"
+    /end MOD_PAR
+    /begin MEASUREMENT rpm "old description"
+      UWORD NO_COMPU_METHOD 0 0 0 65535
+      ECU_ADDRESS 0x1000
+      /begin IF_DATA XCP
+        LINK_MAP "rpm" 0x1000 0 0 0 1 0 0
+      /end IF_DATA
+    /end MEASUREMENT
+    /begin MEASUREMENT speed ""
+      UWORD NO_COMPU_METHOD 0 0 0 65535
+      ECU_ADDRESS 0x1010
+    /end MEASUREMENT
+    /begin MEASUREMENT third_party ""
+      UBYTE NO_COMPU_METHOD 0 0 0 255
+    /end MEASUREMENT
+    /begin GROUP engine ""
+      /begin REF_MEASUREMENT
+        third_party
+      /end REF_MEASUREMENT
+    /end GROUP
+  /end MODULE
+/end PROJECT
+"#;
+
+    fn generated() -> A2lFile {
+        let mut generated = a2lfile::new();
+        let module = &mut generated.project.module[0];
+        let mut rpm = Measurement::new(
+            "rpm".to_string(),
+            "engine speed".to_string(),
+            DataType::Uword,
+            "NO_COMPU_METHOD".to_string(),
+            0,
+            0.0,
+            0.0,
+            65535.0,
+        );
+        let mut speed = rpm.clone();
+        speed.name = "speed".to_string();
+        speed.long_identifier = String::new();
+        speed.ecu_address = Some(EcuAddress::new(0x1010));
+        rpm.ecu_address = None;
+        let mut torque = speed.clone();
+        torque.name = "torque".to_string();
+        module.measurement = vec![rpm, speed, torque];
+        let mut group = Group::new("engine".to_string(), String::new());
+        let mut references = RefMeasurement::new();
+        references.identifier_list = vec!["rpm".to_string(), "torque".to_string()];
+        group.ref_measurement = Some(references);
+        module.group.push(group);
+        generated
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut log_msgs = Vec::new();
+        let mut a2l_file = a2lfile::load_from_string(EXISTING, None, &mut log_msgs, false).unwrap();
+        let report = merge(&mut a2l_file.project.module[0], &generated().project.module[0], true);
+        assert_eq!(report.added, vec!["MEASUREMENT torque"]);
+        assert_eq!(report.updated, vec!["MEASUREMENT rpm", "GROUP engine"]);
+        assert_eq!(report.unchanged, vec!["MEASUREMENT speed"]);
+        assert!(report.removed.is_empty());
+
+        let module = &a2l_file.project.module[0];
+        assert!(module.mod_par.as_ref().unwrap().cpu_type.is_some());
+        assert_eq!(module.measurement.len(), 4);
+        // the address and the IF_DATA of rpm are kept, its description is updated
+        let rpm = &module.measurement[0];
+        assert_eq!(rpm.long_identifier, "engine speed");
+        assert_eq!(rpm.ecu_address.as_ref().unwrap().address, 0x1000);
+        assert_eq!(rpm.if_data.len(), 1);
+        assert_eq!(module.measurement[2].name, "third_party");
+        assert_eq!(
+            module.group[0].ref_measurement.as_ref().unwrap().identifier_list,
+            vec!["third_party", "rpm", "torque"]
+        );
+
+        // a second merge changes nothing
+        let report = merge(&mut a2l_file.project.module[0], &generated().project.module[0], true);
+        assert!(report.added.is_empty() && report.updated.is_empty());
+        assert_eq!(report.unchanged.len(), 4);
+    }
+
+    // objects with the OWNER_LABEL were generated before, the others were written by hand
+    const STALE: &str = r#"
+ASAP2_VERSION 1 71
+/begin PROJECT ecu ""
+  /begin MODULE main ""
+    /begin MEASUREMENT rpm ""
+      UWORD CM_rpm 0 0 0 65535
+      /begin ANNOTATION ANNOTATION_LABEL "generated by A2lCommentGenerator" /end ANNOTATION
+      /begin ANNOTATION ANNOTATION_LABEL "reviewed" /end ANNOTATION
+    /end MEASUREMENT
+    /begin MEASUREMENT gear ""
+      UBYTE CM_gear 0 0 0 255
+      /begin ANNOTATION ANNOTATION_LABEL "generated by A2lCommentGenerator" /end ANNOTATION
+    /end MEASUREMENT
+    /begin MEASUREMENT third_party ""
+      UBYTE CM_third_party 0 0 0 255
+    /end MEASUREMENT
+    /begin CHARACTERISTIC old_map ""
+      MAP 0 RL_MAP_UBYTE 0 NO_COMPU_METHOD 0 255
+      /begin AXIS_DESCR STD_AXIS NO_INPUT_QUANTITY CM_axis 4 0 100 /end AXIS_DESCR
+      /begin AXIS_DESCR STD_AXIS NO_INPUT_QUANTITY CM_axis 4 0 100 /end AXIS_DESCR
+      /begin ANNOTATION ANNOTATION_LABEL "generated by A2lCommentGenerator" /end ANNOTATION
+    /end CHARACTERISTIC
+    /begin INSTANCE engine "" engine 0
+      /begin ANNOTATION ANNOTATION_LABEL "generated by A2lCommentGenerator" /end ANNOTATION
+    /end INSTANCE
+    /begin TYPEDEF_STRUCTURE engine "" 2
+      /begin STRUCTURE_COMPONENT temp temp 0 /end STRUCTURE_COMPONENT
+    /end TYPEDEF_STRUCTURE
+    /begin TYPEDEF_MEASUREMENT temp "" UWORD CM_temp 0 0 0 65535 /end TYPEDEF_MEASUREMENT
+    /begin COMPU_METHOD CM_rpm "" LINEAR "%8.3" "" COEFFS_LINEAR 2 0 /end COMPU_METHOD
+    /begin COMPU_METHOD CM_gear "" TAB_VERB "%8.3" "" COMPU_TAB_REF VTAB_gear /end COMPU_METHOD
+    /begin COMPU_METHOD CM_temp "" LINEAR "%8.3" "" COEFFS_LINEAR 1 -40 /end COMPU_METHOD
+    /begin COMPU_METHOD CM_axis "" LINEAR "%8.3" "" COEFFS_LINEAR 1 0 /end COMPU_METHOD
+    /begin COMPU_METHOD CM_third_party "" LINEAR "%8.3" "" COEFFS_LINEAR 1 0 /end COMPU_METHOD
+    /begin COMPU_VTAB VTAB_gear "" TAB_VERB 1 0 "neutral" /end COMPU_VTAB
+    /begin FUNCTION transmission ""
+      /begin OUT_MEASUREMENT gear rpm /end OUT_MEASUREMENT
+    /end FUNCTION
+    /begin GROUP engine ""
+      /begin REF_MEASUREMENT rpm gear third_party /end REF_MEASUREMENT
+      /begin ANNOTATION ANNOTATION_LABEL "generated by A2lCommentGenerator" /end ANNOTATION
+    /end GROUP
+    /begin GROUP maps ""
+      /begin REF_CHARACTERISTIC old_map /end REF_CHARACTERISTIC
+      /begin ANNOTATION ANNOTATION_LABEL "generated by A2lCommentGenerator" /end ANNOTATION
+    /end GROUP
+    /begin GROUP calibration ""
+      /begin SUB_GROUP maps /end SUB_GROUP
+      /begin REF_CHARACTERISTIC old_map /end REF_CHARACTERISTIC
+    /end GROUP
+    /begin RECORD_LAYOUT RL_MAP_UBYTE FNC_VALUES 1 UBYTE ROW_DIR DIRECT /end RECORD_LAYOUT
+  /end MODULE
+/end PROJECT
+"#;
+
+    #[test]
+    fn test_remove_stale_objects() {
+        let mut log_msgs = Vec::new();
+        let mut a2l_file = a2lfile::load_from_string(STALE, None, &mut log_msgs, false).unwrap();
+        // rpm is generated again without a conversion and moved into another group
+        let mut generated = a2lfile::new();
+        let module = &mut generated.project.module[0];
+        let mut rpm = a2l_file.project.module[0].measurement[0].clone();
+        rpm.conversion = "NO_COMPU_METHOD".to_string();
+        rpm.annotation.truncate(1);
+        module.measurement.push(rpm);
+        let mut group = Group::new("speeds".to_string(), String::new());
+        let mut references = RefMeasurement::new();
+        references.identifier_list = vec!["rpm".to_string()];
+        group.ref_measurement = Some(references);
+        group.annotation = module.measurement[0].annotation.clone();
+        module.group.push(group);
+
+        // nothing is removed if the generated objects may be incomplete
+        let mut unchanged_file = a2l_file.clone();
+        let report = merge(&mut unchanged_file.project.module[0], &generated.project.module[0], false);
+        assert!(report.removed.is_empty());
+        assert_eq!(unchanged_file.project.module[0].measurement.len(), 3);
+
+        let report = merge(&mut a2l_file.project.module[0], &generated.project.module[0], true);
+        assert_eq!(
+            report.removed,
+            vec![
+                "MEASUREMENT gear",
+                "CHARACTERISTIC old_map",
+                "INSTANCE engine",
+                "TYPEDEF_STRUCTURE engine",
+                "COMPU_METHOD CM_rpm",
+                "COMPU_METHOD CM_gear",
+                "RECORD_LAYOUT RL_MAP_UBYTE",
+                "TYPEDEF_MEASUREMENT temp",
+                "COMPU_VTAB VTAB_gear",
+                "COMPU_METHOD CM_temp",
+                "GROUP maps",
+            ]
+        );
+        assert_eq!(report.added, vec!["GROUP speeds"]);
+        assert_eq!(
+            report.updated,
+            vec!["MEASUREMENT rpm", "GROUP engine", "GROUP calibration", "FUNCTION transmission"]
+        );
+
+        let module = &a2l_file.project.module[0];
+        // the objects written by hand and the conversion of the axes are kept
+        let measurements: Vec<&str> = module.measurement.iter().map(|object| object.name.as_str()).collect();
+        assert_eq!(measurements, vec!["rpm", "third_party"]);
+        let compu_methods: Vec<&str> = module.compu_method.iter().map(|object| object.name.as_str()).collect();
+        assert_eq!(compu_methods, vec!["CM_axis", "CM_third_party"]);
+        // the annotation added by hand stays with the generated object
+        assert_eq!(module.measurement[0].annotation.len(), 2);
+        let group_names: Vec<&str> = module.group.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(group_names, vec!["engine", "calibration", "speeds"]);
+        assert_eq!(module.group[0].ref_measurement.as_ref().unwrap().identifier_list, vec!["third_party"]);
+        assert!(module.group[1].sub_group.as_ref().unwrap().identifier_list.is_empty());
+        assert!(module.group[1].ref_characteristic.as_ref().unwrap().identifier_list.is_empty());
+        let out_measurement = module.function[0].out_measurement.as_ref().unwrap();
+        assert_eq!(out_measurement.identifier_list, vec!["rpm"]);
+
+        // the next merge finds nothing to remove
+        let report = merge(&mut a2l_file.project.module[0], &generated.project.module[0], true);
+        assert!(report.removed.is_empty() && report.updated.is_empty());
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let mut log_msgs = Vec::new();
+        let mut a2l_file = a2lfile::load_from_string(STALE, None, &mut log_msgs, false).unwrap();
+        let mut generated = a2lfile::new();
+        let module = &mut generated.project.module[0];
+        for name in ["CM_rpm", "CM_third_party"] {
+            let existing = &a2l_file.project.module[0].compu_method;
+            let mut compu_method = existing.iter().find(|object| object.name == name).unwrap().clone();
+            compu_method.format = "%6.1".to_string();
+            module.compu_method.push(compu_method);
+        }
+        let report = merge(&mut a2l_file.project.module[0], &generated.project.module[0], false);
+        // only the generated measurement uses CM_rpm, the conversion of the hand written one is kept
+        assert_eq!(report.updated, vec!["COMPU_METHOD CM_rpm"]);
+        let line = STALE.lines().position(|line| line.contains("COMPU_METHOD CM_third_party")).unwrap();
+        assert_eq!(report.conflicts, vec![("COMPU_METHOD CM_third_party".to_string(), line as u32 + 1)]);
+        let compu_methods = &a2l_file.project.module[0].compu_method;
+        let kept = compu_methods.iter().find(|object| object.name == "CM_third_party").unwrap();
+        assert_eq!(kept.format, "%8.3");
+
+        // the conflict is reported at the existing object
+        let dir = TestDir::new("a2l_merge_test_merge_conflicts");
+        let path = dir.write("ecu.a2l", STALE);
+        let mut diagnostics = Vec::new();
+        merge_file(&path, &generated, &path, false, &mut diagnostics).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, SourceLocation::new(&path, line + 1, 1));
+        assert!(diagnostics[0].message.starts_with("the generated COMPU_METHOD CM_third_party was not applied"));
+    }
+
+    #[test]
+    fn test_mod_common_mismatches() {
+        let existing = "ASAP2_VERSION 1 71\n/begin PROJECT p \"\"\n/begin MODULE m \"\"\n\
+            /begin MOD_COMMON \"\"\nBYTE_ORDER MSB_FIRST\nALIGNMENT_WORD 2\n/end MOD_COMMON\n\
+            /end MODULE\n/end PROJECT\n";
+        let mut log_msgs = Vec::new();
+        let mut a2l_file = a2lfile::load_from_string(existing, None, &mut log_msgs, false).unwrap();
+        let mut generated = a2lfile::new();
+        let mut mod_common = ModCommon::new(String::new());
+        mod_common.byte_order = Some(ByteOrder::new(ByteOrderEnum::MsbLast));
+        mod_common.alignment_word = Some(AlignmentWord::new(2));
+        mod_common.alignment_long = Some(AlignmentLong::new(4));
+        generated.project.module[0].mod_common = Some(mod_common);
+        let report = merge(&mut a2l_file.project.module[0], &generated.project.module[0], true);
+        // the existing settings are kept, only the byte order differs
+        assert_eq!(
+            report.mod_common_mismatches,
+            vec![(
+                "BYTE_ORDER MSB_FIRST of MOD_COMMON doesn't match the target, which has MSB_LAST; \
+                 the generated objects are read with it"
+                    .to_string(),
+                4
+            )]
+        );
+        let mod_common = a2l_file.project.module[0].mod_common.as_ref().unwrap();
+        assert_eq!(mod_common.byte_order.as_ref().unwrap().byte_order, ByteOrderEnum::MsbFirst);
+    }
+
+    #[test]
+    fn test_merge_file() {
+        let dir = TestDir::new("a2l_merge_test_merge_file");
+        let path = dir.write("ecu.a2l", EXISTING);
+        let mut diagnostics = Vec::new();
+        let report = merge_file(&path, &generated(), &path, true, &mut diagnostics).unwrap();
+        assert!(diagnostics.is_empty());
+        assert_eq!(report.added.len(), 1);
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("CPU_TYPE \"hand written\""));
+        assert!(text.contains("/begin MEASUREMENT torque"));
+        // the existing objects keep their order, the new one follows them
+        let position = |name: &str| text.find(&format!("/begin MEASUREMENT {}", name)).unwrap();
+        assert!(position("rpm") < position("speed") && position("third_party") < position("torque"));
+        assert!(
+            merge_file(
+                &dir.path.join("missing.a2l").to_string_lossy(),
+                &generated(),
+                &path,
+                true,
+                &mut diagnostics
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = TestDir::new("a2l_merge_test_load");
+        let mut diagnostics = Vec::new();
+        // the generated objects can't be assigned to one of several modules
+        let modules = "ASAP2_VERSION 1 71\n/begin PROJECT p \"\"\n/begin MODULE a \"\"\n/end MODULE\n\
+            /begin MODULE b \"\"\n/end MODULE\n/end PROJECT\n";
+        let error = load(&dir.write("modules.a2l", modules), &mut diagnostics).unwrap_err();
+        assert!(error.to_string().ends_with("expected one MODULE, found 2"));
+        assert!(diagnostics.is_empty());
+        // a project without a module is parsed with a warning
+        let empty = "ASAP2_VERSION 1 71\n/begin PROJECT p \"\"\n/end PROJECT\n";
+        let error = load(&dir.write("empty.a2l", empty), &mut diagnostics).unwrap_err();
+        assert!(error.to_string().ends_with("expected one MODULE, found 0"));
+        assert_eq!(diagnostics[0].location.line, 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "element MODULE is missing in block PROJECT starting on line 2"
+        );
+    }
+}
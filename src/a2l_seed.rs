@@ -0,0 +1,957 @@
+// seeding annotation comments from an existing a2l file, the reverse of the generator
+// MEASUREMENTs, CHARACTERISTICs and AXIS_PTS are matched with the C declarations by name and an
+// annotation comment derived from the object is inserted in front of the declaration
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use a2lfile::*;
+
+use crate::a2l_merge;
+use crate::c_types::StructType;
+use crate::code_parser::AnnotatedDeclaration;
+use crate::diagnostics::Diagnostic;
+use crate::error::{GeneratorError, SourceLocation};
+
+// names of the objects like "MEASUREMENT engine.rpm"
+#[derive(Debug, Default)]
+pub struct SeedReport {
+    pub seeded: Vec<String>,
+    // the declaration already has an annotation comment, it is left as it is
+    pub annotated: Vec<String>,
+    // no declaration was found in the C sources
+    pub missing: Vec<String>,
+    // the directory to apply the patch in, if some of the sources are outside the working directory
+    pub patch_root: Option<String>,
+}
+
+// the source files with the comment blocks to insert
+pub struct Seed {
+    pub report: SeedReport,
+    files: Vec<SeededFile>,
+}
+
+struct SeededFile {
+    path: String,
+    code: String,
+    // line before which the comment is inserted, starting at 1, and the lines of the comment
+    insertions: Vec<(usize, Vec<String>)>,
+}
+
+// a declaration or struct member which can take the annotation of an a2l object
+struct Target<'a> {
+    location: &'a SourceLocation,
+    length: usize,
+    is_annotated: bool,
+    // the declaration of the struct variable of a member, it needs an "a2l on" comment too
+    instance: Option<&'a AnnotatedDeclaration>,
+}
+
+// read the a2l file, derive the annotations of its objects and write them as a patch to output_path,
+// "-" is stdout, or insert them into the source files if in_place is set
+pub fn seed_file(
+    a2l_path: &str,
+    declarations: &[AnnotatedDeclaration],
+    output_path: &str,
+    in_place: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<SeedReport, GeneratorError> {
+    let a2l_file = a2l_merge::load(a2l_path, diagnostics)?;
+    let mut seed = seed(&a2l_file.project.module[0], declarations, diagnostics)?;
+    if in_place {
+        seed.write_sources()?;
+        return Ok(seed.report);
+    }
+    let patch_root = seed.patch_root();
+    if patch_root != std::env::current_dir().unwrap_or_default() {
+        seed.report.patch_root = Some(patch_root.to_string_lossy().to_string());
+    }
+    if output_path == "-" {
+        print!("{}", seed.patch());
+    } else {
+        std::fs::write(output_path, seed.patch()).map_err(|error| GeneratorError::Write {
+            file: output_path.to_string(),
+            message: error.to_string(),
+        })?;
+    }
+    Ok(seed.report)
+}
+
+pub fn seed(
+    module: &Module,
+    declarations: &[AnnotatedDeclaration],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Seed, GeneratorError> {
+    let targets = targets(declarations);
+    let mut seed = Seed {
+        report: SeedReport::default(),
+        files: Vec::new(),
+    };
+    // the object whose annotation is inserted at a location, declarations of several variables and
+    // the members of a struct with several instances can only take one annotation
+    let mut seeded_locations: HashMap<&SourceLocation, String> = HashMap::new();
+    let mut seeded_instances = HashSet::new();
+    let measurements = (module.measurement.iter()).map(|object| {
+        (
+            "MEASUREMENT",
+            object.name.as_str(),
+            measurement_comment(module, object),
+        )
+    });
+    let characteristics = (module.characteristic.iter()).map(|object| {
+        (
+            "CHARACTERISTIC",
+            object.name.as_str(),
+            characteristic_comment(module, object),
+        )
+    });
+    let axis_pts = (module.axis_pts.iter())
+        .map(|object| ("AXIS_PTS", object.name.as_str(), axis_pts_comment(module, object)));
+    for (kind, name, comment) in measurements.chain(characteristics).chain(axis_pts) {
+        let object_name = format!("{} {}", kind, name);
+        let Some(target) = targets.get(name) else {
+            seed.report.missing.push(object_name);
+            continue;
+        };
+        if target.is_annotated {
+            seed.report.annotated.push(object_name);
+            continue;
+        }
+        if let Some(seeded) = seeded_locations.get(target.location) {
+            diagnostics.push(Diagnostic::new(
+                target.location.clone(),
+                target.length,
+                format!(
+                    "'{}' shares its declaration with '{}', only the annotation of '{}' is seeded",
+                    name, seeded, seeded
+                ),
+            ));
+            continue;
+        }
+        let comment = match comment {
+            Ok(comment) => comment,
+            Err(message) => {
+                diagnostics.push(Diagnostic::new(
+                    target.location.clone(),
+                    target.length,
+                    format!("'{}' can't be annotated: {}", name, message),
+                ));
+                continue;
+            }
+        };
+        if !seed.insert(target.location, target.length, comment, diagnostics)? {
+            continue;
+        }
+        if let Some(instance) = target.instance
+            && seeded_instances.insert(&instance.name)
+        {
+            let length = instance.declaration.trim_end_matches(';').len();
+            seed.insert(
+                &instance.location,
+                length,
+                vec!["a2l on".to_string()],
+                diagnostics,
+            )?;
+        }
+        seeded_locations.insert(target.location, name.to_string());
+        seed.report.seeded.push(object_name);
+    }
+    Ok(seed)
+}
+
+// the declarations and struct members by the names of their a2l objects, e.g. "engine.rpm"
+// extern declarations are skipped, the annotation belongs to the definition of the variable
+fn targets(declarations: &[AnnotatedDeclaration]) -> HashMap<String, Target<'_>> {
+    let mut targets = HashMap::new();
+    for declaration in declarations {
+        if declaration.declaration.trim_start().starts_with("extern") {
+            continue;
+        }
+        let is_annotated = !declaration.comment.is_empty();
+        targets.entry(declaration.name.clone()).or_insert(Target {
+            location: &declaration.location,
+            length: declaration.declaration.trim_end_matches(';').len(),
+            is_annotated,
+            instance: None,
+        });
+        if let Some(struct_type) = &declaration.struct_type {
+            let instance = if is_annotated { None } else { Some(declaration) };
+            add_member_targets(&declaration.name, struct_type, instance, &mut targets);
+        }
+    }
+    targets
+}
+
+fn add_member_targets<'a>(
+    prefix: &str,
+    struct_type: &'a StructType,
+    instance: Option<&'a AnnotatedDeclaration>,
+    targets: &mut HashMap<String, Target<'a>>,
+) {
+    for member in &struct_type.members {
+        let name = format!("{}.{}", prefix, member.name);
+        if let Some(nested) = &member.struct_type {
+            add_member_targets(&name, nested, instance, targets);
+        } else {
+            targets.entry(name).or_insert(Target {
+                location: &member.location,
+                length: member.declaration.trim_end_matches(';').len(),
+                is_annotated: member.comment.is_some(),
+                instance,
+            });
+        }
+    }
+}
+
+impl Seed {
+    // add a comment in front of the line of the location, the comment gets the indentation of the line
+    // returns false if the location doesn't start its line, e.g. a member of a struct defined in one line
+    fn insert(
+        &mut self,
+        location: &SourceLocation,
+        length: usize,
+        comment: Vec<String>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<bool, GeneratorError> {
+        let index = match self.files.iter().position(|file| file.path == location.file) {
+            Some(index) => index,
+            None => {
+                let code = std::fs::read_to_string(&location.file).map_err(|error| GeneratorError::Read {
+                    file: location.file.clone(),
+                    message: error.to_string(),
+                })?;
+                self.files.push(SeededFile {
+                    path: location.file.clone(),
+                    code,
+                    insertions: Vec::new(),
+                });
+                self.files.len() - 1
+            }
+        };
+        let file = &mut self.files[index];
+        let line = file.code.lines().nth(location.line - 1).unwrap_or_default();
+        let indent: String = line.chars().take(location.column - 1).collect();
+        if !indent.chars().all(char::is_whitespace) {
+            diagnostics.push(Diagnostic::new(
+                location.clone(),
+                length,
+                "the annotation can't be seeded, the declaration doesn't start its line".to_string(),
+            ));
+            return Ok(false);
+        }
+        let lines = comment
+            .into_iter()
+            .map(|comment_line| format!("{}// {}", indent, comment_line))
+            .collect();
+        file.insertions.push((location.line, lines));
+        Ok(true)
+    }
+
+    // a unified diff of all seeded files, which can be applied with "git apply" or "patch -p1" in patch_root
+    pub fn patch(&self) -> String {
+        let root = self.patch_root();
+        let mut patch = String::new();
+        for file in self.patched_files() {
+            let path = patch_path(&file.path, &root);
+            patch.push_str(&format!("--- a/{}\n+++ b/{}\n", path, path));
+            patch.push_str(&file.hunks());
+        }
+        patch
+    }
+
+    // the working directory if it contains all patched files, otherwise their common directory
+    pub fn patch_root(&self) -> PathBuf {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let paths: Vec<PathBuf> = self.patched_files().map(|file| absolute_path(&file.path)).collect();
+        if paths.iter().all(|path| path.starts_with(&current_dir)) {
+            return current_dir;
+        }
+        let mut root = paths[0].parent().map(Path::to_path_buf).unwrap_or_default();
+        while !paths.iter().all(|path| path.starts_with(&root)) && root.pop() {}
+        root
+    }
+
+    fn patched_files(&self) -> impl Iterator<Item = &SeededFile> {
+        self.files.iter().filter(|file| !file.insertions.is_empty())
+    }
+
+    // insert the comments into the source files
+    pub fn write_sources(&self) -> Result<(), GeneratorError> {
+        for file in self.patched_files() {
+            let newline = file.newline();
+            let mut code = String::new();
+            let insertions = file.sorted_insertions();
+            let mut insertions = insertions.iter().peekable();
+            for (index, line) in file.code.split_inclusive('\n').enumerate() {
+                while let Some((_, comment)) =
+                    insertions.next_if(|(line_number, _)| *line_number == index + 1)
+                {
+                    for comment_line in comment {
+                        code.push_str(comment_line);
+                        code.push_str(newline);
+                    }
+                }
+                code.push_str(line);
+            }
+            std::fs::write(&file.path, code).map_err(|error| GeneratorError::Write {
+                file: file.path.clone(),
+                message: error.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+// the path of a source file in the patch, relative to the root of the patch
+fn patch_path(path: &str, root: &Path) -> String {
+    let path = absolute_path(path);
+    let relative = path.strip_prefix(root).unwrap_or(&path);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+// the path relative to the working directory made absolute, "." and ".." are resolved
+fn absolute_path(path: &str) -> PathBuf {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let mut absolute = PathBuf::new();
+    for component in current_dir.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            _ => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+// lines of context around the inserted comments in the patch
+const CONTEXT_LINES: usize = 3;
+
+impl SeededFile {
+    fn sorted_insertions(&self) -> Vec<&(usize, Vec<String>)> {
+        let mut insertions: Vec<_> = self.insertions.iter().collect();
+        insertions.sort_by_key(|(line, _)| *line);
+        insertions
+    }
+
+    // the line ending of the file, the inserted lines use the same
+    fn newline(&self) -> &'static str {
+        if self.code.contains("\r\n") { "\r\n" } else { "\n" }
+    }
+
+    // the hunks of the unified diff, insertions whose context overlaps share a hunk
+    fn hunks(&self) -> String {
+        let lines: Vec<&str> = self.code.split_inclusive('\n').collect();
+        let newline = self.newline();
+        let mut hunks = String::new();
+        // number of lines inserted by the previous hunks
+        let mut offset = 0;
+        let insertions = self.sorted_insertions();
+        let mut index = 0;
+        while index < insertions.len() {
+            // the insertions of this hunk and the range of original lines it shows, starting at 1
+            let first = insertions[index].0.saturating_sub(CONTEXT_LINES).max(1);
+            let mut end = index + 1;
+            while end < insertions.len()
+                && insertions[end].0.saturating_sub(CONTEXT_LINES) <= insertions[end - 1].0 + CONTEXT_LINES
+            {
+                end += 1;
+            }
+            let last = (insertions[end - 1].0 + CONTEXT_LINES - 1).min(lines.len());
+            let inserted: usize = insertions[index..end]
+                .iter()
+                .map(|(_, comment)| comment.len())
+                .sum();
+            let old_count = last + 1 - first;
+            hunks.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                first,
+                old_count,
+                first + offset,
+                old_count + inserted
+            ));
+            let mut hunk_insertions = insertions[index..end].iter().peekable();
+            for line_number in first..=last {
+                while let Some((_, comment)) = hunk_insertions.next_if(|(line, _)| *line == line_number) {
+                    for comment_line in comment {
+                        hunks.push_str(&format!("+{}{}", comment_line, newline));
+                    }
+                }
+                let line = lines[line_number - 1];
+                hunks.push(' ');
+                hunks.push_str(line);
+                if !line.ends_with('\n') {
+                    hunks.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+            offset += inserted;
+            index = end;
+        }
+        hunks
+    }
+}
+
+// the annotation of an object, or the reason why it can't be annotated
+fn measurement_comment(module: &Module, measurement: &Measurement) -> Result<Vec<String>, String> {
+    let mut comment = vec!["a2l on".to_string(), "a2l-type measurement".to_string()];
+    add_scaling(
+        &mut comment,
+        module,
+        &measurement.long_identifier,
+        measurement.phys_unit.as_ref(),
+        &measurement.conversion,
+        measurement.lower_limit,
+        measurement.upper_limit,
+    )?;
+    add_common(
+        &mut comment,
+        module,
+        &measurement.name,
+        measurement.display_identifier.as_ref(),
+        false,
+    );
+    add_max_refresh(&mut comment, measurement.max_refresh.as_ref());
+    if measurement.read_write.is_some() {
+        comment.push("a2l-read-write".to_string());
+    }
+    add_byte_order(&mut comment, measurement.byte_order.as_ref());
+    Ok(comment)
+}
+
+fn characteristic_comment(module: &Module, characteristic: &Characteristic) -> Result<Vec<String>, String> {
+    let mut comment = vec!["a2l on".to_string(), "a2l-type characteristic".to_string()];
+    if characteristic.characteristic_type != CharacteristicType::Value {
+        let characteristic_type = characteristic.characteristic_type.to_string().to_lowercase();
+        comment.push(format!("a2l-characteristic-type {}", characteristic_type));
+    }
+    add_scaling(
+        &mut comment,
+        module,
+        &characteristic.long_identifier,
+        characteristic.phys_unit.as_ref(),
+        &characteristic.conversion,
+        characteristic.lower_limit,
+        characteristic.upper_limit,
+    )?;
+    for axis_descr in &characteristic.axis_descr {
+        match (axis_descr.attribute, &axis_descr.axis_pts_ref) {
+            (AxisDescrAttribute::ComAxis, Some(axis_pts_ref)) => {
+                comment.push(format!("a2l-axis-ref {}", argument(&axis_pts_ref.axis_points)))
+            }
+            (AxisDescrAttribute::StdAxis, _) => comment.push(format!(
+                "a2l-axis {} {} {} {} {}",
+                argument(&axis_descr.input_quantity),
+                argument(&axis_descr.conversion),
+                axis_descr.max_axis_points,
+                number(axis_descr.lower_limit),
+                number(axis_descr.upper_limit)
+            )),
+            (attribute, _) => return Err(format!("{} axes are not supported", attribute)),
+        }
+    }
+    add_common(
+        &mut comment,
+        module,
+        &characteristic.name,
+        characteristic.display_identifier.as_ref(),
+        true,
+    );
+    add_max_refresh(&mut comment, characteristic.max_refresh.as_ref());
+    if characteristic.read_only.is_some() {
+        comment.push("a2l-read-only".to_string());
+    }
+    add_byte_order(&mut comment, characteristic.byte_order.as_ref());
+    Ok(comment)
+}
+
+fn axis_pts_comment(module: &Module, axis_pts: &AxisPts) -> Result<Vec<String>, String> {
+    let mut comment = vec!["a2l on".to_string(), "a2l-type axis_pts".to_string()];
+    if axis_pts.input_quantity != "NO_INPUT_QUANTITY" {
+        comment.push(format!(
+            "a2l-input-quantity {}",
+            argument(&axis_pts.input_quantity)
+        ));
+    }
+    add_scaling(
+        &mut comment,
+        module,
+        &axis_pts.long_identifier,
+        axis_pts.phys_unit.as_ref(),
+        &axis_pts.conversion,
+        axis_pts.lower_limit,
+        axis_pts.upper_limit,
+    )?;
+    add_common(
+        &mut comment,
+        module,
+        &axis_pts.name,
+        axis_pts.display_identifier.as_ref(),
+        true,
+    );
+    add_max_refresh(&mut comment, axis_pts.max_refresh.as_ref());
+    if axis_pts.read_only.is_some() {
+        comment.push("a2l-read-only".to_string());
+    }
+    add_byte_order(&mut comment, axis_pts.byte_order.as_ref());
+    Ok(comment)
+}
+
+// description, unit, limits and conversion, the unit of the COMPU_METHOD is used if the object has none
+fn add_scaling(
+    comment: &mut Vec<String>,
+    module: &Module,
+    long_identifier: &str,
+    phys_unit: Option<&PhysUnit>,
+    conversion: &str,
+    lower_limit: f64,
+    upper_limit: f64,
+) -> Result<(), String> {
+    let compu_method = module
+        .compu_method
+        .iter()
+        .find(|compu_method| compu_method.name == conversion);
+    if !long_identifier.is_empty() {
+        comment.push(format!("a2l-description {}", argument(long_identifier)));
+    }
+    let unit = match (phys_unit, compu_method) {
+        (Some(phys_unit), _) => phys_unit.unit.as_str(),
+        (None, Some(compu_method)) => compu_method.unit.as_str(),
+        (None, None) => "",
+    };
+    if !unit.is_empty() {
+        comment.push(format!("a2l-unit {}", argument(unit)));
+    }
+    comment.push(format!("a2l-min {}", number(lower_limit)));
+    comment.push(format!("a2l-max {}", number(upper_limit)));
+    let Some(compu_method) = compu_method else {
+        return Ok(());
+    };
+    let compu_vtab = compu_method.compu_tab_ref.as_ref().and_then(|compu_tab_ref| {
+        (module.compu_vtab.iter()).find(|compu_vtab| compu_vtab.name == compu_tab_ref.conversion_table)
+    });
+    match (
+        compu_method.conversion_type,
+        &compu_method.coeffs_linear,
+        &compu_method.coeffs,
+        compu_vtab,
+    ) {
+        (ConversionType::Identical, ..) => {}
+        (ConversionType::Linear, Some(coeffs), ..) => {
+            comment.push(format!(
+                "a2l-linear-coeffs {} {}",
+                number(coeffs.a),
+                number(coeffs.b)
+            ));
+        }
+        (ConversionType::RatFunc, _, Some(coeffs), _) => comment.push(format!(
+            "a2l-rat-func-coeffs {} {} {} {} {} {}",
+            number(coeffs.a),
+            number(coeffs.b),
+            number(coeffs.c),
+            number(coeffs.d),
+            number(coeffs.e),
+            number(coeffs.f)
+        )),
+        (ConversionType::TabVerb, .., Some(compu_vtab)) => {
+            let pairs: Vec<String> = (compu_vtab.value_pairs.iter())
+                .map(|pair| format!("{} {}", number(pair.in_val), argument(&pair.out_val)))
+                .collect();
+            comment.push(format!("a2l-vtab {}", pairs.join(" ")));
+        }
+        (conversion_type, ..) => {
+            return Err(format!(
+                "the {} conversion {} is not supported",
+                conversion_type, compu_method.name
+            ));
+        }
+    }
+    if compu_method.format != "%8.3" {
+        comment.push(format!("a2l-format {}", compu_method.format));
+    }
+    Ok(())
+}
+
+// display identifier and the first group of the object
+fn add_common(
+    comment: &mut Vec<String>,
+    module: &Module,
+    name: &str,
+    display_identifier: Option<&DisplayIdentifier>,
+    is_characteristic: bool,
+) {
+    if let Some(display_identifier) = display_identifier {
+        comment.push(format!(
+            "a2l-display-identifier {}",
+            display_identifier.display_name
+        ));
+    }
+    let group = module.group.iter().find(|group| {
+        let references = if is_characteristic {
+            group
+                .ref_characteristic
+                .as_ref()
+                .map(|references| &references.identifier_list)
+        } else {
+            group
+                .ref_measurement
+                .as_ref()
+                .map(|references| &references.identifier_list)
+        };
+        references.is_some_and(|references| references.iter().any(|reference| reference == name))
+    });
+    if let Some(group) = group {
+        comment.push(format!("a2l-group {}", group.name));
+    }
+}
+
+// only the scaling units of microseconds, milliseconds and seconds can be annotated
+fn add_max_refresh(comment: &mut Vec<String>, max_refresh: Option<&MaxRefresh>) {
+    let Some(max_refresh) = max_refresh else {
+        return;
+    };
+    let unit = match max_refresh.scaling_unit {
+        0 => "us",
+        3 => "ms",
+        6 => "s",
+        _ => return,
+    };
+    comment.push(format!("a2l-max-refresh {}{}", max_refresh.rate, unit));
+}
+
+fn add_byte_order(comment: &mut Vec<String>, byte_order: Option<&ByteOrder>) {
+    match byte_order.map(|byte_order| byte_order.byte_order) {
+        Some(ByteOrderEnum::MsbFirst) => comment.push("a2l-byte-order msb_first".to_string()),
+        Some(ByteOrderEnum::MsbLast) => comment.push("a2l-byte-order msb_last".to_string()),
+        _ => {}
+    }
+}
+
+// large limits like the ones of a float are written in scientific notation
+fn number(value: f64) -> String {
+    if value.abs() < 1e15 {
+        value.to_string()
+    } else {
+        format!("{:e}", value)
+    }
+}
+
+// an argument of an annotation, quoted if it isn't a single word
+fn argument(text: &str) -> String {
+    let text = text.replace(['\r', '\n'], " ");
+    if !text.is_empty() && !text.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return text;
+    }
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::a2l_comment_generator::A2lCommentGenerator;
+    use crate::code_parser::CodeParser;
+    use crate::test_support::TestDir;
+
+    const LEGACY: &str = r#"
+ASAP2_VERSION 1 71
+/begin PROJECT ecu ""
+  /begin MODULE main ""
+    /begin MEASUREMENT rpm "engine speed"
+      UWORD rpm_conv 0 0 0 8000
+      DISPLAY_IDENTIFIER EngineSpeed
+      BYTE_ORDER MSB_FIRST
+      ECU_ADDRESS 0x1000
+    /end MEASUREMENT
+    /begin MEASUREMENT engine.state ""
+      UBYTE state_conv 0 0 0 2
+    /end MEASUREMENT
+    /begin MEASUREMENT annotated ""
+      UBYTE NO_COMPU_METHOD 0 0 0 255
+    /end MEASUREMENT
+    /begin MEASUREMENT missing ""
+      UBYTE NO_COMPU_METHOD 0 0 0 255
+    /end MEASUREMENT
+    /begin MEASUREMENT formula "computed"
+      UBYTE formula_conv 0 0 0 255
+    /end MEASUREMENT
+    /begin CHARACTERISTIC gain "gain of the \"controller\""
+      VALUE 0x2000 value_layout 0 NO_COMPU_METHOD -1.5 2.5e20
+      PHYS_UNIT "%"
+      MAX_REFRESH 3 50
+      READ_ONLY
+    /end CHARACTERISTIC
+    /begin CHARACTERISTIC curve ""
+      CURVE 0x2010 curve_layout 0 NO_COMPU_METHOD 0 100
+      /begin AXIS_DESCR COM_AXIS NO_INPUT_QUANTITY NO_COMPU_METHOD 8 0 100
+        AXIS_PTS_REF curve_axis
+      /end AXIS_DESCR
+    /end CHARACTERISTIC
+    /begin AXIS_PTS curve_axis ""
+      0x2030 rpm axis_layout 0 NO_COMPU_METHOD 8 0 8000
+    /end AXIS_PTS
+    /begin COMPU_METHOD rpm_conv ""
+      LINEAR "%6.1" "1/min"
+      COEFFS_LINEAR 0.25 0
+    /end COMPU_METHOD
+    /begin COMPU_METHOD state_conv ""
+      TAB_VERB "%8.3" ""
+      COMPU_TAB_REF state_table
+    /end COMPU_METHOD
+    /begin COMPU_METHOD formula_conv ""
+      FORM "%8.3" ""
+      /begin FORMULA "X1*2" /end FORMULA
+    /end COMPU_METHOD
+    /begin COMPU_VTAB state_table "" TAB_VERB 3
+      0 "off" 1 "idle" 2 "full load"
+    /end COMPU_VTAB
+    /begin GROUP engine ""
+      /begin REF_MEASUREMENT rpm /end REF_MEASUREMENT
+    /end GROUP
+  /end MODULE
+/end PROJECT
+"#;
+
+    const CODE: &str = "\
+#include <stdint.h>
+
+uint16_t rpm;
+extern uint8_t formula;
+uint8_t formula;
+
+struct engine_data {
+    uint16_t speed;
+    uint8_t state;
+};
+struct engine_data engine;
+
+// a2l on
+uint8_t annotated;
+
+const float gain = 1.0f;
+const int16_t curve[8];
+const int16_t curve_axis[8];
+";
+
+    fn seed_code(name: &str) -> (TestDir, String, Seed, Vec<Diagnostic>) {
+        let dir = TestDir::new(name);
+        let file = dir.write("ecu.c", CODE);
+        let mut code_parser = CodeParser::new();
+        code_parser.set_all_declarations(true);
+        code_parser.add_file_path(file.clone()).unwrap();
+        let mut errors = Vec::new();
+        let declarations = code_parser.parse_files(&mut errors, &mut Vec::new());
+        assert!(errors.is_empty());
+        let mut log_msgs = Vec::new();
+        let a2l_file = a2lfile::load_from_string(LEGACY, None, &mut log_msgs, false).unwrap();
+        let mut diagnostics = Vec::new();
+        let seed = seed(&a2l_file.project.module[0], &declarations, &mut diagnostics).unwrap();
+        (dir, file, seed, diagnostics)
+    }
+
+    #[test]
+    fn test_seed() {
+        let (_dir, file, seed, diagnostics) = seed_code("a2l_seed_test_seed");
+        assert_eq!(
+            seed.report.seeded,
+            vec![
+                "MEASUREMENT rpm",
+                "MEASUREMENT engine.state",
+                "CHARACTERISTIC gain",
+                "CHARACTERISTIC curve",
+                "AXIS_PTS curve_axis"
+            ]
+        );
+        assert_eq!(seed.report.annotated, vec!["MEASUREMENT annotated"]);
+        assert_eq!(seed.report.missing, vec!["MEASUREMENT missing"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(
+            diagnostics[0]
+                .message
+                .contains("'formula' can't be annotated: the FORM conversion")
+        );
+        // the comment is inserted at the definition, not at the extern declaration
+        assert_eq!(diagnostics[0].location, SourceLocation::new(&file, 5, 1));
+
+        let insertions = &seed.files[0].sorted_insertions();
+        let comment = |line: usize| {
+            insertions
+                .iter()
+                .find(|(number, _)| *number == line)
+                .unwrap()
+                .1
+                .join("\n")
+        };
+        assert_eq!(
+            comment(3),
+            "// a2l on\n// a2l-type measurement\n// a2l-description \"engine speed\"\n// a2l-unit 1/min\n\
+             // a2l-min 0\n// a2l-max 8000\n// a2l-linear-coeffs 0.25 0\n// a2l-format %6.1\n\
+             // a2l-display-identifier EngineSpeed\n// a2l-group engine\n// a2l-byte-order msb_first"
+        );
+        // the member and its struct variable are annotated
+        assert_eq!(
+            comment(9),
+            "    // a2l on\n    // a2l-type measurement\n    // a2l-min 0\n    // a2l-max 2\n\
+             \x20   // a2l-vtab 0 off 1 idle 2 \"full load\""
+        );
+        assert_eq!(comment(11), "// a2l on");
+        assert_eq!(
+            comment(16),
+            "// a2l on\n// a2l-type characteristic\n// a2l-description \"gain of the \\\"controller\\\"\"\n\
+             // a2l-unit %\n// a2l-min -1.5\n// a2l-max 2.5e20\n// a2l-max-refresh 50ms\n// a2l-read-only"
+        );
+        assert_eq!(
+            comment(17),
+            "// a2l on\n// a2l-type characteristic\n// a2l-characteristic-type curve\n\
+             // a2l-min 0\n// a2l-max 100\n// a2l-axis-ref curve_axis"
+        );
+        assert_eq!(
+            comment(18),
+            "// a2l on\n// a2l-type axis_pts\n// a2l-input-quantity rpm\n// a2l-min 0\n// a2l-max 8000"
+        );
+    }
+
+    #[test]
+    fn test_patch() {
+        let (dir, _, seed, _) = seed_code("a2l_seed_test_patch");
+        let patch = seed.patch();
+        // the source is outside the working directory, the patch applies in its directory
+        assert_eq!(seed.patch_root(), dir.path);
+        assert!(patch.starts_with("--- a/ecu.c\n+++ b/ecu.c\n@@ -1,18 +1,54 @@\n"));
+        assert!(patch.contains("\n+// a2l-byte-order msb_first\n uint16_t rpm;\n"));
+        assert!(patch.ends_with("\n+// a2l-max 8000\n const int16_t curve_axis[8];\n"));
+
+        // insertions far apart get their own hunks, a missing final newline is marked
+        let code: Vec<String> = (1..=20).map(|line| format!("line{}", line)).collect();
+        let file = SeededFile {
+            path: "src/ecu.c".to_string(),
+            code: code.join("\n"),
+            insertions: vec![(20, vec!["// b".to_string()]), (2, vec!["// a".to_string()])],
+        };
+        assert_eq!(
+            file.hunks(),
+            "@@ -1,4 +1,5 @@\n line1\n+// a\n line2\n line3\n line4\n\
+             @@ -17,4 +18,5 @@\n line17\n line18\n line19\n+// b\n line20\n\\ No newline at end of file\n"
+        );
+        let current_dir = std::env::current_dir().unwrap();
+        assert_eq!(patch_path("./src/ecu.c", &current_dir), "src/ecu.c");
+        assert_eq!(patch_path("src/../include/ecu.h", &current_dir), "include/ecu.h");
+
+        // the patch of several files applies in their common directory
+        let seeded_file = |path: PathBuf| SeededFile {
+            path: path.to_string_lossy().to_string(),
+            code: "int a;\n".to_string(),
+            insertions: vec![(1, vec!["// a2l on".to_string()])],
+        };
+        let seed = Seed {
+            report: SeedReport::default(),
+            files: vec![
+                seeded_file(dir.path.join("src/ecu.c")),
+                seeded_file(dir.path.join("include/ecu.h")),
+            ],
+        };
+        assert_eq!(seed.patch_root(), dir.path);
+        let patch = seed.patch();
+        assert!(patch.starts_with("--- a/src/ecu.c\n+++ b/src/ecu.c\n"));
+        assert!(patch.contains("\n--- a/include/ecu.h\n+++ b/include/ecu.h\n"));
+    }
+
+    #[test]
+    fn test_write_sources() {
+        let (_dir, file, seed, _) = seed_code("a2l_seed_test_write_sources");
+        seed.write_sources().unwrap();
+        let code = std::fs::read_to_string(&file).unwrap();
+        assert!(code.contains("struct engine_data {\n    uint16_t speed;\n    // a2l on\n"));
+        assert!(code.ends_with("// a2l-max 8000\nconst int16_t curve_axis[8];\n"));
+
+        // the seeded sources describe the objects of the a2l file again
+        let mut code_parser = CodeParser::new();
+        code_parser.add_file_path(file.clone()).unwrap();
+        let mut errors = Vec::new();
+        let mut diagnostics = Vec::new();
+        let declarations = code_parser.parse_files(&mut errors, &mut diagnostics);
+        let mut generator = A2lCommentGenerator::new("ecu", "main");
+        for declaration in &declarations {
+            generator.add_declaration(declaration, &mut diagnostics).unwrap();
+        }
+        generator.finish(&mut errors);
+        assert!(
+            errors.is_empty() && diagnostics.is_empty(),
+            "{:?} {:?}",
+            errors,
+            diagnostics
+        );
+        let module = &generator.a2l_file().project.module[0];
+        let rpm = module
+            .measurement
+            .iter()
+            .find(|object| object.name == "rpm")
+            .unwrap();
+        assert_eq!(rpm.long_identifier, "engine speed");
+        assert_eq!((rpm.lower_limit, rpm.upper_limit), (0.0, 8000.0));
+        let conversion = module
+            .compu_method
+            .iter()
+            .find(|object| object.name == rpm.conversion)
+            .unwrap();
+        assert_eq!(conversion.coeffs_linear.as_ref().unwrap().a, 0.25);
+        assert_eq!(conversion.format, "%6.1");
+        assert_eq!(conversion.unit, "1/min");
+        let state = module
+            .measurement
+            .iter()
+            .find(|object| object.name == "engine.state")
+            .unwrap();
+        let conversion = module
+            .compu_method
+            .iter()
+            .find(|object| object.name == state.conversion)
+            .unwrap();
+        assert_eq!(conversion.conversion_type, ConversionType::TabVerb);
+        let gain = module
+            .characteristic
+            .iter()
+            .find(|object| object.name == "gain")
+            .unwrap();
+        assert_eq!(gain.long_identifier, "gain of the \"controller\"");
+        assert_eq!(gain.upper_limit, 2.5e20);
+        assert!(gain.read_only.is_some());
+        let curve = module
+            .characteristic
+            .iter()
+            .find(|object| object.name == "curve")
+            .unwrap();
+        assert_eq!(
+            curve.axis_descr[0].axis_pts_ref.as_ref().unwrap().axis_points,
+            "curve_axis"
+        );
+        let group = module
+            .group
+            .iter()
+            .find(|object| object.name == "engine")
+            .unwrap();
+        assert_eq!(
+            group.ref_measurement.as_ref().unwrap().identifier_list,
+            vec!["rpm"]
+        );
+    }
+
+    #[test]
+    fn test_seed_file() {
+        let dir = TestDir::new("a2l_seed_test_seed_file");
+        let path = dir.write("empty.a2l", "ASAP2_VERSION 1 71\n/begin PROJECT p \"\"\n/end PROJECT\n");
+        let output_path = dir.path.join("seed.patch").to_string_lossy().to_string();
+        let mut diagnostics = Vec::new();
+        // there is no module to take the objects from
+        let error = seed_file(&path, &[], &output_path, false, &mut diagnostics).unwrap_err();
+        assert!(error.to_string().ends_with("expected one MODULE, found 0"));
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!dir.path.join("seed.patch").exists());
+    }
+
+    #[test]
+    fn test_argument() {
+        assert_eq!(argument("degC"), "degC");
+        assert_eq!(argument(""), "\"\"");
+        assert_eq!(argument("a \"b\"\nc\\d"), "\"a \\\"b\\\" c\\\\d\"");
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::error::SourceLocation;
+
+// a named value of a C enum
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enumerator {
+    pub name: String,
+    pub value: i64,
+}
+
+// a member of a struct or union as declared in its body
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructMember {
+    pub name: String,
+    pub c_type: String,
+    // the C type with typedefs resolved
+    pub base_type: String,
+    pub array_dims: Vec<usize>,
+    // declared with a width like "uint8_t flag : 1;", the layout of a bitfield depends on the compiler
+    pub is_bitfield: bool,
+    pub declaration: String,
+    pub location: SourceLocation,
+    // annotation comment in front of the member and its location
+    pub comment: Option<(String, SourceLocation)>,
+    pub enumerators: Vec<Enumerator>,
+    // members of a nested struct or union
+    pub struct_type: Option<StructType>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructType {
+    pub is_union: bool,
+    pub members: Vec<StructMember>,
+}
+
+// sizes in bytes of the C types which differ between targets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetProfile {
+    pub int_size: usize,
+    pub long_size: usize,
+    pub pointer_size: usize,
+    pub size_t_size: usize,
+    // signedness of a plain char
+    pub char_signed: bool,
+    pub big_endian: bool,
+    // largest alignment of a member of a struct, smaller types are aligned to their size
+    pub max_alignment: usize,
+}
+
+impl Default for TargetProfile {
+    fn default() -> Self {
+        TargetProfile::from_data_model("ilp32").unwrap()
+    }
+}
+
+impl TargetProfile {
+    // sizes of int, long and pointers of a data model, size_t has the size of a pointer
+    pub fn from_data_model(data_model: &str) -> Option<Self> {
+        let (int_size, long_size, pointer_size, max_alignment) = match data_model.to_lowercase().as_str() {
+            "ilp32" => (4, 4, 4, 8),
+            "lp64" => (4, 8, 8, 8),
+            "llp64" => (4, 4, 8, 8),
+            // 16 bit targets with near or far pointers, they align to words
+            "ip16" => (2, 4, 2, 2),
+            "lp32" => (2, 4, 4, 2),
+            _ => return None,
+        };
+        Some(TargetProfile {
+            int_size,
+            long_size,
+            pointer_size,
+            size_t_size: pointer_size,
+            char_signed: true,
+            big_endian: false,
+            max_alignment,
+        })
+    }
+}
+
+// types and macros defined in the parsed code
+// types are looked up by the type name used in a declaration, e.g. "enum state" or the name of a typedef
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    enums: HashMap<String, Vec<Enumerator>>,
+    structs: HashMap<String, StructType>,
+    // typedefs of other types than enums and structs with their resolved base type
+    typedefs: HashMap<String, String>,
+    // dimensions of array typedefs like "typedef uint8_t buffer_t[8];"
+    typedef_dims: HashMap<String, Vec<usize>>,
+    macros: HashMap<String, String>,
+    // only known for defined(), their invocations can't be evaluated
+    function_macros: HashSet<String>,
+    // headers whose definitions are already in the table
+    included_files: HashSet<PathBuf>,
+}
+
+impl SymbolTable {
+    pub fn add_enum(&mut self, name: &str, enumerators: Vec<Enumerator>) {
+        self.enums.insert(normalize_type_name(name), enumerators);
+    }
+
+    pub fn enumerators(&self, name: &str) -> Option<&Vec<Enumerator>> {
+        self.enums.get(&normalize_type_name(name))
+    }
+
+    pub fn add_struct(&mut self, name: &str, struct_type: StructType) {
+        self.structs.insert(normalize_type_name(name), struct_type);
+    }
+
+    pub fn struct_type(&self, name: &str) -> Option<&StructType> {
+        self.structs.get(&normalize_type_name(name))
+    }
+
+    pub fn add_typedef(&mut self, name: &str, c_type: &str) {
+        let base_type = self.base_type(c_type);
+        self.typedefs.insert(normalize_type_name(name), base_type);
+    }
+
+    pub fn add_typedef_dims(&mut self, name: &str, array_dims: Vec<usize>) {
+        self.typedef_dims.insert(normalize_type_name(name), array_dims);
+    }
+
+    // dimensions of an array typedef, empty for other types
+    pub fn typedef_dims(&self, c_type: &str) -> &[usize] {
+        self.typedef_dims
+            .get(&normalize_type_name(c_type))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // resolve a chain of typedefs like "Speed_t" -> "uint16" -> "unsigned short int" to "unsigned short"
+    // a type which isn't a typedef is its own base type
+    pub fn base_type(&self, c_type: &str) -> String {
+        let name = canonical_type_name(c_type);
+        self.typedefs.get(&name).cloned().unwrap_or(name)
+    }
+
+    pub fn add_macro(&mut self, name: &str, value: &str) {
+        self.macros.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn add_function_macro(&mut self, name: &str) {
+        self.function_macros.insert(name.to_string());
+    }
+
+    pub fn remove_macro(&mut self, name: &str) {
+        self.macros.remove(name);
+        self.function_macros.remove(name);
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name) || self.function_macros.contains(name)
+    }
+
+    // remember an included header, false if it was included before
+    pub fn add_included_file(&mut self, path: PathBuf) -> bool {
+        self.included_files.insert(path)
+    }
+
+    // value of an object-like macro
+    pub fn macro_value(&self, name: &str) -> Option<&str> {
+        self.macros.get(name).map(String::as_str)
+    }
+
+    // value of an enumerator of any known enum, they share one namespace in C
+    pub fn enumerator_value(&self, name: &str) -> Option<i64> {
+        self.enums
+            .values()
+            .flatten()
+            .find(|enumerator| enumerator.name == name)
+            .map(|enumerator| enumerator.value)
+    }
+}
+
+// "enum  state" and "enum state" name the same type
+fn normalize_type_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// write the spellings of a basic integer type the same way, e.g. "short unsigned int" as "unsigned short"
+fn canonical_type_name(name: &str) -> String {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let integer_words = ["signed", "unsigned", "char", "short", "int", "long"];
+    if words.is_empty() || !words.iter().all(|word| integer_words.contains(word)) {
+        return normalize_type_name(name);
+    }
+    let longs = words.iter().filter(|word| **word == "long").count();
+    let base = if words.contains(&"char") {
+        "char"
+    } else if words.contains(&"short") {
+        "short"
+    } else if longs == 1 {
+        "long"
+    } else if longs > 1 {
+        "long long"
+    } else {
+        "int"
+    };
+    if words.contains(&"unsigned") {
+        format!("unsigned {}", base)
+    } else if words.contains(&"signed") && base == "char" {
+        // the signedness of a plain char depends on the compiler
+        "signed char".to_string()
+    } else {
+        base.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_type() {
+        let mut symbols = SymbolTable::default();
+        symbols.add_typedef("uint16", "short  unsigned int");
+        symbols.add_typedef("Speed_t", "uint16");
+        symbols.add_typedef("Counter_t", "long signed int");
+        assert_eq!(symbols.base_type("Speed_t"), "unsigned short");
+        assert_eq!(symbols.base_type("Counter_t"), "long");
+        assert_eq!(symbols.base_type("signed"), "int");
+        assert_eq!(symbols.base_type("unsigned"), "unsigned int");
+        assert_eq!(symbols.base_type("signed char"), "signed char");
+        assert_eq!(symbols.base_type("unsigned long long int"), "unsigned long long");
+        assert_eq!(symbols.base_type("long double"), "long double");
+        assert_eq!(symbols.base_type("uint8_t"), "uint8_t");
+        assert_eq!(symbols.base_type("uint8_t  *"), "uint8_t *");
+    }
+
+    #[test]
+    fn test_target_profile() {
+        let lp64 = TargetProfile::from_data_model("LP64").unwrap();
+        assert_eq!((lp64.int_size, lp64.long_size, lp64.pointer_size, lp64.size_t_size), (4, 8, 8, 8));
+        let ip16 = TargetProfile::from_data_model("ip16").unwrap();
+        assert_eq!((ip16.int_size, ip16.long_size, ip16.pointer_size), (2, 4, 2));
+        assert_eq!(ip16.max_alignment, 2);
+        assert_eq!(TargetProfile::default(), TargetProfile::from_data_model("ilp32").unwrap());
+        assert!(TargetProfile::default().char_signed);
+        assert!(TargetProfile::from_data_model("ilp16").is_none());
+    }
+}
@@ -0,0 +1,317 @@
+// command line handling of the generator
+
+use crate::c_types::TargetProfile;
+
+pub const USAGE: &str = "\
+Usage: A2lCommentGenerator [OPTIONS] <FILE|DIRECTORY>...
+
+Generates an a2l file from the a2l annotation comments in C source files.
+Directories are searched recursively for .c files.
+
+Options:
+  -o, --output <FILE>         path of the generated a2l file [default: output.a2l]
+      --merge <FILE>          merge the generated objects into an existing a2l file, objects
+                              are matched by name, the file is updated unless -o is given;
+                              previously generated objects without annotation are removed
+      --seed-from <FILE>      reverse mode: derive annotation comments from the objects of an
+                              existing a2l file and write them as a patch of the C sources
+                              to -o [default: stdout]
+      --in-place              with --seed-from, insert the comments into the C sources
+      --elf <FILE>            take the addresses of the variables from the linked firmware
+      --dwarf                 verify data types, array dimensions and struct layouts
+                              with the debug info of the --elf firmware
+      --prefer-dwarf          like --dwarf, but use the debug info where it differs from the C source
+      --map <FILE>            take the addresses of the variables from the map file of the linker
+      --map-format <FORMAT>   format of the map file: gnu, iar or ghs [default: detected]
+  -D <NAME[=VALUE]>           define a preprocessor macro, may be repeated
+  -I <DIRECTORY>              search included headers in the directory, may be repeated
+      --project <NAME>        name of the a2l PROJECT [default: new_project]
+      --module <NAME>         name of the a2l MODULE [default: new_module]
+      --typedef-structures    describe struct instances by TYPEDEF_STRUCTURE and INSTANCE
+                              instead of one object per member
+      --legacy-arrays         also write the deprecated NUMBER and ARRAY_SIZE keywords for arrays
+      --data-model <MODEL>    sizes of int, long and pointers on the target:
+                              ilp32, lp64, llp64, ip16 or lp32 [default: ilp32]
+      --unsigned-char         a plain char is unsigned on the target
+      --byte-order <ORDER>    byte order of the target: little or big [default: little]
+      --deny-warnings         fail if an annotation contains mistakes
+  -v, --verbose               print every found declaration
+  -q, --quiet                 only print errors
+  -h, --help                  print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug)]
+pub struct CliOptions {
+    pub input_paths: Vec<String>,
+    pub output_path: String,
+    pub merge_path: Option<String>,
+    pub seed_path: Option<String>,
+    pub in_place: bool,
+    pub elf_path: Option<String>,
+    pub check_dwarf: bool,
+    pub prefer_dwarf: bool,
+    pub map_path: Option<String>,
+    pub map_format: Option<String>,
+    pub defines: Vec<(String, String)>,
+    pub include_paths: Vec<String>,
+    pub project_name: String,
+    pub module_name: String,
+    pub typedef_structures: bool,
+    pub legacy_arrays: bool,
+    pub target: TargetProfile,
+    pub verbosity: Verbosity,
+    pub deny_warnings: bool,
+    pub show_help: bool,
+}
+
+impl Default for CliOptions {
+    fn default() -> Self {
+        CliOptions {
+            input_paths: Vec::new(),
+            output_path: "output.a2l".to_string(),
+            merge_path: None,
+            seed_path: None,
+            in_place: false,
+            elf_path: None,
+            check_dwarf: false,
+            prefer_dwarf: false,
+            map_path: None,
+            map_format: None,
+            defines: Vec::new(),
+            include_paths: Vec::new(),
+            project_name: "new_project".to_string(),
+            module_name: "new_module".to_string(),
+            typedef_structures: false,
+            legacy_arrays: false,
+            target: TargetProfile::default(),
+            verbosity: Verbosity::Normal,
+            deny_warnings: false,
+            show_help: false,
+        }
+    }
+}
+
+impl CliOptions {
+    // parse the command line arguments, without the program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = CliOptions::default();
+        // applied after the arguments, the data model may come later
+        let mut unsigned_char = false;
+        let mut big_endian = false;
+        let mut output_given = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.show_help = true,
+                "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+                "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+                "--deny-warnings" => options.deny_warnings = true,
+                "--typedef-structures" => options.typedef_structures = true,
+                "--legacy-arrays" => options.legacy_arrays = true,
+                "--unsigned-char" => unsigned_char = true,
+                "--byte-order" => {
+                    big_endian = match next_value(&mut args, &arg)?.as_str() {
+                        "little" => false,
+                        "big" => true,
+                        byte_order => return Err(format!("unknown byte order '{}'", byte_order)),
+                    }
+                }
+                "--data-model" => {
+                    let data_model = next_value(&mut args, &arg)?;
+                    options.target = TargetProfile::from_data_model(&data_model)
+                        .ok_or_else(|| format!("unknown data model '{}'", data_model))?;
+                }
+                "-o" | "--output" => {
+                    options.output_path = next_value(&mut args, &arg)?;
+                    output_given = true;
+                }
+                "--merge" => options.merge_path = Some(next_value(&mut args, &arg)?),
+                "--seed-from" => options.seed_path = Some(next_value(&mut args, &arg)?),
+                "--in-place" => options.in_place = true,
+                "--elf" => options.elf_path = Some(next_value(&mut args, &arg)?),
+                "--dwarf" => options.check_dwarf = true,
+                "--prefer-dwarf" => {
+                    options.check_dwarf = true;
+                    options.prefer_dwarf = true;
+                }
+                "--map" => options.map_path = Some(next_value(&mut args, &arg)?),
+                "--map-format" => options.map_format = Some(next_value(&mut args, &arg)?),
+                "--project" => options.project_name = next_value(&mut args, &arg)?,
+                "--module" => options.module_name = next_value(&mut args, &arg)?,
+                "-D" => {
+                    let define = next_value(&mut args, &arg)?;
+                    options.defines.push(parse_define(&define)?);
+                }
+                _ if arg.starts_with("-D") => options.defines.push(parse_define(&arg[2..])?),
+                "-I" => options.include_paths.push(next_value(&mut args, &arg)?),
+                _ if arg.starts_with("-I") => options.include_paths.push(arg[2..].to_string()),
+                _ if arg.starts_with("--output=") => {
+                    options.output_path = arg["--output=".len()..].to_string();
+                    output_given = true;
+                }
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("unknown option '{}'", arg));
+                }
+                _ => options.input_paths.push(arg),
+            }
+        }
+        options.target.char_signed = !unsigned_char;
+        if let Some(merge_path) = &options.merge_path
+            && !output_given
+        {
+            options.output_path = merge_path.clone();
+        }
+        // the patch of the seed mode goes to stdout by default
+        if options.seed_path.is_some() && !output_given {
+            options.output_path = "-".to_string();
+        }
+        options.target.big_endian = big_endian;
+        if options.elf_path.is_some() && options.map_path.is_some() {
+            return Err("the addresses are taken either from --elf or from --map".to_string());
+        }
+        if options.seed_path.is_some() && options.merge_path.is_some() {
+            return Err("--seed-from reads an a2l file and doesn't write one, it can't be combined with --merge".to_string());
+        }
+        if options.in_place && (options.seed_path.is_none() || output_given) {
+            return Err("--in-place rewrites the sources of --seed-from instead of writing a patch to -o".to_string());
+        }
+        if options.check_dwarf && options.elf_path.is_none() {
+            return Err("the debug info is read from the firmware given by --elf".to_string());
+        }
+        if options.input_paths.is_empty() && !options.show_help {
+            return Err("no input files given".to_string());
+        }
+        Ok(options)
+    }
+}
+
+// get the value of an option which expects an argument
+fn next_value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("option '{}' requires a value", option))
+}
+
+// split "NAME=VALUE" into its parts, a define without a value is defined as 1 like in the C compiler
+fn parse_define(define: &str) -> Result<(String, String), String> {
+    let (name, value) = match define.split_once('=') {
+        Some((name, value)) => (name, value),
+        None => (define, "1"),
+    };
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("invalid define '{}'", define));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = CliOptions::parse(args(&[
+            "-o", "ecu.a2l", "--elf", "ecu.elf", "--prefer-dwarf", "-D", "ENABLE", "-DVARIANT=2", "-I", "include", "-Iconfig", "--project", "Ecu",
+            "--module", "Main", "--typedef-structures", "--legacy-arrays", "--unsigned-char", "--data-model",
+            "LP64", "--byte-order", "big", "-v", "src", "test_file.c",
+        ]))
+        .unwrap();
+        assert_eq!(options.output_path, "ecu.a2l");
+        assert_eq!(options.elf_path.as_deref(), Some("ecu.elf"));
+        assert!(options.check_dwarf && options.prefer_dwarf);
+        assert_eq!(options.merge_path, None);
+        assert_eq!(
+            options.defines,
+            vec![
+                ("ENABLE".to_string(), "1".to_string()),
+                ("VARIANT".to_string(), "2".to_string())
+            ]
+        );
+        assert_eq!(options.include_paths, vec!["include", "config"]);
+        assert_eq!(options.project_name, "Ecu");
+        assert_eq!(options.module_name, "Main");
+        assert!(options.typedef_structures);
+        assert!(options.legacy_arrays);
+        assert_eq!(options.target.long_size, 8);
+        assert!(!options.target.char_signed);
+        assert!(options.target.big_endian);
+        assert_eq!(options.verbosity, Verbosity::Verbose);
+        assert_eq!(options.input_paths, vec!["src", "test_file.c"]);
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let options = CliOptions::parse(args(&["-q", "test_file.c"])).unwrap();
+        assert_eq!(options.output_path, "output.a2l");
+        assert_eq!(options.elf_path, None);
+        assert!(!options.check_dwarf);
+        assert_eq!(options.map_path, None);
+        assert_eq!(options.map_format, None);
+        assert!(options.defines.is_empty());
+        assert!(options.include_paths.is_empty());
+        assert_eq!(options.verbosity, Verbosity::Quiet);
+        assert!(!options.deny_warnings);
+        assert!(!options.typedef_structures);
+        assert!(!options.legacy_arrays);
+        assert_eq!(options.target, TargetProfile::default());
+        let options = CliOptions::parse(args(&["--deny-warnings", "test_file.c"])).unwrap();
+        assert!(options.deny_warnings);
+    }
+
+    #[test]
+    fn test_parse_map_file() {
+        let options = CliOptions::parse(args(&["--map", "ecu.map", "--map-format", "iar", "test_file.c"])).unwrap();
+        assert_eq!(options.map_path.as_deref(), Some("ecu.map"));
+        assert_eq!(options.map_format.as_deref(), Some("iar"));
+    }
+
+    #[test]
+    fn test_parse_merge() {
+        let options = CliOptions::parse(args(&["--merge", "ecu.a2l", "test_file.c"])).unwrap();
+        assert_eq!(options.merge_path.as_deref(), Some("ecu.a2l"));
+        assert_eq!(options.output_path, "ecu.a2l");
+        let options = CliOptions::parse(args(&["--merge", "ecu.a2l", "--output=new.a2l", "test_file.c"])).unwrap();
+        assert_eq!(options.output_path, "new.a2l");
+    }
+
+    #[test]
+    fn test_parse_seed() {
+        let options = CliOptions::parse(args(&["--seed-from", "legacy.a2l", "src"])).unwrap();
+        assert_eq!(options.seed_path.as_deref(), Some("legacy.a2l"));
+        assert_eq!(options.output_path, "-");
+        assert!(!options.in_place);
+        let options = CliOptions::parse(args(&["--seed-from", "legacy.a2l", "-o", "seed.patch", "src"])).unwrap();
+        assert_eq!(options.output_path, "seed.patch");
+        let options = CliOptions::parse(args(&["--seed-from", "legacy.a2l", "--in-place", "src"])).unwrap();
+        assert!(options.in_place);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(CliOptions::parse(args(&[])).is_err());
+        assert!(CliOptions::parse(args(&["-o"])).is_err());
+        assert!(CliOptions::parse(args(&["--unknown", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["-D", "1=2", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["--data-model", "ilp16", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["--byte-order", "middle", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["--elf", "ecu.elf", "--map", "ecu.map", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["--dwarf", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["--seed-from", "a.a2l", "--merge", "b.a2l", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["--in-place", "test_file.c"])).is_err());
+        assert!(CliOptions::parse(args(&["--seed-from", "a.a2l", "--in-place", "-o", "a.patch", "src"])).is_err());
+        assert!(CliOptions::parse(args(&["--help"])).unwrap().show_help);
+    }
+}
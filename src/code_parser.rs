@@ -1,64 +1,824 @@
-use a2lfile::*;
-use regex::Regex;
-use tree_sitter::{Node, Parser, Query, QueryCursor, StreamingIterator};
-use tree_sitter_c::LANGUAGE;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tree_sitter::{Node, Parser};
+
+use crate::a2l_comment_parser::is_a2l_on;
+use crate::c_types::*;
+use crate::diagnostics::Diagnostic;
+use crate::error::*;
+use crate::preprocessor::*;
+
+// a declaration which is preceded by an "a2l on" comment block
+// if the parser collects all declarations, the comment of the other declarations is empty
+#[derive(Debug, Clone)]
+pub struct AnnotatedDeclaration {
+    pub comment: String,
+    pub comment_location: SourceLocation,
+    pub declaration: String,
+    pub location: SourceLocation,
+    pub c_type: String,
+    // the C type with typedefs resolved
+    pub base_type: String,
+    pub name: String,
+    // dimensions of an array, e.g. [4, 8] for "x[4][8]", empty for scalars
+    pub array_dims: Vec<usize>,
+    // values of the enum type of the variable, empty for other types
+    pub enumerators: Vec<Enumerator>,
+    // members of the struct or union type of the variable
+    pub struct_type: Option<StructType>,
+}
+
+impl AnnotatedDeclaration {
+    // the annotated members of a struct instance with names like "engine.rpm"
+    pub fn members(&self) -> Vec<AnnotatedDeclaration> {
+        let mut members = Vec::new();
+        if let Some(struct_type) = &self.struct_type {
+            flatten_members(&self.name, struct_type, &mut members);
+        }
+        members
+    }
+
+    // an annotated member of a struct as declaration of its own
+    pub fn from_member(name: String, member: &StructMember) -> Option<AnnotatedDeclaration> {
+        let (comment, comment_location) = member.comment.clone()?;
+        Some(AnnotatedDeclaration {
+            comment,
+            comment_location,
+            declaration: member.declaration.clone(),
+            location: member.location.clone(),
+            c_type: member.c_type.clone(),
+            base_type: member.base_type.clone(),
+            name,
+            array_dims: member.array_dims.clone(),
+            enumerators: member.enumerators.clone(),
+            struct_type: None,
+        })
+    }
+}
+
+// headers of the C standard library, their types are known without parsing them
+const STANDARD_HEADERS: [&str; 15] = [
+    "assert.h", "ctype.h", "errno.h", "float.h", "inttypes.h", "limits.h", "math.h", "stdarg.h",
+    "stdbool.h", "stddef.h", "stdint.h", "stdio.h", "stdlib.h", "string.h", "time.h",
+];
 
 pub struct CodeParser {
     files_paths: Vec<String>,
+    defines: HashMap<String, String>,
+    // directories searched for included headers, in the given order
+    include_paths: Vec<String>,
+    verbose: bool,
+    // also collect the declarations without annotation comment
+    all_declarations: bool,
+    // code and syntax tree of the headers read so far, the files which include them walk them again
+    headers: RefCell<HashMap<PathBuf, (String, tree_sitter::Tree)>>,
+}
+
+impl Default for CodeParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CodeParser {
     pub fn new() -> Self {
         CodeParser {
             files_paths: Vec::new(),
+            defines: HashMap::new(),
+            include_paths: Vec::new(),
+            verbose: false,
+            all_declarations: false,
+            headers: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // add a source file, directories are searched recursively for .c files
+    pub fn add_file_path(&mut self, file_path: String) -> Result<(), GeneratorError> {
+        let path = Path::new(&file_path);
+        if path.is_dir() {
+            let mut entries: Vec<_> = std::fs::read_dir(path)
+                .map_err(|error| GeneratorError::Read {
+                    file: file_path.clone(),
+                    message: error.to_string(),
+                })?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect();
+            // keep the order of the generated objects stable
+            entries.sort();
+            for entry in entries {
+                if entry.is_dir() || entry.extension().is_some_and(|extension| extension == "c") {
+                    self.add_file_path(entry.to_string_lossy().to_string())?;
+                }
+            }
+        } else {
+            self.files_paths.push(file_path);
         }
+        Ok(())
+    }
+
+    pub fn add_define(&mut self, name: String, value: String) {
+        self.defines.insert(name, value);
+    }
+
+    pub fn add_include_path(&mut self, include_path: String) {
+        self.include_paths.push(include_path);
     }
 
-    pub fn add_file_path(&mut self, file_path: String) {
-        self.files_paths.push(file_path);
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
     }
 
-    pub fn parse_file(&self, file_path: &str) {
+    pub fn set_all_declarations(&mut self, all_declarations: bool) {
+        self.all_declarations = all_declarations;
+    }
+
+    // parse all added files and collect the annotated declarations
+    // files which can't be parsed are skipped, the reason is added to errors
+    // every file starts with its own symbol table, the macros of one file don't leak into the next
+    pub fn parse_files(
+        &self,
+        errors: &mut Vec<GeneratorError>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Vec<AnnotatedDeclaration> {
+        let mut vec_found = Vec::new();
+        for file_path in &self.files_paths {
+            match self.parse_file(file_path, &mut self.symbol_table(), diagnostics) {
+                Ok(found) => vec_found.extend(found),
+                Err(error) => errors.push(error),
+            }
+        }
+        vec_found
+    }
+
+    // a symbol table which knows the macros given on the command line
+    pub fn symbol_table(&self) -> SymbolTable {
+        let mut symbols = SymbolTable::default();
+        for (name, value) in &self.defines {
+            symbols.add_macro(name, value);
+        }
+        symbols
+    }
+
+    pub fn parse_file(
+        &self,
+        file_path: &str,
+        symbols: &mut SymbolTable,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<AnnotatedDeclaration>, GeneratorError> {
+        let (code, tree) = self.read_tree(file_path)?;
+        self.walk_through_code(&tree, &code, file_path, symbols, diagnostics)
+    }
+
+    // read and parse a file, a syntax error anywhere in the file is an error
+    fn read_tree(&self, file_path: &str) -> Result<(String, tree_sitter::Tree), GeneratorError> {
         // read in file
-        let code = std::fs::read_to_string(file_path).expect("Unable to read file");
+        let code = std::fs::read_to_string(file_path).map_err(|error| GeneratorError::Read {
+            file: file_path.to_string(),
+            message: error.to_string(),
+        })?;
         // parse the code
         let mut parser = Parser::new();
         let language = tree_sitter_c::LANGUAGE;
         parser
             .set_language(&language.into())
             .expect("Error loading C parser");
-        let tree = parser.parse(&code, None).unwrap(); // Pass a reference to `code`
-        assert!(!tree.root_node().has_error());
-        // walk through the code
-        self.walk_through_code(&tree, &code).unwrap();
-    }
-
-    fn walk_through_code(&self, tree: &tree_sitter::Tree, code: &str) -> Result<(), tree_sitter::QueryError> {
-        // Define the Query
-        let query_source = r#"
-        (translation_unit
-            (declaration
-                declarator: (init_declarator
-                    declarator: (identifier) @variable))
+        let tree = parser.parse(&code, None).ok_or_else(|| GeneratorError::Syntax {
+            location: SourceLocation::new(file_path, 1, 1),
+            message: "unable to parse file".to_string(),
+        })?;
+        if tree.root_node().has_error() {
+            let error_node = first_error_node(tree.root_node()).unwrap_or(tree.root_node());
+            let message = if error_node.is_missing() {
+                format!("missing '{}'", error_node.kind())
+            } else {
+                format!("unexpected '{}'", self.get_node_text(&error_node, &code).trim())
+            };
+            return Err(GeneratorError::Syntax {
+                location: self.get_location(&error_node, &code, file_path),
+                message,
+            });
+        }
+        Ok((code, tree))
+    }
+
+    // pair every "a2l on" comment block with the declaration directly following it
+    fn walk_through_code(
+        &self,
+        tree: &tree_sitter::Tree,
+        code: &str,
+        file_path: &str,
+        symbols: &mut SymbolTable,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Vec<AnnotatedDeclaration>, GeneratorError> {
+        let mut cursor = tree.root_node().walk();
+        let items: Vec<Node> = tree.root_node().children(&mut cursor).collect();
+        let mut vec_found = Vec::new();
+        self.walk_items(&items, code, file_path, symbols, &mut vec_found, diagnostics)?;
+        Ok(vec_found)
+    }
+
+    // walk through a list of items at one nesting level, the items of the compiled branch of a
+    // conditional and of an extern "C" block are walked like top-level ones
+    // an "a2l on" block doesn't reach into or out of a nested level
+    fn walk_items(
+        &self,
+        items: &[Node],
+        code: &str,
+        file_path: &str,
+        symbols: &mut SymbolTable,
+        vec_found: &mut Vec<AnnotatedDeclaration>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        // the first comment node of the current "a2l on" block
+        let mut comment_start: Option<Node> = None;
+        let mut comment_end = 0;
+
+        for child in items {
+            let child = *child;
+            match child.kind() {
+                "preproc_if" | "preproc_ifdef" => {
+                    let branch = self.active_branch(&child, code, file_path, symbols)?;
+                    self.walk_items(&branch, code, file_path, symbols, vec_found, diagnostics)?;
+                }
+                "linkage_specification" => {
+                    if let Some(body) = child.child_by_field_name("body") {
+                        let nested: Vec<Node> = if body.kind() == "declaration_list" {
+                            let mut cursor = body.walk();
+                            body.named_children(&mut cursor).collect()
+                        } else {
+                            vec![body]
+                        };
+                        self.walk_items(&nested, code, file_path, symbols, vec_found, diagnostics)?;
+                    }
+                }
+                _ => self.track_definitions(&child, code, file_path, symbols, diagnostics)?,
+            }
+            if child.kind() == "comment" {
+                let comment_text = self.get_node_text(&child, code);
+                if comment_start.is_none() && is_a2l_on(&strip_comment_markers(&comment_text)) {
+                    comment_start = Some(child);
+                }
+                comment_end = child.end_byte();
+            } else if child.kind() == "declaration" && (comment_start.is_some() || self.all_declarations) {
+                let comment_block = comment_start
+                    .map(|first_comment| self.comment_block(&first_comment, comment_end, code, file_path));
+                match self.declarations(&child, comment_block, code, file_path, symbols) {
+                    Ok(found) => vec_found.extend(found),
+                    // declarations without annotation are only collected if they are supported
+                    Err(_) if comment_start.is_none() => {}
+                    Err(error) => return Err(error),
+                }
+                comment_start = None;
+            } else {
+                comment_start = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    // the variables of a declaration with their annotation comment block, which is empty if there is none
+    fn declarations(
+        &self,
+        declaration: &Node,
+        comment_block: Option<(String, SourceLocation)>,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<Vec<AnnotatedDeclaration>, GeneratorError> {
+        let declaration_text = self.get_node_text(declaration, code);
+        let location = self.get_location(declaration, code, file_path);
+        let (comment, comment_location) = comment_block.unwrap_or_else(|| (String::new(), location.clone()));
+        let type_node = declaration.child_by_field_name("type");
+        let c_type = type_node
+            .map(|type_node| self.get_node_text(&type_node, code))
+            .unwrap_or_default();
+        let enumerators = match type_node {
+            Some(type_node) => self.enum_type(&type_node, code, file_path, symbols)?,
+            None => Vec::new(),
+        };
+        let struct_type = match type_node {
+            Some(type_node) => self.struct_type(&type_node, code, file_path, symbols)?,
+            None => None,
+        };
+        let mut found = Vec::new();
+        for (name, array_dims, pointer) in self.declarator_names(declaration, code, file_path, symbols)? {
+            // the struct of a pointer isn't part of the variable
+            let struct_type = if pointer { None } else { struct_type.clone() };
+            let enumerators = if pointer { Vec::new() } else { enumerators.clone() };
+            if struct_type.is_some() && !array_dims.is_empty() {
+                return Err(GeneratorError::Declaration {
+                    location,
+                    message: format!("arrays of structures are not supported: {}", name),
+                });
+            }
+            found.push(AnnotatedDeclaration {
+                comment: comment.clone(),
+                comment_location: comment_location.clone(),
+                declaration: declaration_text.clone(),
+                location: location.clone(),
+                c_type: c_type.clone(),
+                base_type: pointer_type(symbols.base_type(&c_type), pointer),
+                name,
+                array_dims,
+                enumerators,
+                struct_type,
+            });
+        }
+        Ok(found)
+    }
+
+    // keep the symbol table up to date in source order: macros, #undef, types and included headers
+    fn track_definitions(
+        &self,
+        node: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &mut SymbolTable,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        match node.kind() {
+            "preproc_include" => self.include_header(node, code, file_path, symbols, diagnostics)?,
+            "preproc_def" => {
+                if let Some(name) = node.child_by_field_name("name") {
+                    let value = node
+                        .child_by_field_name("value")
+                        .map(|value| self.get_node_text(&value, code))
+                        .unwrap_or_default();
+                    symbols.add_macro(&self.get_node_text(&name, code), value.trim());
+                }
+            }
+            "preproc_function_def" => {
+                if let Some(name) = node.child_by_field_name("name") {
+                    symbols.add_function_macro(&self.get_node_text(&name, code));
+                }
+            }
+            // #undef has no node of its own
+            "preproc_call" => {
+                let directive = node.child_by_field_name("directive");
+                let argument = node.child_by_field_name("argument");
+                if let (Some(directive), Some(argument)) = (directive, argument)
+                    && self.get_node_text(&directive, code) == "#undef"
+                {
+                    symbols.remove_macro(self.get_node_text(&argument, code).trim());
+                }
+            }
+            "declaration" | "type_definition" | "enum_specifier" | "struct_specifier" | "union_specifier" => {
+                self.collect_types(node, code, file_path, symbols)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // add the definitions of an included header to the symbol table
+    // annotated declarations in headers are only collected if the header is an input file itself
+    fn include_header(
+        &self,
+        include: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &mut SymbolTable,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), GeneratorError> {
+        let Some(path_node) = include.child_by_field_name("path") else {
+            return Ok(());
+        };
+        let path_text = self.get_node_text(&path_node, code);
+        // "header.h" is searched next to the including file first, <header.h> only in the include paths
+        let (name, mut directories) = match path_node.kind() {
+            "string_literal" => {
+                let including_directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+                (path_text.trim_matches('"'), vec![including_directory.to_path_buf()])
+            }
+            "system_lib_string" => (path_text.trim_start_matches('<').trim_end_matches('>'), Vec::new()),
+            // an include by a macro name isn't supported
+            _ => return Ok(()),
+        };
+        directories.extend(self.include_paths.iter().map(PathBuf::from));
+        let Some(header_path) = directories
+            .iter()
+            .map(|directory| directory.join(name))
+            .find(|header_path| header_path.is_file())
+        else {
+            // the types of the standard headers are known without them
+            if path_node.kind() != "system_lib_string" || !STANDARD_HEADERS.contains(&name) {
+                diagnostics.push(Diagnostic::new(
+                    self.get_location(&path_node, code, file_path),
+                    path_text.chars().count(),
+                    format!("header '{}' not found, its types and macros are unknown", name),
+                ));
+            }
+            return Ok(());
+        };
+        // a header is only included once per file and only read once, even if several files include it
+        // its definitions depend on the macros of the including file, so it is walked again for every file
+        let canonical_path = header_path.canonicalize().unwrap_or(header_path.clone());
+        if !symbols.add_included_file(canonical_path.clone()) {
+            return Ok(());
+        }
+        let header_path = header_path.to_string_lossy().to_string();
+        if self.verbose {
+            println!("{}: including {}", self.get_location(include, code, file_path), header_path);
+        }
+        let cached = self.headers.borrow().get(&canonical_path).cloned();
+        let (header_code, tree) = match cached {
+            Some(header) => header,
+            None => {
+                let header = self.read_tree(&header_path)?;
+                self.headers.borrow_mut().insert(canonical_path, header.clone());
+                header
+            }
+        };
+        let mut cursor = tree.root_node().walk();
+        let items: Vec<Node> = tree.root_node().children(&mut cursor).collect();
+        self.walk_items(&items, &header_code, &header_path, symbols, &mut Vec::new(), diagnostics)
+    }
+
+    // get the items of the branch of a conditional which is compiled, empty if no branch is
+    fn active_branch<'tree>(
+        &self,
+        conditional: &Node<'tree>,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<Vec<Node<'tree>>, GeneratorError> {
+        let mut branch = *conditional;
+        loop {
+            let active = match branch.kind() {
+                "preproc_if" | "preproc_elif" => {
+                    let condition = branch
+                        .child_by_field_name("condition")
+                        .map(|condition| self.get_node_text(&condition, code))
+                        .unwrap_or_default();
+                    evaluate_condition(&condition, symbols).map_err(|message| GeneratorError::Preprocessor {
+                        location: self.get_location(&branch, code, file_path),
+                        message,
+                    })? != 0
+                }
+                "preproc_ifdef" | "preproc_elifdef" => {
+                    let defined = branch
+                        .child_by_field_name("name")
+                        .is_some_and(|name| symbols.is_defined(&self.get_node_text(&name, code)));
+                    // the directive is "#ifdef", "#ifndef", "#elifdef" or "#elifndef"
+                    let negated = branch
+                        .child(0)
+                        .is_some_and(|directive| self.get_node_text(&directive, code).ends_with("ndef"));
+                    defined != negated
+                }
+                _ => true,
+            };
+            if self.verbose {
+                let directive = self.get_node_text(&branch, code);
+                println!(
+                    "{}: {} is {}",
+                    self.get_location(&branch, code, file_path),
+                    directive.lines().next().unwrap_or_default().trim(),
+                    if active { "active" } else { "inactive" }
+                );
+            }
+            if active {
+                // the condition, the name and the alternative aren't items of the branch
+                let excluded: Vec<usize> = ["condition", "name", "alternative"]
+                    .iter()
+                    .filter_map(|field| branch.child_by_field_name(field))
+                    .map(|node| node.id())
+                    .collect();
+                let mut cursor = branch.walk();
+                return Ok(branch
+                    .named_children(&mut cursor)
+                    .filter(|item| !excluded.contains(&item.id()))
+                    .collect());
+            }
+            match branch.child_by_field_name("alternative") {
+                Some(alternative) => branch = alternative,
+                None => return Ok(Vec::new()),
+            }
+        }
+    }
+
+    // remember the enums defined by a declaration, a typedef or a plain "enum x { ... };"
+    fn collect_types(
+        &self,
+        node: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &mut SymbolTable,
+    ) -> Result<(), GeneratorError> {
+        let specifier = if matches!(node.kind(), "enum_specifier" | "struct_specifier" | "union_specifier") {
+            Some(*node)
+        } else {
+            node.child_by_field_name("type")
+        };
+        let Some(specifier) = specifier else {
+            return Ok(());
+        };
+        if let Some(body) = specifier.child_by_field_name("body")
+            && let Some(name) = specifier.child_by_field_name("name")
+        {
+            let name = self.get_node_text(&name, code);
+            match specifier.kind() {
+                "enum_specifier" => {
+                    let enumerators = self.enumerators(&body, code, file_path, symbols)?;
+                    symbols.add_enum(&format!("enum {}", name), enumerators);
+                }
+                "struct_specifier" | "union_specifier" => {
+                    let struct_type = self.struct_members(&specifier, &body, code, file_path, symbols)?;
+                    let keyword = if struct_type.is_union { "union" } else { "struct" };
+                    symbols.add_struct(&format!("{} {}", keyword, name), struct_type);
+                }
+                _ => {}
+            }
+        }
+        if node.kind() == "type_definition" {
+            let enumerators = self.enum_type(&specifier, code, file_path, symbols)?;
+            let struct_type = self.struct_type(&specifier, code, file_path, symbols)?;
+            // a typedef whose array size can't be evaluated is left out, its variables report the unknown type
+            let Ok(names) = self.declarator_names(node, code, file_path, symbols) else {
+                return Ok(());
+            };
+            for (name, array_dims, pointer) in names {
+                // a pointer type like "typedef uint8_t *buffer_t;"
+                if pointer {
+                    let base_type = pointer_type(symbols.base_type(&self.get_node_text(&specifier, code)), true);
+                    symbols.add_typedef(&name, &base_type);
+                    continue;
+                }
+                // an array type like "typedef uint8_t buffer_t[8];", the variables of it get its dimensions
+                if !array_dims.is_empty() {
+                    symbols.add_typedef_dims(&name, array_dims);
+                }
+                if !enumerators.is_empty() {
+                    symbols.add_enum(&name, enumerators.clone());
+                }
+                if let Some(struct_type) = &struct_type {
+                    symbols.add_struct(&name, struct_type.clone());
+                }
+                if enumerators.is_empty() && struct_type.is_none() {
+                    symbols.add_typedef(&name, &self.get_node_text(&specifier, code));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // get the members of a struct or union type, None if it isn't one
+    fn struct_type(
+        &self,
+        type_node: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<Option<StructType>, GeneratorError> {
+        if let Some(body) = type_node.child_by_field_name("body")
+            && matches!(type_node.kind(), "struct_specifier" | "union_specifier")
+        {
+            return self.struct_members(type_node, &body, code, file_path, symbols).map(Some);
+        }
+        Ok(symbols.struct_type(&self.get_node_text(type_node, code)).cloned())
+    }
+
+    // get the members of a struct body, every member can have its own annotation comment
+    fn struct_members(
+        &self,
+        specifier: &Node,
+        body: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<StructType, GeneratorError> {
+        let mut members = Vec::new();
+        let mut cursor = body.walk();
+        let mut comment_start: Option<Node> = None;
+        let mut comment_end = 0;
+        for child in body.named_children(&mut cursor) {
+            if child.kind() == "comment" {
+                if comment_start.is_none() && is_a2l_on(&strip_comment_markers(&self.get_node_text(&child, code))) {
+                    comment_start = Some(child);
+                }
+                comment_end = child.end_byte();
+            } else if child.kind() == "field_declaration" {
+                let comment = comment_start
+                    .map(|first_comment| self.comment_block(&first_comment, comment_end, code, file_path));
+                let type_node = child.child_by_field_name("type");
+                let c_type = type_node
+                    .map(|type_node| self.get_node_text(&type_node, code))
+                    .unwrap_or_default();
+                let (enumerators, struct_type) = match type_node {
+                    Some(type_node) => (
+                        self.enum_type(&type_node, code, file_path, symbols)?,
+                        self.struct_type(&type_node, code, file_path, symbols)?,
+                    ),
+                    None => (Vec::new(), None),
+                };
+                let mut child_cursor = child.walk();
+                let is_bitfield = child
+                    .named_children(&mut child_cursor)
+                    .any(|node| node.kind() == "bitfield_clause");
+                for (name, array_dims, pointer) in self.declarator_names(&child, code, file_path, symbols)? {
+                    members.push(StructMember {
+                        name,
+                        c_type: c_type.clone(),
+                        base_type: pointer_type(symbols.base_type(&c_type), pointer),
+                        array_dims,
+                        is_bitfield,
+                        declaration: self.get_node_text(&child, code),
+                        location: self.get_location(&child, code, file_path),
+                        comment: comment.clone(),
+                        enumerators: if pointer { Vec::new() } else { enumerators.clone() },
+                        struct_type: if pointer { None } else { struct_type.clone() },
+                    });
+                }
+                comment_start = None;
+            } else {
+                comment_start = None;
+            }
+        }
+        Ok(StructType {
+            is_union: specifier.kind() == "union_specifier",
+            members,
+        })
+    }
+
+    // the text of an annotation comment block from its first comment up to comment_end
+    // the comment block contains only comments and whitespace, this keeps the positions intact
+    fn comment_block(
+        &self,
+        first_comment: &Node,
+        comment_end: usize,
+        code: &str,
+        file_path: &str,
+    ) -> (String, SourceLocation) {
+        (
+            strip_comment_markers(&code[first_comment.start_byte()..comment_end]),
+            self.get_location(first_comment, code, file_path),
         )
-        "#;
-        let language = tree_sitter_c::LANGUAGE; // Use the `language()` function
-        let query = Query::new(&language.into(), query_source)?; // Use `?` operator safely
-        let mut query_cursor = QueryCursor::new();
+    }
 
-        // Execute the Query
-        let mut captures = query_cursor.captures(&query, tree.root_node(), code.as_bytes());
+    // get the enumerators of a type, empty if it isn't an enum
+    fn enum_type(
+        &self,
+        type_node: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<Vec<Enumerator>, GeneratorError> {
+        if let Some(body) = type_node.child_by_field_name("body")
+            && type_node.kind() == "enum_specifier"
+        {
+            return self.enumerators(&body, code, file_path, symbols);
+        }
+        Ok(symbols
+            .enumerators(&self.get_node_text(type_node, code))
+            .cloned()
+            .unwrap_or_default())
+    }
 
-        // Iterate over the captures
-        while let Some((query_match, _)) = captures.next() {
-            for capture in query_match.captures {
-                let node = capture.node; // Access the node from the capture
-                let node_text = self.get_node_text(&node, code);
-                println!("found text:\n{}\n", node_text);
+    // get the values of an enumerator list, without a value an enumerator is the previous one plus 1
+    fn enumerators(
+        &self,
+        enumerator_list: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<Vec<Enumerator>, GeneratorError> {
+        let mut enumerators: Vec<Enumerator> = Vec::new();
+        let mut cursor = enumerator_list.walk();
+        for node in enumerator_list.named_children(&mut cursor) {
+            if node.kind() != "enumerator" {
+                continue;
             }
+            let Some(name_node) = node.child_by_field_name("name") else {
+                continue;
+            };
+            let value = match node.child_by_field_name("value") {
+                Some(value_node) => {
+                    let text = self.get_node_text(&value_node, code);
+                    // earlier enumerators of the same enum aren't in the symbol table yet
+                    let identifier_value = |name: &str| {
+                        enumerators
+                            .iter()
+                            .find(|enumerator| enumerator.name == name)
+                            .map(|enumerator| enumerator.value)
+                            .or_else(|| symbols.enumerator_value(name))
+                    };
+                    evaluate(&text, symbols, &identifier_value).map_err(|message| GeneratorError::Declaration {
+                        location: self.get_location(&value_node, code, file_path),
+                        message: format!("unsupported enumerator value '{}': {}", text, message),
+                    })?
+                }
+                None => enumerators.last().map_or(0, |previous| previous.value + 1),
+            };
+            enumerators.push(Enumerator {
+                name: self.get_node_text(&name_node, code),
+                value,
+            });
         }
+        Ok(enumerators)
+    }
 
-        Ok(()) // Return success
+    // get the variable names and array dimensions of all declarators of a declaration,
+    // e.g. "a" and "b" in "int a, b[2] = {1, 2};"
+    fn declarator_names(
+        &self,
+        declaration: &Node,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<Vec<(String, Vec<usize>, bool)>, GeneratorError> {
+        let mut names = Vec::new();
+        let mut cursor = declaration.walk();
+        for declarator in declaration.children_by_field_name("declarator", &mut cursor) {
+            let mut node = declarator;
+            let mut array_dims = Vec::new();
+            let mut initializer = None;
+            let mut pointer = false;
+            // descend through init, array and pointer declarators to the identifier, the name of a typedef
+            // is a type identifier
+            while !matches!(node.kind(), "identifier" | "field_identifier" | "type_identifier") {
+                // the dimensions outside of a pointer belong to the pointed to type
+                if node.kind() == "pointer_declarator" {
+                    pointer = true;
+                    array_dims.clear();
+                }
+                if node.kind() == "init_declarator" {
+                    initializer = node.child_by_field_name("value");
+                }
+                if node.kind() == "array_declarator" {
+                    // only the outermost dimension can be taken from the initializer
+                    let initializer = if array_dims.is_empty() { initializer } else { None };
+                    array_dims.push(self.array_size(&node, initializer, code, file_path, symbols)?);
+                }
+                match node.child_by_field_name("declarator") {
+                    Some(inner) => node = inner,
+                    None => break,
+                }
+            }
+            // function declarations are not variables
+            if declarator.kind() == "function_declarator" {
+                continue;
+            }
+            if !matches!(node.kind(), "identifier" | "field_identifier" | "type_identifier") {
+                return Err(GeneratorError::Declaration {
+                    location: self.get_location(&declarator, code, file_path),
+                    message: format!(
+                        "unable to find the variable name in '{}'",
+                        self.get_node_text(&declarator, code)
+                    ),
+                });
+            }
+            // the outermost array declarator is the last dimension
+            array_dims.reverse();
+            // the dimensions of an array typedef follow the ones of the declarator
+            if !pointer && let Some(type_node) = declaration.child_by_field_name("type") {
+                array_dims.extend(symbols.typedef_dims(&self.get_node_text(&type_node, code)));
+            }
+            names.push((self.get_node_text(&node, code), array_dims, pointer));
+        }
+        Ok(names)
+    }
+
+    // get the number of elements of an array declarator, "x[] = {1, 2}" is sized by its initializer list
+    fn array_size(
+        &self,
+        array_declarator: &Node,
+        initializer: Option<Node>,
+        code: &str,
+        file_path: &str,
+        symbols: &SymbolTable,
+    ) -> Result<usize, GeneratorError> {
+        let Some(size) = array_declarator.child_by_field_name("size") else {
+            return match initializer {
+                Some(list) if list.kind() == "initializer_list" => {
+                    let mut cursor = list.walk();
+                    Ok(list
+                        .named_children(&mut cursor)
+                        .filter(|element| element.kind() != "comment")
+                        .count())
+                }
+                _ => Err(GeneratorError::Declaration {
+                    location: self.get_location(array_declarator, code, file_path),
+                    message: "unable to determine the array size".to_string(),
+                }),
+            };
+        };
+        let size_text = self.get_node_text(&size, code);
+        let size = evaluate(&size_text, symbols, &|name| symbols.enumerator_value(name))
+            .and_then(|size| usize::try_from(size).map_err(|_| "negative size".to_string()));
+        size.map_err(|message| GeneratorError::Declaration {
+            location: self.get_location(array_declarator, code, file_path),
+            message: format!("unsupported array size '{}': {}", size_text, message),
+        })
+    }
+
+    // get the position of a node, the column is counted in characters
+    fn get_location(&self, node: &Node, code: &str, file_path: &str) -> SourceLocation {
+        let start = node.start_byte();
+        let line_start = start - node.start_position().column;
+        let column = code[line_start..start].chars().count() + 1;
+        SourceLocation::new(file_path, node.start_position().row + 1, column)
     }
 
     fn get_node_text(&self, node: &Node, code: &str) -> String {
@@ -68,13 +828,536 @@ impl CodeParser {
     }
 }
 
+// parse a C integer literal like 10, 0x1F or 8u
+pub fn parse_c_integer(text: &str) -> Option<usize> {
+    let text = text.trim().trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16).ok()
+    } else if text.len() > 1 && text.starts_with('0') {
+        usize::from_str_radix(&text[1..], 8).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+// add the annotated members of a struct with the given name prefix
+fn flatten_members(prefix: &str, struct_type: &StructType, found: &mut Vec<AnnotatedDeclaration>) {
+    for member in &struct_type.members {
+        let name = format!("{}.{}", prefix, member.name);
+        if let Some(nested) = &member.struct_type {
+            flatten_members(&name, nested, found);
+        } else if let Some(declaration) = AnnotatedDeclaration::from_member(name, member) {
+            found.push(declaration);
+        }
+    }
+}
+
+// the base type of a pointer is marked by a trailing '*'
+fn pointer_type(base_type: String, pointer: bool) -> String {
+    if pointer { format!("{} *", base_type) } else { base_type }
+}
+
+// find the first syntax error in the tree
+fn first_error_node(node: Node) -> Option<Node> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.has_error()
+            && let Some(error_node) = first_error_node(child)
+        {
+            return Some(error_node);
+        }
+    }
+    None
+}
+
+// replace the "//", "/*", "*/" and leading "*" markers of a comment with spaces
+// lines and columns of the annotations stay the same as in the source file
+fn strip_comment_markers(comment: &str) -> String {
+    let mut lines = Vec::new();
+    for line in comment.lines() {
+        let mut line = line.trim_end().to_string();
+        if line.ends_with("*/") {
+            line.truncate(line.len() - 2);
+        }
+        let indent = line.len() - line.trim_start().len();
+        if line[indent..].starts_with("//") || line[indent..].starts_with("/*") {
+            line.replace_range(indent..indent + 2, "  ");
+        } else if line[indent..].starts_with('*') {
+            line.replace_range(indent..indent + 1, " ");
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_support::TestDir;
+
+    // parse a single file with the macros of the parser
+    fn parse(code_parser: &CodeParser, file_path: &str) -> Result<Vec<AnnotatedDeclaration>, GeneratorError> {
+        code_parser.parse_file(file_path, &mut code_parser.symbol_table(), &mut Vec::new())
+    }
 
     #[test]
     fn test_code_parser() {
         let mut code_parser = CodeParser::new();
-        code_parser.add_file_path("test_file.c".to_string());
-        code_parser.parse_file("test_file.c");
+        code_parser.add_file_path("test_file.c".to_string()).unwrap();
+        let mut errors = Vec::new();
+        let found = code_parser.parse_files(&mut errors, &mut Vec::new());
+        assert!(errors.is_empty());
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].name, "arraytest");
+        assert_eq!(found[0].c_type, "uint32_t");
+        assert_eq!(found[0].array_dims, vec![32]);
+        assert_eq!(found[0].comment, "   a2l on\n   a2l-unit °deg");
+        assert_eq!(found[0].comment_location, SourceLocation::new("test_file.c", 1, 1));
+        assert_eq!(found[0].location, SourceLocation::new("test_file.c", 3, 1));
+        assert_eq!(found[1].name, "velo");
+        assert_eq!(found[1].c_type, "float");
+        assert!(found[1].array_dims.is_empty());
+        assert_eq!(found[1].comment, "\na2l on\na2l-unit m/s\n");
+        assert_eq!(found[1].location, SourceLocation::new("test_file.c", 9, 1));
+    }
+
+    #[test]
+    fn test_enums() {
+        let dir = TestDir::new("a2l_code_parser_test_enums");
+        let file = dir.write(
+            "enums.c",
+            "enum gear { REVERSE = -1, NEUTRAL, FIRST, SECOND = 0x10, THIRD };\n\
+             typedef enum { OFF, ON = FIRST } switch_t;\n\
+             // a2l on\n\
+             enum gear current_gear;\n\
+             // a2l on\n\
+             switch_t fan;\n\
+             // a2l on\n\
+             int counter;\n",
+        );
+        let found = parse(&CodeParser::new(), &file).unwrap();
+        let values = |declaration: &AnnotatedDeclaration| {
+            declaration
+                .enumerators
+                .iter()
+                .map(|enumerator| (enumerator.value, enumerator.name.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            values(&found[0]),
+            vec![
+                (-1, "REVERSE".to_string()),
+                (0, "NEUTRAL".to_string()),
+                (1, "FIRST".to_string()),
+                (16, "SECOND".to_string()),
+                (17, "THIRD".to_string()),
+            ]
+        );
+        assert_eq!(values(&found[1]), vec![(0, "OFF".to_string()), (1, "ON".to_string())]);
+        assert!(found[2].enumerators.is_empty());
+
+        std::fs::write(&file, "enum e { A = sizeof(int) };\n").unwrap();
+        assert!(matches!(
+            parse(&CodeParser::new(), &file),
+            Err(GeneratorError::Declaration { .. })
+        ));
+    }
+
+    #[test]
+    fn test_array_sizes() {
+        let dir = TestDir::new("a2l_code_parser_test_array_sizes");
+        let file = dir.write(
+            "arrays.c",
+            "#define ROWS 4\n\
+             // a2l on\n\
+             uint8_t table[ROWS][COLUMNS];\n",
+        );
+        let mut code_parser = CodeParser::new();
+        code_parser.add_define("COLUMNS".to_string(), "8".to_string());
+        let found = parse(&code_parser, &file).unwrap();
+        assert_eq!(found[0].array_dims, vec![4, 8]);
+
+        let error = parse(&CodeParser::new(), &file).unwrap_err();
+        assert!(error.to_string().contains("unsupported array size 'COLUMNS'"));
+    }
+
+    #[test]
+    fn test_preprocessor_conditionals() {
+        let dir = TestDir::new("a2l_code_parser_test_preprocessor_conditionals");
+        let file = dir.write(
+            "variants.c",
+            "#if VARIANT == 2 && defined(USE_LONG)\n\
+             #define SIZE 16\n\
+             #elif VARIANT > 2\n\
+             #define SIZE 32\n\
+             #else\n\
+             #define SIZE 8\n\
+             #endif\n\
+             #ifndef OFFSET\n\
+             #define OFFSET 1\n\
+             #endif\n\
+             // a2l on\n\
+             uint8_t first[SIZE + OFFSET];\n\
+             #undef SIZE\n\
+             #define SIZE 2\n\
+             // a2l on\n\
+             uint8_t second[SIZE * 2];\n",
+        );
+        let found = parse(&CodeParser::new(), &file).unwrap();
+        assert_eq!(found[0].array_dims, vec![9]);
+        assert_eq!(found[1].array_dims, vec![4]);
+
+        let mut code_parser = CodeParser::new();
+        code_parser.add_define("VARIANT".to_string(), "2".to_string());
+        code_parser.add_define("USE_LONG".to_string(), "".to_string());
+        code_parser.add_define("OFFSET".to_string(), "0".to_string());
+        let found = parse(&code_parser, &file).unwrap();
+        assert_eq!(found[0].array_dims, vec![16]);
+
+        let mut code_parser = CodeParser::new();
+        code_parser.add_define("VARIANT".to_string(), "3".to_string());
+        let found = parse(&code_parser, &file).unwrap();
+        assert_eq!(found[0].array_dims, vec![33]);
+
+        std::fs::write(&file, "#if 1 / VARIANT\n#endif\n").unwrap();
+        let error = parse(&CodeParser::new(), &file).unwrap_err();
+        assert!(error.to_string().contains("invalid preprocessor directive"));
+    }
+
+    #[test]
+    fn test_nested_declarations() {
+        let dir = TestDir::new("a2l_code_parser_test_nested_declarations");
+        let file = dir.write(
+            "nested.h",
+            "#if VARIANT == 1\n\
+             // a2l on\n\
+             extern uint8_t variant_one;\n\
+             #else\n\
+             // a2l on\n\
+             extern uint16_t variant_other;\n\
+             #ifndef NO_EXTRA\n\
+             /* a2l on */\n\
+             extern float extra;\n\
+             #endif\n\
+             #endif\n\
+             // a2l on\n\
+             #define UNRELATED 1\n\
+             int not_annotated;\n",
+        );
+        let found = parse(&CodeParser::new(), &file).unwrap();
+        let names: Vec<&str> = found.iter().map(|decl| decl.name.as_str()).collect();
+        assert_eq!(names, vec!["variant_other", "extra"]);
+
+        let mut code_parser = CodeParser::new();
+        code_parser.add_define("VARIANT".to_string(), "1".to_string());
+        let found = parse(&code_parser, &file).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "variant_one");
+        assert_eq!(found[0].c_type, "uint8_t");
+
+        std::fs::write(
+            &file,
+            "extern \"C\" {\n\
+             // a2l on\n\
+             uint8_t in_block;\n\
+             }\n",
+        )
+        .unwrap();
+        let found = parse(&CodeParser::new(), &file).unwrap();
+        assert_eq!(found[0].name, "in_block");
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = TestDir::new("a2l_code_parser_test_includes");
+        dir.write(
+            "include/types.h",
+            "#ifndef TYPES_H\n\
+             #define TYPES_H\n\
+             #include \"sizes.h\"\n\
+             typedef enum { OFF, ON } switch_t;\n\
+             /* a2l on */\n\
+             extern switch_t in_header;\n\
+             #endif\n",
+        );
+        dir.write("include/sizes.h", "#define SIZE 4\n");
+        dir.write("config.h", "#define CHANNELS 2\n");
+        let first = dir.write(
+            "first.c",
+            "#include <stdint.h>\n\
+             #include <types.h>\n\
+             #include \"config.h\"\n\
+             #include \"missing.h\"\n\
+             // a2l on\n\
+             switch_t state[SIZE][CHANNELS];\n",
+        );
+        let second = dir.write(
+            "second.c",
+            "#include \"types.h\"\n\
+             // a2l on\n\
+             uint8_t values[SIZE];\n",
+        );
+
+        let mut code_parser = CodeParser::new();
+        code_parser.add_include_path(dir.path.join("include").to_string_lossy().to_string());
+        code_parser.add_file_path(first.clone()).unwrap();
+        code_parser.add_file_path(second).unwrap();
+        let mut errors = Vec::new();
+        let mut diagnostics = Vec::new();
+        let found = code_parser.parse_files(&mut errors, &mut diagnostics);
+        assert!(errors.is_empty(), "{:?}", errors);
+        // declarations in included headers aren't collected
+        let names: Vec<&str> = found.iter().map(|decl| decl.name.as_str()).collect();
+        assert_eq!(names, vec!["state", "values"]);
+        assert_eq!(found[0].array_dims, vec![4, 2]);
+        assert_eq!(found[0].enumerators.len(), 2);
+        assert_eq!(found[1].array_dims, vec![4]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, SourceLocation::new(&first, 4, 10));
+        assert_eq!(diagnostics[0].length, "\"missing.h\"".len());
+        assert!(diagnostics[0].message.contains("header 'missing.h' not found"));
+
+        // <...> isn't searched next to the including file
+        std::fs::write(&first, "#include <config.h>\n").unwrap();
+        let mut diagnostics = Vec::new();
+        CodeParser::new()
+            .parse_file(&first, &mut SymbolTable::default(), &mut diagnostics)
+            .unwrap();
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_files_own_macros() {
+        let dir = TestDir::new("a2l_code_parser_test_files_own_macros");
+        dir.write("config.h", "#ifdef DEBUG_BUILD\n#define N 4\n#else\n#define N 2\n#endif\n");
+        let first = dir.write(
+            "a.c",
+            "#define DEBUG_BUILD 1\n\
+             #include \"config.h\"\n\
+             // a2l on\n\
+             const uint8_t arr1[N];\n",
+        );
+        let second = dir.write(
+            "b.c",
+            "#if DEBUG_BUILD\n\
+             // a2l on\n\
+             uint8_t debug_only;\n\
+             #endif\n\
+             // a2l on\n\
+             const uint8_t arr2[N];\n",
+        );
+        let third = dir.write(
+            "c.c",
+            "#include \"config.h\"\n\
+             // a2l on\n\
+             const uint8_t arr3[N];\n",
+        );
+
+        let mut code_parser = CodeParser::new();
+        for file in [first, second.clone(), third] {
+            code_parser.add_file_path(file).unwrap();
+        }
+        let mut errors = Vec::new();
+        let found = code_parser.parse_files(&mut errors, &mut Vec::new());
+        // the macros of a.c are unknown in b.c
+        let names: Vec<&str> = found.iter().map(|decl| decl.name.as_str()).collect();
+        assert_eq!(names, vec!["arr1", "arr3"]);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], GeneratorError::Declaration { location, .. } if location.file == second));
+        // the header read for a.c is evaluated again with the macros of c.c
+        assert_eq!(found[0].array_dims, vec![4]);
+        assert_eq!(found[1].array_dims, vec![2]);
+    }
+
+    #[test]
+    fn test_bitfield_members() {
+        let dir = TestDir::new("a2l_code_parser_test_bitfield_members");
+        let file = dir.write(
+            "bitfields.c",
+            "struct flags { uint8_t a : 1; uint8_t b : 7; uint16_t c; };\n\
+             // a2l on\n\
+             struct flags f;\n",
+        );
+        let found = parse(&CodeParser::new(), &file).unwrap();
+        let members = &found[0].struct_type.as_ref().unwrap().members;
+        let bitfields: Vec<_> = members.iter().map(|member| (member.name.as_str(), member.is_bitfield)).collect();
+        assert_eq!(bitfields, vec![("a", true), ("b", true), ("c", false)]);
+    }
+
+    #[test]
+    fn test_array_typedefs() {
+        let dir = TestDir::new("a2l_code_parser_test_array_typedefs");
+        let file = dir.write(
+            "arrays.c",
+            "#define SIZE 8\n\
+             typedef uint8_t buf_t[SIZE];\n\
+             typedef buf_t bufs_t[2];\n\
+             typedef struct { /* a2l on */ buf_t data; } msg_t;\n\
+             // a2l on\n\
+             buf_t buffer;\n\
+             // a2l on\n\
+             bufs_t buffers[3];\n\
+             // a2l on\n\
+             msg_t msg;\n",
+        );
+        let found = parse(&CodeParser::new(), &file).unwrap();
+        assert_eq!(found[0].base_type, "uint8_t");
+        assert_eq!(found[0].array_dims, vec![8]);
+        // the dimensions of the variable come first
+        assert_eq!(found[1].base_type, "uint8_t");
+        assert_eq!(found[1].array_dims, vec![3, 2, 8]);
+        let members = found[2].members();
+        assert_eq!(members[0].name, "msg.data");
+        assert_eq!(members[0].array_dims, vec![8]);
+    }
+
+    #[test]
+    fn test_typedef_chains() {
+        let dir = TestDir::new("a2l_code_parser_test_typedef_chains");
+        let file = dir.write(
+            "typedefs.c",
+            "typedef unsigned short int uint16;\n\
+             typedef uint16 Speed_t;\n\
+             typedef struct { /* a2l on */ Speed_t limit; } limits_t;\n\
+             // a2l on\n\
+             Speed_t speed;\n\
+             // a2l on\n\
+             limits_t limits;\n\
+             // a2l on\n\
+             uint8_t raw;\n\
+             typedef Speed_t *SpeedPtr_t;\n\
+             // a2l on\n\
+             SpeedPtr_t speed_ptr, *speeds[2];\n",
+        );
+        let found = parse(&CodeParser::new(), &file).unwrap();
+        assert_eq!(found[3].base_type, "unsigned short *");
+        assert_eq!(found[4].base_type, "unsigned short * *");
+        assert_eq!(found[4].array_dims, vec![2]);
+        assert_eq!(found[0].c_type, "Speed_t");
+        assert_eq!(found[0].base_type, "unsigned short");
+        assert_eq!(found[1].members()[0].base_type, "unsigned short");
+        assert_eq!(found[2].base_type, "uint8_t");
+    }
+
+    #[test]
+    fn test_struct_members() {
+        let dir = TestDir::new("a2l_code_parser_test_struct_members");
+        let file = dir.write(
+            "structs.c",
+            "struct sensor {\n\
+             \x20   // a2l on\n\
+             \x20   // a2l-unit degC\n\
+             \x20   float temp;\n\
+             \x20   int raw;\n\
+             };\n\
+             typedef struct {\n\
+             \x20   /* a2l on */\n\
+             \x20   uint16_t rpm, load[4];\n\
+             \x20   struct sensor oil;\n\
+             \x20   union { /* a2l on */ uint32_t word; uint8_t bytes[4]; } status;\n\
+             } engine_t;\n\
+             // a2l on\n\
+             engine_t engine;\n\
+             // a2l on\n\
+             struct sensor sensors[2];\n",
+        );
+        let error = parse(&CodeParser::new(), &file).unwrap_err();
+        assert!(error.to_string().contains("arrays of structures are not supported: sensors"));
+
+        std::fs::write(
+            &file,
+            std::fs::read_to_string(&file).unwrap().replace("sensors[2]", "sensor"),
+        )
+        .unwrap();
+        let instances = parse(&CodeParser::new(), &file).unwrap();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].c_type, "engine_t");
+        let found: Vec<_> = instances.iter().flat_map(|instance| instance.members()).collect();
+        let names: Vec<_> = found.iter().map(|declaration| declaration.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["engine.rpm", "engine.load", "engine.oil.temp", "engine.status.word", "sensor.temp"]
+        );
+        assert_eq!(found[0].c_type, "uint16_t");
+        assert_eq!(found[1].array_dims, vec![4]);
+        assert_eq!(found[2].c_type, "float");
+        assert_eq!(found[2].comment, "   a2l on\n       a2l-unit degC");
+        assert_eq!(found[2].comment_location, SourceLocation::new(&file, 2, 5));
+        assert_eq!(found[2].location, SourceLocation::new(&file, 4, 5));
+    }
+
+    #[test]
+    fn test_all_declarations() {
+        let dir = TestDir::new("a2l_code_parser_test_all_declarations");
+        let file = dir.write(
+            "plain.c",
+            "struct sensor { float temp; };\n\
+             struct sensor sensors[2];\n\
+             int counter;\n\
+             // a2l on\n\
+             float gain;\n\
+             struct sensor oil;\n",
+        );
+        assert_eq!(parse(&CodeParser::new(), &file).unwrap().len(), 1);
+        let mut code_parser = CodeParser::new();
+        code_parser.set_all_declarations(true);
+        // the unsupported array of structures is skipped, it has no annotation
+        let found = parse(&code_parser, &file).unwrap();
+        let names: Vec<_> = found.iter().map(|declaration| declaration.name.as_str()).collect();
+        assert_eq!(names, vec!["counter", "gain", "oil"]);
+        assert!(found[0].comment.is_empty());
+        assert_eq!(found[0].location, SourceLocation::new(&file, 3, 1));
+        assert_eq!(found[1].comment, "   a2l on");
+        assert_eq!(found[2].struct_type.as_ref().unwrap().members[0].name, "temp");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let code_parser = CodeParser::new();
+        let error = parse(&code_parser, "does_not_exist.c").unwrap_err();
+        assert!(matches!(error, GeneratorError::Read { .. }));
+
+        let dir = TestDir::new("a2l_code_parser_test_parse_errors");
+        let file = dir.write("broken.c", "int a = 1;\nint b = ;\n");
+        match parse(&code_parser, &file) {
+            Err(GeneratorError::Syntax { location, .. }) => assert_eq!(location.line, 2),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_add_directory() {
+        let dir = TestDir::new("a2l_code_parser_test_add_directory");
+        dir.write("a.c", "");
+        dir.write("a.h", "");
+        dir.write("sub/b.c", "");
+
+        let mut code_parser = CodeParser::new();
+        code_parser.add_file_path(dir.path.to_string_lossy().to_string()).unwrap();
+        assert_eq!(code_parser.files_paths.len(), 2);
+        assert!(code_parser.files_paths[0].ends_with("a.c"));
+        assert!(code_parser.files_paths[1].ends_with("b.c"));
+    }
+
+    #[test]
+    fn test_parse_c_integer() {
+        assert_eq!(parse_c_integer("10"), Some(10));
+        assert_eq!(parse_c_integer("0x1F"), Some(31));
+        assert_eq!(parse_c_integer("010"), Some(8));
+        assert_eq!(parse_c_integer("8u"), Some(8));
+        assert_eq!(parse_c_integer("SIZE"), None);
+    }
+
+    #[test]
+    fn test_strip_comment_markers() {
+        assert_eq!(strip_comment_markers("// a2l on"), "   a2l on");
+        assert_eq!(strip_comment_markers("/* a2l-unit mm */"), "   a2l-unit mm");
+        assert_eq!(
+            strip_comment_markers("/*\n * a2l on\n * a2l-unit m/s\n */"),
+            "\n   a2l on\n   a2l-unit m/s\n"
+        );
     }
 }
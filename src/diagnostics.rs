@@ -0,0 +1,81 @@
+use crate::error::SourceLocation;
+
+// a problem in the annotations which doesn't stop the generation, the default value is used instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub location: SourceLocation,
+    // number of characters of the offending token
+    pub length: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(location: SourceLocation, length: usize, message: String) -> Self {
+        Diagnostic {
+            location,
+            length,
+            message,
+        }
+    }
+
+    // render the diagnostic like rustc, with the source line and a caret under the offending token
+    pub fn render(&self, as_error: bool) -> String {
+        let source = std::fs::read_to_string(&self.location.file).unwrap_or_default();
+        self.render_with_source(&source, as_error)
+    }
+
+    fn render_with_source(&self, source: &str, as_error: bool) -> String {
+        let level = if as_error { "error" } else { "warning" };
+        let mut text = format!("{}: {}\n", level, self.message);
+        let line_number = self.location.line.to_string();
+        let padding = " ".repeat(line_number.len());
+        text.push_str(&format!("{}--> {}\n", padding, self.location));
+        if let Some(source_line) = source.lines().nth(self.location.line.saturating_sub(1)) {
+            // keep tabs so that the caret lines up with the token
+            let indent: String = source_line
+                .chars()
+                .take(self.location.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            text.push_str(&format!("{} |\n", padding));
+            text.push_str(&format!("{} | {}\n", line_number, source_line));
+            text.push_str(&format!(
+                "{} | {}{}\n",
+                padding,
+                indent,
+                "^".repeat(self.length.max(1))
+            ));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let source = "int a;\n// a2l-min invalid\nint b;\n";
+        let diagnostic = Diagnostic::new(
+            SourceLocation::new("test.c", 2, 12),
+            7,
+            "'invalid' is not a number".to_string(),
+        );
+        assert_eq!(
+            diagnostic.render_with_source(source, false),
+            "warning: 'invalid' is not a number\n \
+             --> test.c:2:12\n  \
+             |\n\
+             2 | // a2l-min invalid\n  \
+             |            ^^^^^^^\n"
+        );
+        assert!(diagnostic.render_with_source(source, true).starts_with("error: "));
+    }
+
+    #[test]
+    fn test_render_without_source() {
+        let diagnostic = Diagnostic::new(SourceLocation::new("", 1, 1), 1, "message".to_string());
+        assert_eq!(diagnostic.render_with_source("", false), "warning: message\n --> :1:1\n");
+    }
+}
@@ -0,0 +1,818 @@
+// reading the types of the variables from the DWARF debug info of the firmware, versions 2 to 5
+// the compiler knows the real layout, which can differ from the C source, e.g. with packing pragmas
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use crate::elf::ElfFile;
+
+const DW_TAG_ARRAY_TYPE: u64 = 0x01;
+const DW_TAG_ENUMERATION_TYPE: u64 = 0x04;
+const DW_TAG_MEMBER: u64 = 0x0d;
+const DW_TAG_POINTER_TYPE: u64 = 0x0f;
+const DW_TAG_REFERENCE_TYPE: u64 = 0x10;
+const DW_TAG_STRUCTURE_TYPE: u64 = 0x13;
+const DW_TAG_TYPEDEF: u64 = 0x16;
+const DW_TAG_UNION_TYPE: u64 = 0x17;
+const DW_TAG_SUBRANGE_TYPE: u64 = 0x21;
+const DW_TAG_BASE_TYPE: u64 = 0x24;
+const DW_TAG_CONST_TYPE: u64 = 0x26;
+const DW_TAG_VARIABLE: u64 = 0x34;
+const DW_TAG_VOLATILE_TYPE: u64 = 0x35;
+const DW_TAG_RESTRICT_TYPE: u64 = 0x37;
+const DW_TAG_ATOMIC_TYPE: u64 = 0x47;
+
+const DW_AT_NAME: u64 = 0x03;
+const DW_AT_BYTE_SIZE: u64 = 0x0b;
+const DW_AT_UPPER_BOUND: u64 = 0x2f;
+const DW_AT_COUNT: u64 = 0x37;
+const DW_AT_DATA_MEMBER_LOCATION: u64 = 0x38;
+const DW_AT_DECLARATION: u64 = 0x3c;
+const DW_AT_ENCODING: u64 = 0x3e;
+const DW_AT_SPECIFICATION: u64 = 0x47;
+const DW_AT_TYPE: u64 = 0x49;
+const DW_AT_DATA_BIT_OFFSET: u64 = 0x6b;
+const DW_AT_STR_OFFSETS_BASE: u64 = 0x72;
+
+const DW_ATE_BOOLEAN: u64 = 0x02;
+const DW_ATE_FLOAT: u64 = 0x04;
+const DW_ATE_SIGNED: u64 = 0x05;
+const DW_ATE_SIGNED_CHAR: u64 = 0x06;
+const DW_ATE_UNSIGNED: u64 = 0x07;
+const DW_ATE_UNSIGNED_CHAR: u64 = 0x08;
+const DW_ATE_UTF: u64 = 0x10;
+
+const DW_UT_TYPE: u64 = 0x02;
+const DW_UT_SKELETON: u64 = 0x04;
+const DW_UT_SPLIT_COMPILE: u64 = 0x05;
+const DW_UT_SPLIT_TYPE: u64 = 0x06;
+
+const DW_OP_PLUS_UCONST: u8 = 0x23;
+
+// typedefs are resolved through at most this many levels, deeper chains are broken debug info
+const MAX_TYPE_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Signed,
+    Unsigned,
+    Float,
+    Other,
+}
+
+// a type of the debug info with typedefs and qualifiers resolved
+#[derive(Debug, Clone, PartialEq)]
+pub enum DwarfType {
+    Base { size: usize, encoding: Encoding },
+    Pointer { size: usize },
+    Enum { size: usize },
+    // nested arrays are one array with several dimensions
+    Array { element: Box<DwarfType>, dims: Vec<usize> },
+    // structs and unions
+    Struct { size: usize, members: Vec<DwarfMember> },
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwarfMember {
+    pub name: String,
+    // offset in bytes from the start of the struct
+    pub offset: usize,
+    pub member_type: DwarfType,
+}
+
+impl DwarfType {
+    pub fn member(&self, name: &str) -> Option<&DwarfMember> {
+        match self {
+            DwarfType::Struct { members, .. } => members.iter().find(|member| member.name == name),
+            _ => None,
+        }
+    }
+}
+
+// the sections of the debug info, missing sections are empty
+#[derive(Default)]
+struct Sections<'a> {
+    info: &'a [u8],
+    abbrev: &'a [u8],
+    strings: &'a [u8],
+    line_strings: &'a [u8],
+    string_offsets: &'a [u8],
+}
+
+// header values of a unit which are needed to read its entries
+struct Unit {
+    start: usize,
+    version: u64,
+    offset_size: usize,
+    address_size: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Unsigned(u64),
+    Signed(i64),
+    String(String),
+    // offsets into .debug_str and .debug_line_str, an index into .debug_str_offsets
+    StringOffset(u64),
+    LineStringOffset(u64),
+    StringIndex(u64),
+    // offset of an entry in .debug_info
+    Reference(u64),
+    Block(Vec<u8>),
+    // values which aren't needed, like addresses and location lists
+    Other,
+}
+
+struct Abbreviation {
+    tag: u64,
+    has_children: bool,
+    // attribute, form and the value of an implicit constant
+    attributes: Vec<(u64, u64, i64)>,
+}
+
+// a debugging information entry
+struct Die {
+    tag: u64,
+    name: Option<String>,
+    attributes: Vec<(u64, Value)>,
+    children: Vec<u64>,
+    address_size: usize,
+}
+
+impl Die {
+    fn attribute(&self, attribute: u64) -> Option<&Value> {
+        self.attributes
+            .iter()
+            .find(|(name, _)| *name == attribute)
+            .map(|(_, value)| value)
+    }
+
+    fn unsigned(&self, attribute: u64) -> Option<u64> {
+        match self.attribute(attribute)? {
+            Value::Unsigned(value) => Some(*value),
+            Value::Signed(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    fn reference(&self, attribute: u64) -> Option<u64> {
+        match self.attribute(attribute)? {
+            Value::Reference(offset) => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+pub struct DebugInfo {
+    variables: HashMap<String, DwarfType>,
+}
+
+impl DebugInfo {
+    pub fn load(elf: &ElfFile) -> Result<Self, String> {
+        let section = |name: &str| elf.section_data(name).unwrap_or_default();
+        if section(".debug_info").is_empty() {
+            return Err("no debug info, the firmware must be compiled with -g".to_string());
+        }
+        let sections = Sections {
+            info: section(".debug_info"),
+            abbrev: section(".debug_abbrev"),
+            strings: section(".debug_str"),
+            line_strings: section(".debug_line_str"),
+            string_offsets: section(".debug_str_offsets"),
+        };
+        DebugInfo::parse(&sections, elf.is_big_endian())
+    }
+
+    fn parse(sections: &Sections, big_endian: bool) -> Result<Self, String> {
+        let mut dies = HashMap::new();
+        let mut top_level = Vec::new();
+        let mut abbreviations = HashMap::new();
+        let mut position = 0;
+        while position < sections.info.len() {
+            position = read_unit(sections, big_endian, position, &mut abbreviations, &mut dies, &mut top_level)?;
+        }
+
+        // a definition wins over a declaration, its name and type may be given by the declaration
+        let mut declared: HashMap<String, (bool, Option<u64>)> = HashMap::new();
+        for offset in top_level {
+            let die = &dies[&offset];
+            if die.tag != DW_TAG_VARIABLE {
+                continue;
+            }
+            let specification = die.reference(DW_AT_SPECIFICATION).and_then(|offset| dies.get(&offset));
+            let Some(name) = die.name.as_ref().or(specification.and_then(|die| die.name.as_ref())) else {
+                continue;
+            };
+            let type_offset = die
+                .reference(DW_AT_TYPE)
+                .or(specification.and_then(|die| die.reference(DW_AT_TYPE)));
+            let is_declaration = die.attribute(DW_AT_DECLARATION).is_some();
+            if declared.get(name).is_none_or(|(was_declaration, _)| *was_declaration) {
+                declared.insert(name.clone(), (is_declaration, type_offset));
+            }
+        }
+        let variables = declared
+            .into_iter()
+            .map(|(name, (_, type_offset))| (name, resolve_type(&dies, type_offset, 0)))
+            .collect();
+        Ok(DebugInfo { variables })
+    }
+
+    // type of a variable, None if the debug info doesn't describe it
+    pub fn variable_type(&self, name: &str) -> Option<&DwarfType> {
+        self.variables.get(name)
+    }
+}
+
+// read the entries of the unit at start, returns the start of the next unit
+fn read_unit(
+    sections: &Sections,
+    big_endian: bool,
+    start: usize,
+    abbreviations: &mut HashMap<u64, HashMap<u64, Abbreviation>>,
+    dies: &mut HashMap<u64, Die>,
+    top_level: &mut Vec<u64>,
+) -> Result<usize, String> {
+    let mut reader = Reader::new(sections.info, start, big_endian);
+    let (unit_length, offset_size) = match reader.unsigned(4)? {
+        0xffff_ffff => (reader.unsigned(8)?, 8),
+        unit_length => (unit_length, 4),
+    };
+    let end = usize::try_from(unit_length)
+        .ok()
+        .and_then(|length| reader.position.checked_add(length))
+        .filter(|end| *end <= sections.info.len())
+        .ok_or_else(|| format!("invalid length of the unit at offset {:#x}", start))?;
+    let version = reader.unsigned(2)?;
+    let (abbrev_offset, address_size) = match version {
+        2..=4 => (reader.unsigned(offset_size)?, reader.unsigned(1)?),
+        5 => {
+            let unit_type = reader.unsigned(1)?;
+            let address_size = reader.unsigned(1)?;
+            let abbrev_offset = reader.unsigned(offset_size)?;
+            match unit_type {
+                // type units describe types for other units, they have no variables
+                DW_UT_TYPE | DW_UT_SPLIT_TYPE => return Ok(end),
+                DW_UT_SKELETON | DW_UT_SPLIT_COMPILE => {
+                    reader.bytes(8)?;
+                }
+                _ => {}
+            }
+            (abbrev_offset, address_size)
+        }
+        _ => return Err(format!("unsupported DWARF version {}", version)),
+    };
+    let unit = Unit {
+        start,
+        version,
+        offset_size,
+        address_size: address_size as usize,
+    };
+    let abbreviations = match abbreviations.entry(abbrev_offset) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(read_abbreviations(sections.abbrev, abbrev_offset, big_endian)?),
+    };
+
+    let mut unit_dies = Vec::new();
+    let mut parents: Vec<u64> = Vec::new();
+    while reader.position < end {
+        let offset = reader.position as u64;
+        let code = reader.uleb()?;
+        // the end of the children of the parent
+        if code == 0 {
+            parents.pop();
+            continue;
+        }
+        let abbreviation = abbreviations
+            .get(&code)
+            .ok_or_else(|| format!("unknown abbreviation {} at offset {:#x}", code, offset))?;
+        let mut attributes = Vec::new();
+        for (attribute, form, implicit_const) in &abbreviation.attributes {
+            attributes.push((*attribute, read_value(&mut reader, &unit, *form, *implicit_const)?));
+        }
+        if let Some(parent) = parents.last() {
+            if let Some(parent) = dies.get_mut(parent) {
+                parent.children.push(offset);
+            }
+            // the entries below the unit entry
+            if parents.len() == 1 {
+                top_level.push(offset);
+            }
+        }
+        if abbreviation.has_children {
+            parents.push(offset);
+        }
+        dies.insert(
+            offset,
+            Die {
+                tag: abbreviation.tag,
+                name: None,
+                attributes,
+                children: Vec::new(),
+                address_size: unit.address_size,
+            },
+        );
+        unit_dies.push(offset);
+    }
+
+    // names are read once the unit is complete, string indexes need the base given by the unit entry
+    let string_offsets_base = unit_dies
+        .first()
+        .and_then(|offset| dies[offset].unsigned(DW_AT_STR_OFFSETS_BASE))
+        .unwrap_or(8);
+    for offset in unit_dies {
+        let die = dies.get_mut(&offset).unwrap();
+        die.name = match die.attribute(DW_AT_NAME) {
+            Some(Value::String(name)) => Some(name.clone()),
+            Some(Value::StringOffset(offset)) => read_string(sections.strings, *offset),
+            Some(Value::LineStringOffset(offset)) => read_string(sections.line_strings, *offset),
+            Some(Value::StringIndex(index)) => {
+                let entry = string_offsets_base + index * unit.offset_size as u64;
+                Reader::new(sections.string_offsets, entry as usize, big_endian)
+                    .unsigned(unit.offset_size)
+                    .ok()
+                    .and_then(|offset| read_string(sections.strings, offset))
+            }
+            _ => None,
+        };
+    }
+    Ok(end)
+}
+
+fn read_abbreviations(data: &[u8], offset: u64, big_endian: bool) -> Result<HashMap<u64, Abbreviation>, String> {
+    let mut reader = Reader::new(data, offset as usize, big_endian);
+    let mut abbreviations = HashMap::new();
+    loop {
+        let code = reader.uleb()?;
+        if code == 0 {
+            return Ok(abbreviations);
+        }
+        let tag = reader.uleb()?;
+        let has_children = reader.unsigned(1)? != 0;
+        let mut attributes = Vec::new();
+        loop {
+            let (attribute, form) = (reader.uleb()?, reader.uleb()?);
+            if attribute == 0 && form == 0 {
+                break;
+            }
+            // DW_FORM_implicit_const keeps its value in the abbreviation
+            let implicit_const = if form == 0x21 { reader.sleb()? } else { 0 };
+            attributes.push((attribute, form, implicit_const));
+        }
+        abbreviations.insert(code, Abbreviation { tag, has_children, attributes });
+    }
+}
+
+fn read_value(reader: &mut Reader, unit: &Unit, form: u64, implicit_const: i64) -> Result<Value, String> {
+    let unit_start = unit.start as u64;
+    Ok(match form {
+        // addr
+        0x01 => Value::Unsigned(reader.unsigned(unit.address_size)?),
+        // block2, block4, block, block1 and exprloc
+        0x03 => {
+            let length = reader.unsigned(2)?;
+            Value::Block(reader.bytes(length as usize)?.to_vec())
+        }
+        0x04 => {
+            let length = reader.unsigned(4)?;
+            Value::Block(reader.bytes(length as usize)?.to_vec())
+        }
+        0x09 | 0x18 => {
+            let length = reader.uleb()?;
+            Value::Block(reader.bytes(length as usize)?.to_vec())
+        }
+        0x0a => {
+            let length = reader.unsigned(1)?;
+            Value::Block(reader.bytes(length as usize)?.to_vec())
+        }
+        // data2, data4, data8, data1, flag, udata and sec_offset
+        0x05 => Value::Unsigned(reader.unsigned(2)?),
+        0x06 => Value::Unsigned(reader.unsigned(4)?),
+        0x07 => Value::Unsigned(reader.unsigned(8)?),
+        0x0b | 0x0c => Value::Unsigned(reader.unsigned(1)?),
+        0x0f => Value::Unsigned(reader.uleb()?),
+        0x17 => Value::Unsigned(reader.unsigned(unit.offset_size)?),
+        // flag_present
+        0x19 => Value::Unsigned(1),
+        // sdata and implicit_const
+        0x0d => Value::Signed(reader.sleb()?),
+        0x21 => Value::Signed(implicit_const),
+        // string, strp, line_strp, strx and strx1 to strx4
+        0x08 => Value::String(reader.string()?),
+        0x0e => Value::StringOffset(reader.unsigned(unit.offset_size)?),
+        0x1f => Value::LineStringOffset(reader.unsigned(unit.offset_size)?),
+        0x1a => Value::StringIndex(reader.uleb()?),
+        0x25..=0x28 => Value::StringIndex(reader.unsigned(form as usize - 0x24)?),
+        // ref_addr, the size is the address size in DWARF 2
+        0x10 => {
+            let size = if unit.version == 2 { unit.address_size } else { unit.offset_size };
+            Value::Reference(reader.unsigned(size)?)
+        }
+        // ref1, ref2, ref4, ref8 and ref_udata are relative to the unit
+        0x11 => Value::Reference(unit_start + reader.unsigned(1)?),
+        0x12 => Value::Reference(unit_start + reader.unsigned(2)?),
+        0x13 => Value::Reference(unit_start + reader.unsigned(4)?),
+        0x14 => Value::Reference(unit_start + reader.unsigned(8)?),
+        0x15 => Value::Reference(unit_start + reader.uleb()?),
+        // indirect, the form is given in the entry
+        0x16 => {
+            let form = reader.uleb()?;
+            return read_value(reader, unit, form, implicit_const);
+        }
+        // addrx, loclistx and rnglistx
+        0x1b | 0x22 | 0x23 => {
+            reader.uleb()?;
+            Value::Other
+        }
+        // addrx1 to addrx4
+        0x29..=0x2c => {
+            reader.unsigned(form as usize - 0x28)?;
+            Value::Other
+        }
+        // ref_sup4, strp_sup, ref_sig8, ref_sup8 and data16
+        0x1c => {
+            reader.bytes(4)?;
+            Value::Other
+        }
+        0x1d => {
+            reader.bytes(unit.offset_size)?;
+            Value::Other
+        }
+        0x20 | 0x24 => {
+            reader.bytes(8)?;
+            Value::Other
+        }
+        0x1e => {
+            reader.bytes(16)?;
+            Value::Other
+        }
+        _ => return Err(format!("unsupported attribute form {:#x}", form)),
+    })
+}
+
+fn resolve_type(dies: &HashMap<u64, Die>, offset: Option<u64>, depth: usize) -> DwarfType {
+    let Some(die) = offset.and_then(|offset| dies.get(&offset)) else {
+        return DwarfType::Unknown;
+    };
+    if depth > MAX_TYPE_DEPTH {
+        return DwarfType::Unknown;
+    }
+    let size = die.unsigned(DW_AT_BYTE_SIZE).unwrap_or(0) as usize;
+    let inner_type = || resolve_type(dies, die.reference(DW_AT_TYPE), depth + 1);
+    let children = |tag: u64| {
+        die.children
+            .iter()
+            .filter_map(|offset| dies.get(offset))
+            .filter(move |child| child.tag == tag)
+    };
+    match die.tag {
+        DW_TAG_TYPEDEF | DW_TAG_CONST_TYPE | DW_TAG_VOLATILE_TYPE | DW_TAG_RESTRICT_TYPE | DW_TAG_ATOMIC_TYPE => {
+            inner_type()
+        }
+        DW_TAG_BASE_TYPE => {
+            let encoding = match die.unsigned(DW_AT_ENCODING) {
+                Some(DW_ATE_SIGNED | DW_ATE_SIGNED_CHAR) => Encoding::Signed,
+                Some(DW_ATE_UNSIGNED | DW_ATE_UNSIGNED_CHAR | DW_ATE_BOOLEAN | DW_ATE_UTF) => Encoding::Unsigned,
+                Some(DW_ATE_FLOAT) => Encoding::Float,
+                _ => Encoding::Other,
+            };
+            DwarfType::Base { size, encoding }
+        }
+        // pointers without a size have the size of an address
+        DW_TAG_POINTER_TYPE | DW_TAG_REFERENCE_TYPE => DwarfType::Pointer {
+            size: if size == 0 { die.address_size } else { size },
+        },
+        DW_TAG_ENUMERATION_TYPE => DwarfType::Enum { size },
+        DW_TAG_ARRAY_TYPE => {
+            // the size of an array without bounds is unknown, e.g. "x[]"
+            let mut dims: Vec<usize> = children(DW_TAG_SUBRANGE_TYPE)
+                .map(|subrange| {
+                    subrange
+                        .unsigned(DW_AT_COUNT)
+                        .or(subrange.unsigned(DW_AT_UPPER_BOUND).map(|bound| bound + 1))
+                        .unwrap_or(0) as usize
+                })
+                .collect();
+            match inner_type() {
+                // an array of a typedef of an array
+                DwarfType::Array { element, dims: inner_dims } => {
+                    dims.extend(inner_dims);
+                    DwarfType::Array { element, dims }
+                }
+                element => DwarfType::Array {
+                    element: Box::new(element),
+                    dims,
+                },
+            }
+        }
+        DW_TAG_STRUCTURE_TYPE | DW_TAG_UNION_TYPE => DwarfType::Struct {
+            size,
+            members: children(DW_TAG_MEMBER)
+                .map(|member| DwarfMember {
+                    name: member.name.clone().unwrap_or_default(),
+                    offset: member_offset(member),
+                    member_type: resolve_type(dies, member.reference(DW_AT_TYPE), depth + 1),
+                })
+                .collect(),
+        },
+        _ => DwarfType::Unknown,
+    }
+}
+
+// offset of a member in bytes, members of unions have none
+fn member_offset(member: &Die) -> usize {
+    let offset = match member.attribute(DW_AT_DATA_MEMBER_LOCATION) {
+        Some(Value::Unsigned(offset)) => *offset,
+        // before DWARF 4 the offset is an expression "DW_OP_plus_uconst offset"
+        Some(Value::Block(expression)) if expression.first() == Some(&DW_OP_PLUS_UCONST) => {
+            Reader::new(expression, 1, false).uleb().unwrap_or(0)
+        }
+        // the offset of a bitfield is given in bits
+        _ => member.unsigned(DW_AT_DATA_BIT_OFFSET).unwrap_or(0) / 8,
+    };
+    offset as usize
+}
+
+fn read_string(data: &[u8], offset: u64) -> Option<String> {
+    Reader::new(data, usize::try_from(offset).ok()?, false).string().ok()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], position: usize, big_endian: bool) -> Self {
+        Reader {
+            data,
+            position,
+            big_endian,
+        }
+    }
+
+    fn bytes(&mut self, size: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .position
+            .checked_add(size)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| format!("unexpected end of the debug info at offset {:#x}", self.position))?;
+        self.position += size;
+        Ok(bytes)
+    }
+
+    // an unsigned integer of up to 8 bytes in the byte order of the file
+    fn unsigned(&mut self, size: usize) -> Result<u64, String> {
+        let bytes = self.bytes(size)?;
+        let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+        Ok(if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        })
+    }
+
+    fn uleb(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.bytes(1)?[0];
+            if shift < 64 {
+                value |= ((byte & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> Result<i64, String> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.bytes(1)?[0];
+            if shift < 64 {
+                value |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    // a zero terminated string
+    fn string(&mut self) -> Result<String, String> {
+        let text = self.data.get(self.position..).unwrap_or_default();
+        let end = text
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or("unterminated string in the debug info")?;
+        self.position += end + 1;
+        Ok(String::from_utf8_lossy(&text[..end]).to_string())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // debug info with the given variables, for the tests of the users of the debug info
+    pub fn build_debug_info(variables: Vec<(&str, DwarfType)>) -> DebugInfo {
+        DebugInfo {
+            variables: variables
+                .into_iter()
+                .map(|(name, variable_type)| (name.to_string(), variable_type))
+                .collect(),
+        }
+    }
+
+    // a field of a test entry, references name the label of another entry
+    enum Field {
+        Text(&'static str),
+        Byte(u8),
+        Ref(&'static str),
+        Block(Vec<u8>),
+    }
+    use Field::*;
+
+    // code, tag, children and (attribute, form) pairs
+    type TestAbbreviation = (u8, u8, bool, &'static [(u8, u8)]);
+
+    const ABBREVIATIONS: &[TestAbbreviation] = &[
+        (1, 0x11, true, &[(0x03, 0x08)]),
+        (2, 0x24, false, &[(0x03, 0x08), (0x0b, 0x0b), (0x3e, 0x0b)]),
+        (3, 0x16, false, &[(0x03, 0x08), (0x49, 0x13)]),
+        (4, 0x13, true, &[(0x03, 0x08), (0x0b, 0x0b)]),
+        (5, 0x0d, false, &[(0x03, 0x08), (0x49, 0x13), (0x38, 0x0b)]),
+        (6, 0x01, true, &[(0x49, 0x13)]),
+        (7, 0x21, false, &[(0x2f, 0x0b)]),
+        (8, 0x34, false, &[(0x03, 0x08), (0x49, 0x13)]),
+        (9, 0x0f, false, &[(0x0b, 0x0b)]),
+        (10, 0x0d, false, &[(0x03, 0x08), (0x49, 0x13), (0x38, 0x0a)]),
+        (11, 0x26, false, &[(0x49, 0x13)]),
+        (12, 0x34, false, &[(0x03, 0x08), (0x49, 0x13), (0x3c, 0x19)]),
+    ];
+
+    fn abbreviations() -> Vec<u8> {
+        let mut data = Vec::new();
+        for (code, tag, has_children, attributes) in ABBREVIATIONS {
+            data.extend([*code, *tag, *has_children as u8]);
+            for (attribute, form) in *attributes {
+                data.extend([*attribute, *form]);
+            }
+            data.extend([0, 0]);
+        }
+        data.push(0);
+        data
+    }
+
+    // a unit of .debug_info of DWARF 4 or 5, entries are (label, abbreviation code, fields), code 0 ends children
+    fn unit(version: u16, entries: &[(&str, u8, Vec<Field>)]) -> Vec<u8> {
+        let header_size = if version == 5 { 12 } else { 11 };
+        let field_size = |field: &Field| match field {
+            Text(text) => text.len() + 1,
+            Byte(_) => 1,
+            Ref(_) => 4,
+            Block(bytes) => bytes.len() + 1,
+        };
+        let mut labels = HashMap::new();
+        let mut offset = header_size;
+        for (label, code, fields) in entries {
+            labels.insert(*label, offset as u32);
+            offset += 1 + if *code == 0 { 0 } else { fields.iter().map(field_size).sum() };
+        }
+        let mut data = Vec::new();
+        data.extend((offset as u32 - 4).to_le_bytes());
+        data.extend(version.to_le_bytes());
+        if version == 5 {
+            data.extend([1, 4, 0, 0, 0, 0]);
+        } else {
+            data.extend([0, 0, 0, 0, 4]);
+        }
+        for (_, code, fields) in entries {
+            data.push(*code);
+            for field in fields {
+                match field {
+                    Text(text) => {
+                        data.extend(text.as_bytes());
+                        data.push(0);
+                    }
+                    Byte(byte) => data.push(*byte),
+                    Ref(label) => data.extend(labels[label].to_le_bytes()),
+                    Block(bytes) => {
+                        data.push(bytes.len() as u8);
+                        data.extend(bytes);
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    // typedef unsigned short uint16;
+    // #pragma pack(1)
+    // struct engine { unsigned char state; uint16 rpm; float temp[2]; struct { char *name; } info; };
+    // extern const struct engine engine;
+    // struct engine engine;
+    // int map[2][3];
+    fn debug_info(version: u16) -> Vec<u8> {
+        unit(
+            version,
+            &[
+                ("unit", 1, vec![Text("engine.c")]),
+                ("uchar", 2, vec![Text("unsigned char"), Byte(1), Byte(0x08)]),
+                ("ushort", 2, vec![Text("short unsigned int"), Byte(2), Byte(0x07)]),
+                ("float", 2, vec![Text("float"), Byte(4), Byte(0x04)]),
+                ("int", 2, vec![Text("int"), Byte(4), Byte(0x05)]),
+                ("uint16", 3, vec![Text("uint16"), Ref("ushort")]),
+                ("engine_t", 4, vec![Text("engine"), Byte(15)]),
+                ("state", 5, vec![Text("state"), Ref("uchar"), Byte(0)]),
+                ("rpm", 5, vec![Text("rpm"), Ref("uint16"), Byte(1)]),
+                // the location expression of DWARF 2 and 3
+                ("temp", 10, vec![Text("temp"), Ref("float_2"), Block(vec![DW_OP_PLUS_UCONST, 3])]),
+                ("info", 5, vec![Text("info"), Ref("info_t"), Byte(11)]),
+                ("", 0, vec![]),
+                ("float_2", 6, vec![Ref("float")]),
+                ("float_2_dim", 7, vec![Byte(1)]),
+                ("", 0, vec![]),
+                ("info_t", 4, vec![Text(""), Byte(4)]),
+                ("name", 5, vec![Text("name"), Ref("pointer"), Byte(0)]),
+                ("", 0, vec![]),
+                ("pointer", 9, vec![Byte(4)]),
+                ("const_engine", 11, vec![Ref("engine_t")]),
+                ("engine_declaration", 12, vec![Text("engine"), Ref("const_engine")]),
+                ("engine", 8, vec![Text("engine"), Ref("engine_t")]),
+                ("map", 8, vec![Text("map"), Ref("int_2_3")]),
+                ("int_2_3", 6, vec![Ref("int")]),
+                ("int_2_3_row", 7, vec![Byte(1)]),
+                ("int_2_3_column", 7, vec![Byte(2)]),
+                ("", 0, vec![]),
+                ("", 0, vec![]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_read_types() {
+        let abbrev = abbreviations();
+        for version in [4, 5] {
+            let info = debug_info(version);
+            let sections = Sections {
+                info: &info,
+                abbrev: &abbrev,
+                ..Default::default()
+            };
+            let debug_info = DebugInfo::parse(&sections, false).unwrap();
+            let engine = debug_info.variable_type("engine").unwrap();
+            let rpm = engine.member("rpm").unwrap();
+            assert_eq!(rpm.offset, 1);
+            assert_eq!(rpm.member_type, DwarfType::Base { size: 2, encoding: Encoding::Unsigned });
+            let temp = engine.member("temp").unwrap();
+            assert_eq!(temp.offset, 3);
+            assert_eq!(
+                temp.member_type,
+                DwarfType::Array {
+                    element: Box::new(DwarfType::Base { size: 4, encoding: Encoding::Float }),
+                    dims: vec![2]
+                }
+            );
+            let info = engine.member("info").unwrap();
+            assert_eq!(info.member_type.member("name").unwrap().member_type, DwarfType::Pointer { size: 4 });
+            assert!(matches!(engine, DwarfType::Struct { size: 15, .. }));
+            match debug_info.variable_type("map").unwrap() {
+                DwarfType::Array { dims, .. } => assert_eq!(dims, &vec![2, 3]),
+                other => panic!("unexpected type {:?}", other),
+            }
+            assert!(debug_info.variable_type("speed").is_none());
+        }
+    }
+
+    #[test]
+    fn test_invalid_debug_info() {
+        let abbrev = abbreviations();
+        let mut info = debug_info(4);
+        info.truncate(40);
+        let sections = Sections {
+            info: &info,
+            abbrev: &abbrev,
+            ..Default::default()
+        };
+        assert!(DebugInfo::parse(&sections, false).is_err());
+        let info = unit(3, &[]);
+        let mut version_6 = info.clone();
+        version_6[4] = 6;
+        let sections = Sections {
+            info: &version_6,
+            abbrev: &abbrev,
+            ..Default::default()
+        };
+        assert_eq!(DebugInfo::parse(&sections, false).err().unwrap(), "unsupported DWARF version 6");
+    }
+}
@@ -0,0 +1,319 @@
+// reading the symbol table of the linked firmware, 32 and 64 bit ELF files of either byte order
+
+use std::collections::HashMap;
+
+use crate::error::GeneratorError;
+
+const SHT_SYMTAB: u64 = 2;
+const STT_OBJECT: u8 = 1;
+const STB_LOCAL: u8 = 0;
+const SHN_UNDEF: u64 = 0;
+
+// address and size of a variable in the linked firmware
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkerSymbol {
+    pub address: u64,
+    pub size: u64,
+}
+
+#[derive(Debug)]
+struct Section {
+    name: String,
+    section_type: u64,
+    offset: u64,
+    size: u64,
+    link: u64,
+}
+
+#[derive(Debug)]
+pub struct ElfFile {
+    data: Vec<u8>,
+    is_64bit: bool,
+    big_endian: bool,
+    sections: Vec<Section>,
+    symbols: HashMap<String, LinkerSymbol>,
+}
+
+impl ElfFile {
+    pub fn load(file_path: &str) -> Result<Self, GeneratorError> {
+        let read_error = |message: String| GeneratorError::Read {
+            file: file_path.to_string(),
+            message,
+        };
+        let data = std::fs::read(file_path).map_err(|error| read_error(error.to_string()))?;
+        ElfFile::parse(data).map_err(read_error)
+    }
+
+    pub fn parse(data: Vec<u8>) -> Result<Self, String> {
+        if data.len() < 16 || &data[..4] != b"\x7fELF" {
+            return Err("not an ELF file".to_string());
+        }
+        let is_64bit = match data[4] {
+            1 => false,
+            2 => true,
+            class => return Err(format!("unsupported ELF class {}", class)),
+        };
+        let big_endian = match data[5] {
+            1 => false,
+            2 => true,
+            encoding => return Err(format!("unsupported ELF data encoding {}", encoding)),
+        };
+        let mut elf = ElfFile {
+            data,
+            is_64bit,
+            big_endian,
+            sections: Vec::new(),
+            symbols: HashMap::new(),
+        };
+        elf.read_sections()?;
+        elf.read_symbols()?;
+        Ok(elf)
+    }
+
+    // variables with their address, a global symbol wins over a static one of the same name
+    pub fn symbols(&self) -> &HashMap<String, LinkerSymbol> {
+        &self.symbols
+    }
+
+    pub fn is_big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    // contents of the section with the name, e.g. ".debug_info"
+    pub fn section_data(&self, name: &str) -> Option<&[u8]> {
+        let section = self.sections.iter().find(|section| section.name == name)?;
+        let start = usize::try_from(section.offset).ok()?;
+        self.data.get(start..start.checked_add(usize::try_from(section.size).ok()?)?)
+    }
+
+    fn read_sections(&mut self) -> Result<(), String> {
+        let (header_offset, entry_size, count, names_index) = if self.is_64bit {
+            (self.read(0x28, 8)?, self.read(0x3a, 2)?, self.read(0x3c, 2)?, self.read(0x3e, 2)?)
+        } else {
+            (self.read(0x20, 4)?, self.read(0x2e, 2)?, self.read(0x30, 2)?, self.read(0x32, 2)?)
+        };
+        let mut name_offsets = Vec::new();
+        for index in 0..count {
+            let header = self.entry_offset(header_offset, index, entry_size)?;
+            let section = if self.is_64bit {
+                Section {
+                    name: String::new(),
+                    section_type: self.read(header + 4, 4)?,
+                    offset: self.read(header + 24, 8)?,
+                    size: self.read(header + 32, 8)?,
+                    link: self.read(header + 40, 4)?,
+                }
+            } else {
+                Section {
+                    name: String::new(),
+                    section_type: self.read(header + 4, 4)?,
+                    offset: self.read(header + 16, 4)?,
+                    size: self.read(header + 20, 4)?,
+                    link: self.read(header + 24, 4)?,
+                }
+            };
+            name_offsets.push(self.read(header, 4)?);
+            self.sections.push(section);
+        }
+        if let Some(names) = self.sections.get(names_index as usize) {
+            let names_offset = names.offset;
+            for (section, name_offset) in self.sections.iter_mut().zip(name_offsets) {
+                let offset = names_offset.checked_add(name_offset).ok_or("invalid section name offset")?;
+                section.name = read_string(&self.data, offset)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_symbols(&mut self) -> Result<(), String> {
+        let Some(symtab) = self.sections.iter().find(|section| section.section_type == SHT_SYMTAB) else {
+            return Err("no symbol table, the file is stripped".to_string());
+        };
+        let strings = self
+            .sections
+            .get(symtab.link as usize)
+            .ok_or("invalid string table of the symbol table")?
+            .offset;
+        let entry_size = if self.is_64bit { 24 } else { 16 };
+        let mut globals = HashMap::new();
+        for index in 1..symtab.size / entry_size {
+            let entry = self.entry_offset(symtab.offset, index, entry_size)?;
+            let (info, section_index, address, size) = if self.is_64bit {
+                (self.read(entry + 4, 1)?, self.read(entry + 6, 2)?, self.read(entry + 8, 8)?, self.read(entry + 16, 8)?)
+            } else {
+                (self.read(entry + 12, 1)?, self.read(entry + 14, 2)?, self.read(entry + 4, 4)?, self.read(entry + 8, 4)?)
+            };
+            let (binding, symbol_type) = ((info >> 4) as u8, (info & 0xf) as u8);
+            if symbol_type != STT_OBJECT || section_index == SHN_UNDEF {
+                continue;
+            }
+            let name_offset = strings.checked_add(self.read(entry, 4)?).ok_or("invalid symbol name offset")?;
+            let name = read_string(&self.data, name_offset)?;
+            let is_global = binding != STB_LOCAL;
+            if globals.get(&name) != Some(&true) {
+                globals.insert(name.clone(), is_global);
+                self.symbols.insert(name, LinkerSymbol { address, size });
+            }
+        }
+        Ok(())
+    }
+
+    // offset of an entry of a table, it must be inside the file, so that the offsets of its fields don't overflow
+    fn entry_offset(&self, table_offset: u64, index: u64, entry_size: u64) -> Result<u64, String> {
+        index
+            .checked_mul(entry_size)
+            .and_then(|offset| offset.checked_add(table_offset))
+            .filter(|offset| *offset < self.data.len() as u64)
+            .ok_or_else(|| format!("unexpected end of file in the table at offset {:#x}", table_offset))
+    }
+
+    // read an unsigned integer of 1, 2, 4 or 8 bytes in the byte order of the file
+    fn read(&self, offset: u64, size: usize) -> Result<u64, String> {
+        let bytes = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.data.get(offset..offset.checked_add(size)?))
+            .ok_or_else(|| format!("unexpected end of file at offset {:#x}", offset))?;
+        let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+        Ok(if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        })
+    }
+}
+
+// read a zero terminated string
+fn read_string(data: &[u8], offset: u64) -> Result<String, String> {
+    let start = usize::try_from(offset).map_err(|_| "invalid string offset".to_string())?;
+    let text = data.get(start..).ok_or("invalid string offset")?;
+    let end = text.iter().position(|byte| *byte == 0).ok_or("unterminated string")?;
+    Ok(String::from_utf8_lossy(&text[..end]).to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // build an ELF file with a symbol table, symbols are (name, address, size, info)
+    pub fn build_elf(is_64bit: bool, big_endian: bool, symbols: &[(&str, u64, u64, u8)]) -> Vec<u8> {
+        let write = |data: &mut Vec<u8>, value: u64, size: usize| {
+            let bytes = value.to_le_bytes();
+            if big_endian {
+                data.extend(bytes[..size].iter().rev());
+            } else {
+                data.extend(&bytes[..size]);
+            }
+        };
+        let word = if is_64bit { 8 } else { 4 };
+        let header_size = if is_64bit { 64 } else { 52 };
+        let section_header_size = if is_64bit { 64 } else { 40 };
+        let symbol_size = if is_64bit { 24 } else { 16 };
+
+        // section names, symbol names and symbols follow the file header
+        let section_names = b"\0.symtab\0.strtab\0.shstrtab\0".to_vec();
+        let mut symbol_names = vec![0u8];
+        let mut symtab = vec![0u8; symbol_size];
+        for (name, address, size, info) in symbols {
+            let name_offset = symbol_names.len() as u64;
+            symbol_names.extend(name.as_bytes());
+            symbol_names.push(0);
+            write(&mut symtab, name_offset, 4);
+            if is_64bit {
+                symtab.extend([*info, 0]);
+                write(&mut symtab, 1, 2);
+                write(&mut symtab, *address, 8);
+                write(&mut symtab, *size, 8);
+            } else {
+                write(&mut symtab, *address, 4);
+                write(&mut symtab, *size, 4);
+                symtab.extend([*info, 0]);
+                write(&mut symtab, 1, 2);
+            }
+        }
+        let names_offset = header_size as u64;
+        let strtab_offset = names_offset + section_names.len() as u64;
+        let symtab_offset = strtab_offset + symbol_names.len() as u64;
+        let section_headers_offset = symtab_offset + symtab.len() as u64;
+
+        let mut data = b"\x7fELF".to_vec();
+        data.extend([if is_64bit { 2 } else { 1 }, if big_endian { 2 } else { 1 }, 1]);
+        data.resize(16, 0);
+        write(&mut data, 2, 2);
+        write(&mut data, 0, 2);
+        write(&mut data, 1, 4);
+        write(&mut data, 0, word);
+        write(&mut data, 0, word);
+        write(&mut data, section_headers_offset, word);
+        write(&mut data, 0, 4);
+        write(&mut data, header_size as u64, 2);
+        write(&mut data, 0, 2);
+        write(&mut data, 0, 2);
+        write(&mut data, section_header_size as u64, 2);
+        write(&mut data, 4, 2);
+        write(&mut data, 3, 2);
+        data.extend(&section_names);
+        data.extend(&symbol_names);
+        data.extend(&symtab);
+
+        // null section, .symtab, .strtab and .shstrtab: name, type, offset, size, link
+        let sections = [
+            (0, 0, 0, 0, 0),
+            (1, SHT_SYMTAB, symtab_offset, symtab.len() as u64, 2),
+            (9, 3, strtab_offset, symbol_names.len() as u64, 0),
+            (17, 3, names_offset, section_names.len() as u64, 0),
+        ];
+        for (name, section_type, offset, size, link) in sections {
+            write(&mut data, name, 4);
+            write(&mut data, section_type, 4);
+            write(&mut data, 0, word);
+            write(&mut data, 0, word);
+            write(&mut data, offset, word);
+            write(&mut data, size, word);
+            write(&mut data, link, 4);
+            write(&mut data, 0, 4);
+            write(&mut data, 0, word);
+            write(&mut data, symbol_size as u64, word);
+        }
+        data
+    }
+
+    #[test]
+    fn test_read_symbols() {
+        // info: global object 0x11, local object 0x01, global function 0x12
+        let symbols = [
+            ("engine", 0x2000_0010, 12, 0x11),
+            ("counter", 0x2000_0000, 4, 0x01),
+            ("counter", 0x2000_0004, 4, 0x11),
+            ("counter", 0x2000_0008, 4, 0x01),
+            ("main", 0x0800_0000, 64, 0x12),
+        ];
+        for (is_64bit, big_endian) in [(false, false), (false, true), (true, false), (true, true)] {
+            let elf = ElfFile::parse(build_elf(is_64bit, big_endian, &symbols)).unwrap();
+            assert_eq!(elf.symbols().len(), 2);
+            assert_eq!(
+                elf.symbols()["engine"],
+                LinkerSymbol {
+                    address: 0x2000_0010,
+                    size: 12
+                }
+            );
+            assert_eq!(elf.symbols()["counter"].address, 0x2000_0004);
+            assert_eq!(elf.sections[1].name, ".symtab");
+            assert_eq!(elf.section_data(".shstrtab"), Some(&b"\0.symtab\0.strtab\0.shstrtab\0"[..]));
+            assert_eq!(elf.section_data(".debug_info"), None);
+        }
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert_eq!(ElfFile::parse(b"MZ\x90\x00".to_vec()).unwrap_err(), "not an ELF file");
+        let mut data = build_elf(false, false, &[]);
+        data.truncate(100);
+        assert!(ElfFile::parse(data).unwrap_err().starts_with("unexpected end of file"));
+        // a section header offset close to the end of the address space
+        let mut data = build_elf(true, false, &[]);
+        data[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(ElfFile::parse(data).is_err());
+        assert!(ElfFile::load("does_not_exist.elf").is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use thiserror::Error;
+
+// position in a source file, line and column start at 1
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    pub fn new(file: &str, line: usize, column: usize) -> Self {
+        SourceLocation {
+            file: file.to_string(),
+            line,
+            column,
+        }
+    }
+
+    // get the location of a position inside a text which starts at this location
+    pub fn advance(&self, text_line: usize, text_column: usize) -> Self {
+        if text_line == 0 {
+            SourceLocation::new(&self.file, self.line, self.column + text_column)
+        } else {
+            SourceLocation::new(&self.file, self.line + text_line, text_column + 1)
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum GeneratorError {
+    #[error("{file}: unable to read: {message}")]
+    Read { file: String, message: String },
+
+    #[error("{location}: syntax error: {message}")]
+    Syntax {
+        location: SourceLocation,
+        message: String,
+    },
+
+    #[error("{location}: invalid preprocessor directive: {message}")]
+    Preprocessor {
+        location: SourceLocation,
+        message: String,
+    },
+
+    #[error("{location}: invalid declaration: {message}")]
+    Declaration {
+        location: SourceLocation,
+        message: String,
+    },
+
+    #[error("{location}: invalid reference: {message}")]
+    Reference {
+        location: SourceLocation,
+        message: String,
+    },
+
+    #[error("{location}: unresolved address: {message}")]
+    Address {
+        location: SourceLocation,
+        message: String,
+    },
+
+    #[error("{file}: unable to write: {message}")]
+    Write { file: String, message: String },
+}
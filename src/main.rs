@@ -1,130 +1,220 @@
-use regex::Regex;
-use tree_sitter::{Node, Parser};
-use tree_sitter_c::LANGUAGE;
-use a2lfile::*;
-
 mod a2l_code_comment;
+mod a2l_comment_parser;
 mod a2l_comment_generator;
+mod a2l_merge;
+mod a2l_seed;
+mod c_types;
+mod cli;
 mod code_parser;
+mod diagnostics;
+mod dwarf;
+mod elf;
+mod error;
+mod map_file;
+mod preprocessor;
+#[cfg(test)]
+mod test_support;
+
+use std::process::ExitCode;
 
-use a2l_code_comment::*;
 use a2l_comment_generator::*;
+use cli::*;
 use code_parser::*;
+use dwarf::DebugInfo;
+use elf::ElfFile;
+use diagnostics::Diagnostic;
+use error::GeneratorError;
 
-fn check_children(node: &Node, code: &str) {
-    let mut cursor = node.walk();
-    for child in node.children(&mut cursor) {
-        if child.kind() == "comment" {
-            let comment_text = get_node_text(&child, code);
-            println!("Comment: {}", comment_text);
-        } else if child.kind() == "declaration" {
-            let declaration_text = get_node_text(&child, code);
-            println!("Declaration: {}", declaration_text);
+fn main() -> ExitCode {
+    let options = match CliOptions::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
         }
+    };
+    if options.show_help {
+        print!("{}", USAGE);
+        return ExitCode::SUCCESS;
     }
-}
-fn main() {
 
-    let file_path = "test_file.c";
-    // read in file
-    let code = std::fs::read_to_string(file_path).expect("Unable to read file");
-    // parse the code
-    let mut parser = Parser::new();
-    let language = tree_sitter_c::LANGUAGE;
-    parser
-        .set_language(&language.into())
-        .expect("Error loading C parser");
-    let tree = parser.parse(&code, None).unwrap(); // Pass a reference to `code`
-    assert!(!tree.root_node().has_error());
+    // errors are collected so that one broken file doesn't stop the whole run
+    let mut errors = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut code_parser = CodeParser::new();
+    // a patch written to stdout must not be mixed with the messages of the parser
+    code_parser.set_verbose(options.verbosity == Verbosity::Verbose && options.output_path != "-");
+    // the reverse mode also needs the declarations which have no annotation yet
+    code_parser.set_all_declarations(options.seed_path.is_some());
+    for (name, value) in &options.defines {
+        code_parser.add_define(name.clone(), value.clone());
+    }
+    for include_path in &options.include_paths {
+        code_parser.add_include_path(include_path.clone());
+    }
+    for input_path in &options.input_paths {
+        if let Err(error) = code_parser.add_file_path(input_path.clone()) {
+            errors.push(error);
+        }
+    }
+    let vec_found = code_parser.parse_files(&mut errors, &mut diagnostics);
+    if let Some(seed_path) = &options.seed_path {
+        return seed(&options, seed_path, &vec_found, errors, diagnostics);
+    }
 
-    let mut cursor = tree.root_node().walk(); // Make the cursor mutable
-    let mut valid_area = false;
-    let mut comment_str = String::new();
-    let mut vec_found: Vec<(String, String)> = Vec::new(); // Vector of tuples to store comments and declarations
+    if options.verbosity >= Verbosity::Normal {
+        println!("Found {} declarations with comments", vec_found.len());
+    }
+    let mut generator = A2lCommentGenerator::new(&options.project_name, &options.module_name);
+    generator.set_typedef_structures(options.typedef_structures);
+    generator.set_legacy_arrays(options.legacy_arrays);
+    generator.set_target(options.target);
+    // the firmware is read before the declarations are added, its debug info is checked with each object
+    let elf = options.elf_path.as_ref().and_then(|elf_path| match ElfFile::load(elf_path) {
+        Ok(elf) => Some(elf),
+        Err(error) => {
+            errors.push(error);
+            None
+        }
+    });
+    if options.check_dwarf
+        && let (Some(elf), Some(elf_path)) = (&elf, &options.elf_path)
+    {
+        match DebugInfo::load(elf) {
+            Ok(debug_info) => generator.set_debug_info(debug_info, options.prefer_dwarf),
+            Err(message) => errors.push(GeneratorError::Read {
+                file: elf_path.clone(),
+                message,
+            }),
+        }
+    }
+    for declaration in vec_found.iter() {
+        if options.verbosity == Verbosity::Verbose {
+            println!("{}: found declaration: {}", declaration.location, declaration.declaration);
+        }
+        if let Err(error) = generator.add_declaration(declaration, &mut diagnostics) {
+            errors.push(error);
+        }
+    }
 
-    let compiler_options = vec!["TEST", "ENABLE", "ENABLE_TEST"];
+    generator.finish(&mut errors);
+    if let Some(elf) = &elf {
+        generator.set_addresses(elf.symbols(), &mut errors, &mut diagnostics);
+    }
+    if let Some(map_path) = &options.map_path {
+        match map_file::load(map_path, options.map_format.as_deref()) {
+            Ok(symbols) => generator.set_addresses(&symbols, &mut errors, &mut diagnostics),
+            Err(error) => errors.push(error),
+        }
+    }
 
-    for child in tree.root_node().children(&mut cursor) {
-        // Pass a mutable reference
-        if child.kind() == "comment" {
-            let comment_text = get_node_text(&child, &code);
-            println!("Comment: {}", comment_text);
-            if comment_text.contains("a2l on") {
-                valid_area = true;
+    // the objects are either merged into an existing file or written to a new one
+    // after an error some objects may be missing, they must not be taken for stale objects then
+    let mut merge_report = None;
+    let remove_stale = errors.is_empty();
+    match &options.merge_path {
+        Some(merge_path) => match a2l_merge::merge_file(
+            merge_path,
+            generator.a2l_file(),
+            &options.output_path,
+            remove_stale,
+            &mut diagnostics,
+        ) {
+            Ok(report) => merge_report = Some(report),
+            Err(error) => errors.push(error),
+        },
+        None => {
+            if let Err(error) = generator.write(&options.output_path) {
+                errors.push(error);
             }
-            if valid_area {
-                comment_str.push_str(&format!("{}\n", comment_text)); // Add a newline after each comment
-            }
-        } else if child.kind() == "declaration"
-            && child.child_by_field_name("declarator").unwrap().kind() == "init_declarator"
-        {
-            let declaration_text = get_node_text(&child, &code);
-            if valid_area {
-                println!("Declaration: {}", declaration_text);
-                // Save comment string and declaration string as a tuple to vec_found
-                vec_found.push((comment_str.clone(), declaration_text.clone()));
-                // Clear comment string
-                comment_str.clear();
-            }
-        } else if child.kind() == "preproc_ifdef" {
-            let preproc_ifdef_name = child.child_by_field_name("name");
-            let preproc_ifdef_name_text = get_node_text(&preproc_ifdef_name.unwrap(), &code);
-            println!("Found preprocessor directive: {}", preproc_ifdef_name_text);
-            if compiler_options.contains(&preproc_ifdef_name_text.as_str()) {
-                check_children(&child, &code);
-            } else {
-                // search for alternative
-                let alternative_node = child
-                    .child_by_field_name("alternative")
-                    .unwrap();
-                let alternative_text = get_node_text(&alternative_node, &code);
-                println!("Found alternative: {}", alternative_text);
+        }
+    }
+    print_problems(&options, &errors, &diagnostics);
+    if let Some(report) = &merge_report
+        && options.verbosity >= Verbosity::Normal
+    {
+        if options.verbosity == Verbosity::Verbose {
+            for (change, names) in [("added", &report.added), ("updated", &report.updated), ("removed", &report.removed)] {
+                for name in names {
+                    println!("{} {}", change, name);
+                }
             }
-        } else {
-            valid_area = false;
-            comment_str.clear();
         }
+        println!(
+            "Merged into {}: {} added, {} updated, {} unchanged and {} removed objects",
+            options.output_path,
+            report.added.len(),
+            report.updated.len(),
+            report.unchanged.len(),
+            report.removed.len()
+        );
+    } else if options.verbosity >= Verbosity::Normal {
+        let module = &generator.a2l_file().project.module[0];
+        println!(
+            "Written {} measurements, {} characteristics and {} instances to {}",
+            module.measurement.len(),
+            module.characteristic.len(),
+            module.instance.len(),
+            options.output_path
+        );
     }
-    // Print vec_found
-    println!("\nFound {} declarations with comments:\n", vec_found.len());
-    for (comment, declaration) in vec_found.iter() {
-        println!("Found comment: \n{}", comment);
-        println!("Found declaration: \n{}", declaration);
-    } 
-
+    exit_code(&options, &errors, &diagnostics)
+}
 
-    let comment = r#"
-    a2l on
-    a2l-type InvalidType
-    a2l-characteristic-type InvalidType
-    a2l-description This is a test description
-    a2l-min invalid_value
-    a2l-max invalid_value
-    a2l-linear-coeffs invalid_value
-    a2l-rat-func-coeffs invalid_value
-    a2l-display-identifier TestIdentifier
-    a2l-group TestGroup
-    a2l-max-refresh 50ms
-    a2l-read-only
-    a2l-unit °C
-    "#;
+// reverse mode: annotate the declarations with the objects of an existing a2l file
+// the patch may be written to stdout, so the messages go to stderr
+fn seed(
+    options: &CliOptions,
+    seed_path: &str,
+    declarations: &[AnnotatedDeclaration],
+    mut errors: Vec<GeneratorError>,
+    mut diagnostics: Vec<Diagnostic>,
+) -> ExitCode {
+    let report = a2l_seed::seed_file(seed_path, declarations, &options.output_path, options.in_place, &mut diagnostics);
+    let report = report.unwrap_or_else(|error| {
+        errors.push(error);
+        a2l_seed::SeedReport::default()
+    });
+    print_problems(options, &errors, &diagnostics);
+    if options.verbosity == Verbosity::Verbose {
+        for (state, names) in [("seeded", &report.seeded), ("already annotated", &report.annotated), ("not found", &report.missing)] {
+            for name in names {
+                eprintln!("{} {}", state, name);
+            }
+        }
+    }
+    if options.verbosity >= Verbosity::Normal {
+        eprintln!(
+            "Seeded {} objects from {}: {} already annotated and {} not found in the sources",
+            report.seeded.len(),
+            seed_path,
+            report.annotated.len(),
+            report.missing.len()
+        );
+        if let Some(patch_root) = &report.patch_root {
+            eprintln!("The patch has to be applied in {}, not all sources are in the working directory", patch_root);
+        }
+    }
+    exit_code(options, &errors, &diagnostics)
+}
 
-    let a2l_code_comment = A2lCodeComment::from_comment(comment);
+// warnings are suppressed in quiet mode, unless they are treated as errors
+fn print_problems(options: &CliOptions, errors: &[GeneratorError], diagnostics: &[Diagnostic]) {
+    if options.deny_warnings || options.verbosity >= Verbosity::Normal {
+        for diagnostic in diagnostics {
+            eprintln!("{}", diagnostic.render(options.deny_warnings));
+        }
+    }
+    for error in errors {
+        eprintln!("Error: {}", error);
+    }
+}
 
-    // Assertions for invalid values
-    assert_eq!(a2l_code_comment.a2l_type, A2lType::Unknown);
-    assert_eq!(a2l_code_comment.a2l_characteristic_type, CharacteristicType::Value);
-    // Check the vaild values
-    assert!(a2l_code_comment.a2l_on);
-    assert_eq!(a2l_code_comment.a2l_description, "This is a test description");
-    assert_eq!(a2l_code_comment.a2l_min, 0.0); // Default value
-    assert_eq!(a2l_code_comment.a2l_max, 0.0); // Default value
-    assert_eq!(a2l_code_comment.a2l_linear_coeffs, "invalid_value");
-    assert_eq!(a2l_code_comment.a2l_rat_func_coeffs, "invalid_value");
-    assert_eq!(a2l_code_comment.a2l_display_identifier, "TestIdentifier");
-    assert_eq!(a2l_code_comment.a2l_group, "TestGroup");
-    assert_eq!(a2l_code_comment.a2l_max_refresh, "50ms");
-    assert!(a2l_code_comment.a2l_read_only);
-    assert!(!a2l_code_comment.a2l_read_write); // Not set in the comment
-    assert_eq!(a2l_code_comment.a2l_unit, "°C");
+fn exit_code(options: &CliOptions, errors: &[GeneratorError], diagnostics: &[Diagnostic]) -> ExitCode {
+    let denied_warnings = options.deny_warnings && !diagnostics.is_empty();
+    if !errors.is_empty() || denied_warnings {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
 }
@@ -0,0 +1,329 @@
+// reading the addresses and sizes of the variables from the map file of the linker
+// every linker writes its own format, each one is read by a MapFormat
+
+use std::collections::{HashMap, HashSet};
+
+use crate::elf::LinkerSymbol;
+use crate::error::GeneratorError;
+
+trait MapFormat {
+    // name of the format on the command line
+    fn name(&self) -> &'static str;
+    // true if the text looks like a map file written by this linker
+    fn detect(&self, text: &str) -> bool;
+    // the symbols of the map file, the size is 0 if the map doesn't tell it
+    fn parse(&self, text: &str) -> HashMap<String, LinkerSymbol>;
+}
+
+const FORMATS: [&dyn MapFormat; 3] = [&GnuMap, &IarMap, &GreenHillsMap];
+
+// read a map file, the format is detected if it isn't given
+pub fn load(file_path: &str, format_name: Option<&str>) -> Result<HashMap<String, LinkerSymbol>, GeneratorError> {
+    let read_error = |message: String| GeneratorError::Read {
+        file: file_path.to_string(),
+        message,
+    };
+    let text = std::fs::read_to_string(file_path).map_err(|error| read_error(error.to_string()))?;
+    let format = match format_name {
+        Some(format_name) => FORMATS
+            .iter()
+            .find(|format| format.name() == format_name)
+            .ok_or_else(|| read_error(format!("unknown map file format '{}'", format_name)))?,
+        None => FORMATS
+            .iter()
+            .find(|format| format.detect(&text))
+            .ok_or_else(|| read_error(format!("unknown map file format, use one of {}", format_names())))?,
+    };
+    Ok(format.parse(&text))
+}
+
+fn format_names() -> String {
+    FORMATS.map(|format| format.name()).join(", ")
+}
+
+// parse a hex number like 0x2000'0010 or 20000010
+fn parse_hex(text: &str) -> Option<u64> {
+    let digits = text.strip_prefix("0x").unwrap_or(text).replace('\'', "");
+    u64::from_str_radix(&digits, 16).ok()
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// GNU ld, written with -Map
+// the symbol lines only have an address, the size is taken from the input section of the variable,
+// which is named like ".bss.engine" if the code was compiled with -fdata-sections
+struct GnuMap;
+
+impl MapFormat for GnuMap {
+    fn name(&self) -> &'static str {
+        "gnu"
+    }
+
+    fn detect(&self, text: &str) -> bool {
+        text.contains("Linker script and memory map")
+    }
+
+    fn parse(&self, text: &str) -> HashMap<String, LinkerSymbol> {
+        let mut symbols = HashMap::new();
+        // name, address and size of the current input section
+        let mut section: Option<(String, u64, u64)> = None;
+        let mut section_name: Option<String> = None;
+        // the discarded sections are listed before the memory map
+        let Some((_, memory_map)) = text.split_once("Linker script and memory map") else {
+            return symbols;
+        };
+        for line in memory_map.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            // a long section name is on a line of its own, followed by address, size and file
+            if let Some(name) = section_name.take()
+                && let [address, size, ..] = tokens[..]
+                && let (Some(address), Some(size)) = (parse_hex(address), parse_hex(size))
+            {
+                section = Some((name, address, size));
+                continue;
+            }
+            match tokens[..] {
+                [name] if line.starts_with(' ') && name.starts_with('.') => section_name = Some(name.to_string()),
+                [name, address, size, ..] if line.starts_with(' ') && name.starts_with('.') => {
+                    if let (Some(address), Some(size)) = (parse_hex(address), parse_hex(size)) {
+                        section = Some((name.to_string(), address, size));
+                    }
+                }
+                [address, name] if address.starts_with("0x") && is_identifier(name) => {
+                    let Some(address) = parse_hex(address) else {
+                        continue;
+                    };
+                    let size = match &section {
+                        Some((section, section_address, size))
+                            if *section_address == address && section.ends_with(&format!(".{}", name)) =>
+                        {
+                            *size
+                        }
+                        _ => 0,
+                    };
+                    symbols.insert(name.to_string(), LinkerSymbol { address, size });
+                }
+                _ => {}
+            }
+        }
+        symbols
+    }
+}
+
+// IAR ILINK, the ENTRY LIST at the end of the map
+struct IarMap;
+
+impl MapFormat for IarMap {
+    fn name(&self) -> &'static str {
+        "iar"
+    }
+
+    fn detect(&self, text: &str) -> bool {
+        text.contains("IAR ELF Linker") || text.contains("*** ENTRY LIST")
+    }
+
+    fn parse(&self, text: &str) -> HashMap<String, LinkerSymbol> {
+        let mut symbols = HashMap::new();
+        let Some((_, entries)) = text.split_once("*** ENTRY LIST") else {
+            return symbols;
+        };
+        // a long name is on a line of its own, followed by the other columns
+        let mut long_name: Option<String> = None;
+        let mut globals = HashSet::new();
+        for line in entries.lines() {
+            let mut tokens: Vec<&str> = line.split_whitespace().collect();
+            if let [name] = tokens[..]
+                && is_identifier(name)
+            {
+                long_name = Some(name.to_string());
+                continue;
+            }
+            let name = long_name.take();
+            if let Some(name) = &name {
+                tokens.insert(0, name);
+            }
+            // name, address, size (missing for labels), type, scope and object file
+            let [name, address, rest @ ..] = &tokens[..] else {
+                continue;
+            };
+            let (size, rest) = match rest.split_first() {
+                Some((size, rest)) if size.starts_with("0x") => (parse_hex(size), rest),
+                _ => (Some(0), rest),
+            };
+            // a global symbol wins over a local one of the same name
+            let is_global = rest.get(1) != Some(&"Lc");
+            if let (Some(address), Some(size), Some(&"Data")) = (parse_hex(address), size, rest.first())
+                && is_identifier(name)
+                && (is_global || !globals.contains(*name))
+            {
+                symbols.insert(name.to_string(), LinkerSymbol { address, size });
+                if is_global {
+                    globals.insert(name.to_string());
+                }
+            }
+        }
+        symbols
+    }
+}
+
+// Green Hills elxr, the symbol lists look like " .bss  20000010+000010 engine"
+struct GreenHillsMap;
+
+impl MapFormat for GreenHillsMap {
+    fn name(&self) -> &'static str {
+        "ghs"
+    }
+
+    fn detect(&self, text: &str) -> bool {
+        text.contains("Global Symbols (sorted")
+    }
+
+    fn parse(&self, text: &str) -> HashMap<String, LinkerSymbol> {
+        let mut symbols = HashMap::new();
+        let mut is_global = false;
+        let mut globals = Vec::new();
+        for line in text.lines() {
+            if line.starts_with("Global Symbols") {
+                is_global = true;
+            } else if line.starts_with("Local Symbols") {
+                is_global = false;
+            }
+            let [section, location, name] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                continue;
+            };
+            let Some((address, size)) = location.split_once('+') else {
+                continue;
+            };
+            // a global symbol wins over a local one of the same name
+            if !section.starts_with('.') || !is_identifier(name) || (!is_global && globals.contains(&name)) {
+                continue;
+            }
+            if let (Some(address), Some(size)) = (parse_hex(address), parse_hex(size)) {
+                symbols.insert(name.to_string(), LinkerSymbol { address, size });
+                if is_global {
+                    globals.push(name);
+                }
+            }
+        }
+        symbols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    const GNU_MAP: &str = "\
+Archive member included to satisfy reference by file (symbol)
+
+Discarded input sections
+
+ .bss.unused    0x00000000        0x4 ./src/main.o
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+RAM              0x20000000         0x00020000         xrw
+
+Linker script and memory map
+
+ .data          0x20000000        0x2 ./src/main.o
+                0x20000000                limit
+ .bss.engine    0x20000010       0x10 ./src/main.o
+                0x20000010                engine
+ .bss.a_very_long_variable_name
+                0x20000020        0x4 ./src/main.o
+                0x20000020                a_very_long_variable_name
+                0x20000024                . = ALIGN (0x4)
+                0x20000024                _ebss = .
+";
+
+    const IAR_MAP: &str = "\
+###############################################################################
+#
+# IAR ELF Linker V9.30.1.335/W64 for ARM                  01/Jan/2024  12:00:00
+#
+###############################################################################
+
+*******************************************************************************
+*** ENTRY LIST
+***
+
+Entry                       Address   Size  Type      Object
+-----                       -------   ----  ----      ------
+engine                  0x2000'0010   0x10  Data  Gb  main.o [1]
+limit                   0x2000'0000    0x2  Data  Gb  main.o [1]
+main                    0x0000'0101   0x20  Code  Gb  main.o [1]
+a_very_long_variable_name_which_wraps
+                        0x2000'0020    0x4  Data  Lc  main.o [1]
+engine                  0x2000'0100    0x4  Data  Lc  other.o [1]
+__vector_table          0x0000'0000         Data  Gb  startup.o [1]
+";
+
+    const GHS_MAP: &str = "\
+Green Hills Software, MULTI v7.1.4
+Load Map Mon Jan 01 12:00:00 2024
+
+Global Symbols (sorted alphabetically)
+
+ .bss                 20000010+000010 engine
+ .data                20000000+000002 limit
+ .text                00000100+000020 main
+
+Local Symbols (sorted alphabetically)
+
+ .bss                 20000030+000004 engine
+ .bss                 20000020+000004 counter
+";
+
+    fn symbol(address: u64, size: u64) -> LinkerSymbol {
+        LinkerSymbol { address, size }
+    }
+
+    #[test]
+    fn test_gnu_map() {
+        assert!(GnuMap.detect(GNU_MAP));
+        assert!(!IarMap.detect(GNU_MAP) && !GreenHillsMap.detect(GNU_MAP));
+        let symbols = GnuMap.parse(GNU_MAP);
+        assert_eq!(symbols.len(), 3);
+        // the size is only known if the variable has a section of its own
+        assert_eq!(symbols["limit"], symbol(0x2000_0000, 0));
+        assert_eq!(symbols["engine"], symbol(0x2000_0010, 0x10));
+        assert_eq!(symbols["a_very_long_variable_name"], symbol(0x2000_0020, 4));
+    }
+
+    #[test]
+    fn test_iar_map() {
+        assert!(IarMap.detect(IAR_MAP));
+        let symbols = IarMap.parse(IAR_MAP);
+        assert_eq!(symbols.len(), 4);
+        assert_eq!(symbols["engine"], symbol(0x2000_0010, 0x10));
+        assert_eq!(symbols["a_very_long_variable_name_which_wraps"], symbol(0x2000_0020, 4));
+        assert_eq!(symbols["__vector_table"], symbol(0, 0));
+        assert!(!symbols.contains_key("main"));
+    }
+
+    #[test]
+    fn test_green_hills_map() {
+        assert!(GreenHillsMap.detect(GHS_MAP));
+        let symbols = GreenHillsMap.parse(GHS_MAP);
+        assert_eq!(symbols["engine"], symbol(0x2000_0010, 0x10));
+        assert_eq!(symbols["limit"], symbol(0x2000_0000, 2));
+        assert_eq!(symbols["counter"], symbol(0x2000_0020, 4));
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = TestDir::new("a2l_map_file_test_load");
+        let file = dir.write("firmware.map", IAR_MAP);
+        assert_eq!(load(&file, None).unwrap()["limit"], symbol(0x2000_0000, 2));
+        assert!(load(&file, Some("gnu")).unwrap().is_empty());
+        assert!(load(&file, Some("keil")).unwrap_err().to_string().contains("unknown map file format 'keil'"));
+        std::fs::write(&file, "no map").unwrap();
+        assert!(load(&file, None).unwrap_err().to_string().ends_with("use one of gnu, iar, ghs"));
+    }
+}
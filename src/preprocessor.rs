@@ -0,0 +1,315 @@
+// evaluation of the constant expressions in #if and #elif directives and in declarations,
+// e.g. array sizes and enumerator values
+
+use crate::c_types::SymbolTable;
+use crate::code_parser::parse_c_integer;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Operator(&'static str),
+}
+
+// longer operators first, so that "<<" isn't read as two "<"
+const OPERATORS: [&str; 25] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "(", ")", "!", "~", "-", "+", "*", "/", "%", "<", ">",
+    "&", "^", "|", "?", ":", ",",
+];
+
+// value of a #if or #elif condition, identifiers which are no macros are 0
+pub fn evaluate_condition(expression: &str, symbols: &SymbolTable) -> Result<i64, String> {
+    evaluate(expression, symbols, &|_| Some(0))
+}
+
+// value of a constant expression, macros are expanded and other identifiers are resolved by identifier_value
+pub fn evaluate(
+    expression: &str,
+    symbols: &SymbolTable,
+    identifier_value: &dyn Fn(&str) -> Option<i64>,
+) -> Result<i64, String> {
+    let tokens = expand(tokenize(expression)?, symbols, &mut Vec::new())?;
+    let mut parser = ExpressionParser {
+        tokens,
+        position: 0,
+        evaluating: true,
+        identifier_value,
+    };
+    let value = parser.parse_expression()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected {} in '{}'", describe(token), expression.trim())),
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    // a line continuation of a multi-line directive is whitespace
+    let chars: Vec<char> = expression.replace("\\\r\n", " ").replace("\\\n", " ").chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < chars.len() {
+        let rest: String = chars[position..].iter().take(2).collect();
+        if chars[position].is_whitespace() {
+            position += 1;
+        } else if rest == "//" {
+            break;
+        } else if rest == "/*" {
+            let text: String = chars[position..].iter().collect();
+            let end = text.find("*/").ok_or("unterminated comment")?;
+            position += text[..end + 2].chars().count();
+        } else if chars[position].is_ascii_alphanumeric() || chars[position] == '_' {
+            let start = position;
+            while position < chars.len() && (chars[position].is_ascii_alphanumeric() || chars[position] == '_') {
+                position += 1;
+            }
+            let word: String = chars[start..position].iter().collect();
+            if chars[start].is_ascii_digit() {
+                let value = parse_c_integer(&word).ok_or_else(|| format!("invalid number '{}'", word))?;
+                tokens.push(Token::Number(value as i64));
+            } else {
+                tokens.push(Token::Identifier(word));
+            }
+        } else if chars[position] == '\'' {
+            // a character constant like 'A', escapes aren't supported
+            if position + 2 < chars.len() && chars[position + 1] != '\\' && chars[position + 2] == '\'' {
+                tokens.push(Token::Number(chars[position + 1] as i64));
+                position += 3;
+            } else {
+                return Err("unsupported character constant".to_string());
+            }
+        } else if let Some(operator) = OPERATORS.iter().find(|operator| rest.starts_with(**operator)) {
+            tokens.push(Token::Operator(operator));
+            position += operator.len();
+        } else {
+            return Err(format!("unexpected character '{}'", chars[position]));
+        }
+    }
+    Ok(tokens)
+}
+
+// replace macros by their values and "defined X" by 0 or 1
+// expanding lists the macros which are currently replaced, a macro isn't expanded inside itself
+fn expand(tokens: Vec<Token>, symbols: &SymbolTable, expanding: &mut Vec<String>) -> Result<Vec<Token>, String> {
+    let mut expanded = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let Token::Identifier(name) = &token else {
+            expanded.push(token);
+            continue;
+        };
+        if name == "defined" {
+            let parenthesized = tokens.next_if_eq(&Token::Operator("(")).is_some();
+            let Some(Token::Identifier(operand)) = tokens.next() else {
+                return Err("expected a macro name after 'defined'".to_string());
+            };
+            if parenthesized && tokens.next() != Some(Token::Operator(")")) {
+                return Err(format!("expected ')' after 'defined({}'", operand));
+            }
+            expanded.push(Token::Number(symbols.is_defined(&operand) as i64));
+        } else if expanding.contains(name) {
+            expanded.push(token);
+        } else if let Some(value) = symbols.macro_value(name) {
+            expanding.push(name.clone());
+            expanded.extend(expand(tokenize(value)?, symbols, expanding)?);
+            expanding.pop();
+        } else if symbols.is_defined(name) {
+            return Err(format!("function-like macro '{}' is not supported", name));
+        } else {
+            expanded.push(token);
+        }
+    }
+    Ok(expanded)
+}
+
+fn describe(token: &Token) -> String {
+    let text = match token {
+        Token::Number(value) => value.to_string(),
+        Token::Identifier(name) => name.clone(),
+        Token::Operator(operator) => operator.to_string(),
+    };
+    format!("'{}'", text)
+}
+
+// binding of the binary operators, higher binds stronger
+fn precedence(operator: &str) -> Option<u8> {
+    match operator {
+        "*" | "/" | "%" => Some(10),
+        "+" | "-" => Some(9),
+        "<<" | ">>" => Some(8),
+        "<" | "<=" | ">" | ">=" => Some(7),
+        "==" | "!=" => Some(6),
+        "&" => Some(5),
+        "^" => Some(4),
+        "|" => Some(3),
+        "&&" => Some(2),
+        "||" => Some(1),
+        _ => None,
+    }
+}
+
+struct ExpressionParser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    // false in the operand of &&, || or ?: which isn't taken, it is parsed but not evaluated like in C
+    evaluating: bool,
+    identifier_value: &'a dyn Fn(&str) -> Option<i64>,
+}
+
+impl ExpressionParser<'_> {
+    fn next_operator(&mut self, operator: &str) -> bool {
+        if matches!(self.tokens.get(self.position), Some(Token::Operator(next)) if *next == operator) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // parse an operand which is skipped if skip is set, e.g. the right side of 0 && x
+    fn parse_operand(
+        &mut self,
+        skip: bool,
+        parse: impl FnOnce(&mut Self) -> Result<i64, String>,
+    ) -> Result<i64, String> {
+        let evaluating = self.evaluating;
+        self.evaluating = evaluating && !skip;
+        let value = parse(self);
+        self.evaluating = evaluating;
+        value
+    }
+
+    // conditional expression: condition ? a : b
+    fn parse_expression(&mut self) -> Result<i64, String> {
+        let condition = self.parse_binary(1)?;
+        if !self.next_operator("?") {
+            return Ok(condition);
+        }
+        let if_true = self.parse_operand(condition == 0, Self::parse_expression)?;
+        if !self.next_operator(":") {
+            return Err("expected ':' in conditional expression".to_string());
+        }
+        let if_false = self.parse_operand(condition != 0, Self::parse_expression)?;
+        Ok(if condition != 0 { if_true } else { if_false })
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<i64, String> {
+        let mut left = self.parse_unary()?;
+        while let Some(Token::Operator(operator)) = self.tokens.get(self.position)
+            && let Some(operator_precedence) = precedence(operator)
+            && operator_precedence >= min_precedence
+        {
+            let operator = *operator;
+            self.position += 1;
+            let skip = (operator == "&&" && left == 0) || (operator == "||" && left != 0);
+            let right = self.parse_operand(skip, |parser| parser.parse_binary(operator_precedence + 1))?;
+            left = if self.evaluating { apply(operator, left, right)? } else { 0 };
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<i64, String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Identifier(_)) if !self.evaluating => Ok(0),
+            Some(Token::Identifier(name)) => {
+                (self.identifier_value)(&name).ok_or_else(|| format!("unknown identifier '{}'", name))
+            }
+            Some(Token::Operator("(")) => {
+                let value = self.parse_expression()?;
+                if self.next_operator(")") {
+                    Ok(value)
+                } else {
+                    Err("expected ')'".to_string())
+                }
+            }
+            Some(Token::Operator("!")) => Ok((self.parse_unary()? == 0) as i64),
+            Some(Token::Operator("~")) => Ok(!self.parse_unary()?),
+            Some(Token::Operator("-")) => Ok(self.parse_unary()?.wrapping_neg()),
+            Some(Token::Operator("+")) => self.parse_unary(),
+            Some(token) => Err(format!("unexpected {}", describe(&token))),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn apply(operator: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match operator {
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("division by zero".to_string()),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "&" => left & right,
+        "^" => left ^ right,
+        "|" => left | right,
+        "&&" => (left != 0 && right != 0) as i64,
+        "||" => (left != 0 || right != 0) as i64,
+        _ => return Err(format!("unsupported operator '{}'", operator)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols() -> SymbolTable {
+        let mut symbols = SymbolTable::default();
+        symbols.add_macro("VARIANT", "2");
+        symbols.add_macro("SIZE", "(0x10u)");
+        symbols.add_macro("DOUBLE_SIZE", "SIZE * 2");
+        symbols.add_macro("EMPTY", "");
+        symbols.add_macro("LOOP", "LOOP + 1");
+        symbols.add_function_macro("MAX");
+        symbols
+    }
+
+    #[test]
+    fn test_conditions() {
+        let symbols = symbols();
+        let condition = |expression: &str| evaluate_condition(expression, &symbols);
+        assert_eq!(condition("VARIANT == 2"), Ok(1));
+        assert_eq!(condition("defined(VARIANT) && !defined UNKNOWN"), Ok(1));
+        assert_eq!(condition("defined EMPTY && defined(MAX)"), Ok(1));
+        assert_eq!(condition("UNKNOWN"), Ok(0));
+        // the operand which isn't taken is not evaluated
+        assert_eq!(condition("VARIANT > 1 || UNKNOWN / 0"), Ok(1));
+        assert_eq!(condition("defined(UNKNOWN) && 100 / UNKNOWN"), Ok(0));
+        assert_eq!(condition("VARIANT ? 1 : 1 / 0"), Ok(1));
+        assert_eq!(condition("VARIANT < 1 || 1 / 0"), Err("division by zero".to_string()));
+        assert_eq!(condition("VARIANT == 2 && \\\n    defined(SIZE)"), Ok(1));
+        assert!(condition("# VARIANT").is_err());
+        assert_eq!(condition("(VARIANT + 1) * 2 - 6 % 4 == 4 /* comment */"), Ok(1));
+        assert_eq!(condition("1 << 4 | 1 ^ 3 & ~0"), Ok(18));
+        assert_eq!(condition("VARIANT >= 3 ? 10 : -10"), Ok(-10));
+        assert_eq!(condition("'A' == 65 // comment"), Ok(1));
+        assert_eq!(condition("LOOP"), Ok(1));
+        assert_eq!(condition("MAX(1, 2)"), Err("function-like macro 'MAX' is not supported".to_string()));
+        assert!(condition("VARIANT ==").is_err());
+        assert!(condition("(1").is_err());
+        assert!(condition("1 2").is_err());
+    }
+
+    #[test]
+    fn test_constants() {
+        let symbols = symbols();
+        let count = |name: &str| if name == "COUNT" { Some(3) } else { None };
+        assert_eq!(evaluate("DOUBLE_SIZE", &symbols, &count), Ok(32));
+        assert_eq!(evaluate("COUNT + 1", &symbols, &count), Ok(4));
+        assert_eq!(
+            evaluate("sizeof(int)", &symbols, &count),
+            Err("unknown identifier 'sizeof'".to_string())
+        );
+        assert_eq!(evaluate("COUNT > 2 ? 1 : UNKNOWN", &symbols, &count), Ok(1));
+    }
+}